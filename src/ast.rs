@@ -1,28 +1,87 @@
+// 语句和表达式的节点比声明大得多; AST只建一次, 不为了large_enum_variant到处加Box
+#![allow(clippy::large_enum_variant)]
 
+// 源代码里的一段, 是预处理之后的文本里的字节偏移, 左闭右开
+// 用SourceMap换成文件名, 行号和列号
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub lo: usize,
+    pub hi: usize,
+}
+
+impl Span {
+    pub fn new(lo: usize, hi: usize) -> Self {
+        return Span { lo, hi };
+    }
+}
 
 #[derive(Debug)]
 pub struct CompUnit {
-    pub func_def : FuncDef,
+    pub global_items : Vec<GlobalItem>,
+}
+
+#[derive(Debug)]
+pub enum GlobalItem {
+    Decl(Decl),
+    FuncDef(FuncDef),
+    FuncDecl(FuncDecl),
+    StructDef(StructDef),
+}
+
+// struct S { int a; float b[4]; };
+#[derive(Debug)]
+pub struct StructDef {
+    pub ident: String,
+    pub fields: Vec<StructField>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub struct StructField {
+    pub btype: BType,
+    pub ident: String,
+    pub dims: Vec<ConstExp>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct FuncDef {
     pub func_type: FuncType,
     pub ident: String,
+    pub func_f_params: Vec<FuncFParam>,
     pub block: Block,
+    pub span: Span,
 }
 
+// int f(int a[], float); 只有声明, 定义在别的文件里或者后面
+#[derive(Debug)]
+pub struct FuncDecl {
+    pub func_type: FuncType,
+    pub ident: String,
+    pub func_f_params: Vec<FuncFParam>,
+    pub span: Span,
+}
 
 #[derive(Debug)]
+pub struct FuncFParam {
+    pub btype: BType,
+    pub ident: String, // 声明里可以省略名字, 这时是空的
+    pub dims: Option<Vec<ConstExp>>, // 数组形参, 第一维是空的 []
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum FuncType {
     Int,
     Void,
+    Float,
 }
 
 
 #[derive(Debug)]
 pub struct Block {
     pub block_items: Vec<BlockItem>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
@@ -31,21 +90,21 @@ pub enum BlockItem {
     Stmt(Stmt),
 }
 
-
 #[derive(Debug)]
 pub enum BasicStmt {
-    Return(Exp),
-    Assgn(LVal, Exp),
-    Exp(Option<Exp>),
+    Return(Option<Exp>, Span),
+    Assgn(UnaryExp, Exp, Span), // 左边要是左值, 变量, 数组元素或者 *p, 语义分析的时候检查
+    CompoundAssgn(UnaryExp, CompoundOp, Exp, Span), // a += b 之类
+    Exp(Option<Exp>), // 位置用Exp自己的
     Block(Block),
-    Break,
-    Continue,
+    Break(Span),
+    Continue(Span),
 }
 
 #[derive(Debug)]
 pub enum Stmt {
-    OpenStmt(OpenStmt),
-    ClosedStmt(ClosedStmt),
+    OpenStmt(OpenStmt, Span),
+    ClosedStmt(ClosedStmt, Span),
 }
 
 #[derive(Debug)]
@@ -53,18 +112,60 @@ pub enum OpenStmt {
     IfStmtNoElse(Exp, Box<Stmt>),
     IfStmtMitElse(Exp, ClosedStmt, Box<OpenStmt>),
     WhileStmt(Exp, Box<OpenStmt>),
+    ForStmt(ForHead, Box<OpenStmt>),
 }
 
 #[derive(Debug)]
 pub enum ClosedStmt {
     IfStmt(Exp, Box<ClosedStmt>, Box<ClosedStmt>),
     WhileStmt(Exp, Box<ClosedStmt>),
+    ForStmt(ForHead, Box<ClosedStmt>),
+    DoWhileStmt(Box<Stmt>, Exp),
+    SwitchStmt(Exp, Vec<SwitchCase>),
     BasicStmt(BasicStmt),
 }
 
+// for (init; cond; step) 括号里的部分
+#[derive(Debug)]
+pub struct ForHead {
+    pub init: ForInit,
+    pub cond: Option<Exp>,
+    pub step: Option<BasicStmt>, // 只会是赋值或者表达式
+    pub span: Span,
+}
+
+// switch里的一个分支, 可以没有语句直接落到下一个
+#[derive(Debug)]
+pub struct SwitchCase {
+    pub label: CaseLabel,
+    pub block_items: Vec<BlockItem>,
+    pub span: Span, // 只是标号的位置, 不带冒号和后面的语句
+}
+
+#[derive(Debug)]
+pub enum CaseLabel {
+    Case(ConstExp),
+    Default,
+}
+
+#[derive(Debug)]
+pub enum ForInit {
+    Decl(Decl),
+    Stmt(BasicStmt), // 只会是赋值或者表达式
+}
+
+// 二元运算那些节点不单独记位置, 用所在的Exp的
 #[derive(Debug)]
 pub struct Exp {
-    pub l_or_exp : LOrExp,
+    pub cond_exp : CondExp,
+    pub span: Span,
+}
+
+// c ? a : b, 中间可以是任意表达式, 右边还可以接着是条件表达式
+#[derive(Debug)]
+pub enum CondExp {
+    LOrExp(LOrExp),
+    CondExp(LOrExp, Box<Exp>, Box<CondExp>),
 }
 
 
@@ -73,6 +174,9 @@ pub enum UnaryOp {
     Add,
     Sub,
     Rev, // 取反！
+    Not, // 按位取反 ~
+    Addr, // 取地址 &
+    Deref, // 解引用 *
 }
 
 
@@ -80,6 +184,24 @@ pub enum UnaryOp {
 pub enum UnaryExp {
     UnaryExp(UnaryOp, Box<UnaryExp>),
     PrimaryExp(Box<PrimaryExp>),
+    FuncCall(String, Vec<Exp>, Span), // 函数调用
+    PreIncDec(IncDecOp, Box<UnaryExp>), // ++a, 结果是新值
+    PostIncDec(Box<PrimaryExp>, IncDecOp), // a++, 结果是旧值
+}
+
+#[derive(Debug)]
+pub enum IncDecOp {
+    Inc,
+    Dec,
+}
+
+#[derive(Debug)]
+pub enum CompoundOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
 }
 
 #[derive(Debug)]
@@ -87,6 +209,7 @@ pub enum PrimaryExp {
     Exp(Box<Exp>),
     LVal(LVal),
     Number(Number),
+    StringLit(Vec<u8>), // 字符串字面量, 只能用作putf的格式串
 }
 
 #[derive(Debug)]
@@ -117,7 +240,8 @@ pub enum AddExp {
 
 #[derive(Debug)]
 pub enum Number {
-    IntConst(i32),
+    IntConst(i64), // 先按i64存, 超出int范围的在生成IR的时候截断
+    FloatConst(f32),
 }
 
 
@@ -149,9 +273,36 @@ pub enum LOrOp {
 }
 
 #[derive(Debug)]
-pub enum RelExp {
+pub enum ShiftOp {
+    Shl,
+    Shr, // 有符号数, 算术右移
+}
+
+#[derive(Debug)]
+pub enum BitAndOp {
+    And,
+}
+
+#[derive(Debug)]
+pub enum BitXorOp {
+    Xor,
+}
+
+#[derive(Debug)]
+pub enum BitOrOp {
+    Or,
+}
+
+#[derive(Debug)]
+pub enum ShiftExp {
     AddExp(AddExp),
-    RelExp(Box<RelExp>, RelOp, AddExp),
+    ShiftExp(Box<ShiftExp>, ShiftOp, AddExp),
+}
+
+#[derive(Debug)]
+pub enum RelExp {
+    ShiftExp(ShiftExp),
+    RelExp(Box<RelExp>, RelOp, ShiftExp),
 }
 
 #[derive(Debug)]
@@ -161,9 +312,27 @@ pub enum EqExp {
 }
 
 #[derive(Debug)]
-pub enum LAndExp {
+pub enum BitAndExp {
     EqExp(EqExp),
-    LAndExp(Box<LAndExp>, LAndOp, EqExp),
+    BitAndExp(Box<BitAndExp>, BitAndOp, EqExp),
+}
+
+#[derive(Debug)]
+pub enum BitXorExp {
+    BitAndExp(BitAndExp),
+    BitXorExp(Box<BitXorExp>, BitXorOp, BitAndExp),
+}
+
+#[derive(Debug)]
+pub enum BitOrExp {
+    BitXorExp(BitXorExp),
+    BitOrExp(Box<BitOrExp>, BitOrOp, BitXorExp),
+}
+
+#[derive(Debug)]
+pub enum LAndExp {
+    BitOrExp(BitOrExp),
+    LAndExp(Box<LAndExp>, LAndOp, BitOrExp),
 }
 
 #[derive(Debug)]
@@ -182,39 +351,68 @@ pub enum Decl {
 pub struct ConstDecl {
     pub btype: BType,
     pub const_defs: Vec<ConstDef>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub struct VarDecl {
     pub btype: BType,
     pub var_defs: Vec<VarDef>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
 pub enum VarDef {
-    VarDefUninit(String),
-    VarDefInit(String, InitVal),
+    VarDefUninit(usize, String, Vec<ConstExp>, Span), // 前面是指针的层数, 后面是数组的各个维度
+    VarDefInit(usize, String, Vec<ConstExp>, InitVal, Span),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum BType {
     Int,
+    Float,
+    Pointer(Box<BType>), // 指向的类型
+    Struct(String), // 结构体的名字, 布局在生成IR的时候算
+}
+
+// int **p 这样的声明, 在基本类型外面套几层指针
+pub fn pointer_type(btype: BType, depth: usize) -> BType {
+    let mut res = btype;
+    for _ in 0..depth {
+        res = BType::Pointer(Box::new(res));
+    }
+    return res;
 }
 
 #[derive(Debug)]
 pub struct ConstDef {
     pub ident: String,
+    pub dims: Vec<ConstExp>,
     pub const_init_val: ConstInitVal,
+    pub span: Span,
 }
 
 #[derive(Debug)]
-pub struct ConstInitVal {
-    pub const_exp: ConstExp,
+pub enum ConstInitVal {
+    ConstExp(ConstExp),
+    InitList(Vec<ConstInitVal>),
 }
 
 #[derive(Debug)]
 pub struct LVal {
     pub ident: String,
+    pub exps: Vec<Exp>, // 数组下标
+    pub members: Vec<Member>, // 后面接着的 .x 和 ->x
+    pub span: Span,
+}
+
+// 结构体的成员, 比如 .x[1] 或者 ->x
+#[derive(Debug)]
+pub struct Member {
+    pub arrow: bool,
+    pub ident: String,
+    pub exps: Vec<Exp>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
@@ -223,6 +421,84 @@ pub struct ConstExp {
 }
 
 #[derive(Debug)]
-pub struct InitVal {
-    pub exp: Exp,
-}
\ No newline at end of file
+pub enum InitVal {
+    Exp(Exp),
+    InitList(Vec<InitVal>),
+}
+// 十六进制的浮点数字面量, 比如 0x1.8p3; 先按f64算, 最后再转成f32
+pub fn parse_hex_float(s: &str) -> f32 {
+    let s = &s[2..];
+    let (mantissa, exp) = s.split_once(['p', 'P']).unwrap();
+    let (int_part, frac_part) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let mut val: f64 = 0.0;
+    for c in int_part.chars().chain(frac_part.chars()) {
+        val = val * 16.0 + c.to_digit(16).unwrap() as f64;
+    }
+    let exp = exp.parse::<i32>().unwrap() - 4 * frac_part.len() as i32;
+    return (val * 2f64.powi(exp)) as f32;
+}
+
+// 八进制和十六进制的整数字面量: 和C一样, 放得进u32的当成unsigned, 再按位转成int
+// 更大的原样留着, 生成IR的时候会给出警告
+pub fn parse_radix_int(s: &str, radix: u32) -> i64 {
+    match u64::from_str_radix(s, radix) {
+        Ok(val) if val <= u32::MAX as u64 => return val as u32 as i32 as i64,
+        Ok(val) => return i64::try_from(val).unwrap_or(i64::MAX),
+        Err(_) => return i64::MAX,
+    }
+}
+
+// 字符和字符串字面量里的转义, 传进来的不带两边的引号
+pub fn unescape(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut res = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' || i + 1 == bytes.len() {
+            res.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+        let c = bytes[i];
+        i += 1;
+        match c {
+            b'n' => res.push(b'\n'),
+            b't' => res.push(b'\t'),
+            b'r' => res.push(b'\r'),
+            b'a' => res.push(0x07),
+            b'b' => res.push(0x08),
+            b'f' => res.push(0x0c),
+            b'v' => res.push(0x0b),
+            b'x' => {
+                // \xHH, 十六进制位数不限, 只取低8位
+                let mut val: u32 = 0;
+                while i < bytes.len() && bytes[i].is_ascii_hexdigit() {
+                    val = val.wrapping_mul(16) + (bytes[i] as char).to_digit(16).unwrap();
+                    i += 1;
+                }
+                res.push(val as u8);
+            }
+            b'0'..=b'7' => {
+                // 八进制最多三位
+                let mut val: u32 = (c - b'0') as u32;
+                let mut len = 1;
+                while len < 3 && i < bytes.len() && (b'0'..=b'7').contains(&bytes[i]) {
+                    val = val * 8 + (bytes[i] - b'0') as u32;
+                    i += 1;
+                    len += 1;
+                }
+                res.push(val as u8);
+            }
+            // \\ \' \" \? 和不认识的都是字符本身
+            _ => res.push(c),
+        }
+    }
+    return res;
+}
+
+// 字符字面量就是一个int, 和C一样按signed char算
+pub fn parse_char_literal(s: &str) -> i32 {
+    let bytes = unescape(&s[1..s.len() - 1]);
+    return bytes.first().map_or(0, |byte| *byte as i8 as i32);
+}
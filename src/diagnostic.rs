@@ -0,0 +1,305 @@
+// 报错和警告: 先收集起来, 最后按rustc的样子带上源代码片段打出来.
+//
+// 错误码:
+//   E0001 语法错误
+//   E01xx 名字: E0101 未声明, E0102 重复声明, E0103 不是函数/不是变量
+//   E02xx 类型: E0201 类型转换, E0202 运算数类型不对, E0203 不能当作值用,
+//               E0204 下标和维度, E0205 结构体成员
+//   E03xx 左值: E0301 需要左值, E0302 给常量赋值
+//   E04xx 控制流: E0401 break, E0402 continue, E0403 return, E0404 switch的标号
+//   E05xx 声明和初值: E0502 初值不是常量, E0503 不完整的类型, E0504 结构体定义,
+//                     E0505 常量表达式除以0, E0506 常量下标越界
+//   E06xx 函数: E0601 参数个数, E0602 参数类型, E0603 类型冲突, E0604 形参, E0605 putf
+//   E07xx 预处理: E0701 指令写错了, E0702 找不到头文件, E0703 #error
+//   E08xx 链接: E0801 重复定义, E0802 找不到定义, E0803 类型冲突, E0804 互相递归, E0805 合出来的Koopa IR不对
+//   W0001 常量表达式溢出, W0002 整数字面量被截断, W0003 #warning, W0004 初值太多(和gcc一样只是警告),
+//   W0005 没用过的变量, W0006 没用过的常量, W0007 走不到的代码, W0008 非void函数没有return就走到头了,
+//   W0009 while的条件是常量, W0010 标量的初值外面套了多层花括号, W0011 常量数组的地址当成了普通的指针
+//
+// 每个警告有个和gcc一样的名字, 命令行上用 -W名字 / -Wno-名字 打开关掉, -Werror / -Werror=名字 当成错误
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{stderr, Write};
+
+use crate::ast::Span;
+use crate::source_map::{Location, SourceMap};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => return "error",
+            Severity::Warning => return "warning",
+        }
+    }
+}
+
+// 源代码里标出来的一段, 主要的用^, 次要的用-
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: String,
+    pub message: String,
+    pub primary: Option<Label>,
+    pub secondary: Vec<Label>,
+    pub location: Option<Location>, // 没有Span的时候(预处理的时候)直接给位置
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &str, message: &str) -> Self {
+        return Diagnostic {
+            severity,
+            code: code.to_string(),
+            message: message.to_string(),
+            primary: None,
+            secondary: Vec::new(),
+            location: None,
+            notes: Vec::new(),
+            help: None,
+        };
+    }
+
+    pub fn error(code: &str, message: &str) -> Self {
+        return Self::new(Severity::Error, code, message);
+    }
+
+    pub fn warning(code: &str, message: &str) -> Self {
+        return Self::new(Severity::Warning, code, message);
+    }
+
+    pub fn with_primary(mut self, span: Span, message: &str) -> Self {
+        self.primary = Some(Label {
+            span,
+            message: message.to_string(),
+        });
+        return self;
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: &str) -> Self {
+        self.secondary.push(Label {
+            span,
+            message: message.to_string(),
+        });
+        return self;
+    }
+
+    pub fn with_location(mut self, location: Location) -> Self {
+        self.location = Some(location);
+        return self;
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_string());
+        return self;
+    }
+
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.help = Some(help.to_string());
+        return self;
+    }
+
+    pub fn is_error(&self) -> bool {
+        return self.severity == Severity::Error;
+    }
+
+    // error[E0401]: break statement not in loop or switch
+    //  --> a.c:3:5
+    //   |
+    // 3 |     break;
+    //   |     ^^^^^^
+    //   |
+    //   = help: ...
+    pub fn render(&self, source_map: Option<&SourceMap>) -> String {
+        let mut out = String::new();
+        if self.code.is_empty() {
+            writeln!(out, "{}: {}", self.severity.name(), self.message).unwrap();
+        } else {
+            writeln!(out, "{}[{}]: {}", self.severity.name(), self.code, self.message).unwrap();
+        }
+        let mut labels: Vec<(&Label, bool)> = Vec::new();
+        if let Some(primary) = &self.primary {
+            labels.push((primary, true));
+        }
+        labels.extend(self.secondary.iter().map(|label| (label, false)));
+        let mut width = 1;
+        match source_map {
+            Some(source_map) if !labels.is_empty() => {
+                width = labels
+                    .iter()
+                    .map(|(label, _)| source_map.lookup(label.span.lo).line.to_string().len())
+                    .max()
+                    .unwrap();
+                let pad = " ".repeat(width);
+                // -->指向主要的位置, 下面的按在源代码里的顺序打, 同一行的只打一次源代码
+                let first = source_map.lookup(labels[0].0.span.lo);
+                writeln!(out, "{}--> {}:{}:{}", pad, first.file, first.line, first.col).unwrap();
+                writeln!(out, "{} |", pad).unwrap();
+                labels.sort_by_key(|(label, _)| label.span.lo);
+                let mut file = first.file.clone();
+                let mut prev_line: Option<(String, usize)> = None;
+                for (label, is_primary) in &labels {
+                    let location = source_map.lookup(label.span.lo);
+                    if location.file != file {
+                        writeln!(out, "{}::: {}:{}:{}", pad, location.file, location.line, location.col).unwrap();
+                        file = location.file.clone();
+                    }
+                    let line_text = source_map.line_text(label.span.lo);
+                    if prev_line != Some((location.file.clone(), location.line)) {
+                        writeln!(out, "{:>width$} | {}", location.line, line_text, width = width).unwrap();
+                        prev_line = Some((location.file.clone(), location.line));
+                    }
+                    // 标记和源代码对齐, 前面的tab保留下来
+                    let indent: String = line_text
+                        .chars()
+                        .take(location.col - 1)
+                        .map(|c| if c == '\t' { '\t' } else { ' ' })
+                        .collect();
+                    let rest: String = line_text.chars().skip(location.col - 1).collect();
+                    let len = source_map.snippet(label.span).chars().count().min(rest.chars().count()).max(1);
+                    let marker = if *is_primary { "^" } else { "-" };
+                    let line = format!("{} | {}{} {}", pad, indent, marker.repeat(len), label.message);
+                    writeln!(out, "{}", line.trim_end()).unwrap();
+                }
+            }
+            _ => {
+                if let Some(location) = &self.location {
+                    writeln!(out, " --> {}:{}", location.file, location.line).unwrap();
+                }
+            }
+        }
+        let pad = " ".repeat(width);
+        if !self.notes.is_empty() || self.help.is_some() {
+            writeln!(out, "{} |", pad).unwrap();
+        }
+        for note in &self.notes {
+            writeln!(out, "{} = note: {}", pad, note).unwrap();
+        }
+        if let Some(help) = &self.help {
+            writeln!(out, "{} = help: {}", pad, help).unwrap();
+        }
+        return out;
+    }
+}
+
+pub fn has_errors(diags: &[Diagnostic]) -> bool {
+    return diags.iter().any(|diag| diag.is_error());
+}
+
+// 都打到stderr上, 中间空一行
+pub fn emit_diagnostics(diags: &[Diagnostic], source_map: Option<&SourceMap>) {
+    for diag in diags {
+        stderr()
+            .write_all(format!("{}\n", diag.render(source_map)).as_bytes())
+            .unwrap();
+    }
+}
+
+// 警告码 -> 命令行上的名字
+const WARNING_NAMES: &[(&str, &str)] = &[
+    ("W0001", "overflow"),
+    ("W0002", "overflow"),
+    ("W0003", "cpp"),
+    ("W0004", "excess-initializers"),
+    ("W0005", "unused-variable"),
+    ("W0006", "unused-const-variable"),
+    ("W0007", "unreachable-code"),
+    ("W0008", "return-type"),
+    ("W0009", "constant-condition"),
+    ("W0010", "braced-scalar-init"),
+    ("W0011", "discarded-qualifiers"),
+];
+
+// -W 开头的选项, 后面的覆盖前面的; 默认所有的警告都打开, 都不算错误
+#[derive(Debug, Default)]
+pub struct WarningOptions {
+    pub enabled: HashMap<String, bool>,
+    pub errors: HashMap<String, bool>, // 单独指定的 -Werror=名字 和 -Wno-error=名字
+    pub all_errors: bool,              // -Werror
+    pub suppress_all: bool,            // -w
+}
+
+impl WarningOptions {
+    // 认不出来的名字给一个警告, 和clang一样
+    pub fn set(&mut self, arg: &str) -> Option<Diagnostic> {
+        if arg == "-w" {
+            self.suppress_all = true;
+            return None;
+        }
+        let option = &arg[2..];
+        let (name, value, is_error) = match option {
+            "error" => {
+                self.all_errors = true;
+                return None;
+            }
+            "no-error" => {
+                self.all_errors = false;
+                return None;
+            }
+            "all" | "extra" => {
+                for (_, name) in WARNING_NAMES {
+                    self.enabled.insert(name.to_string(), true);
+                }
+                return None;
+            }
+            _ => match option.strip_prefix("error=") {
+                Some(name) => (name, true, true),
+                None => match option.strip_prefix("no-error=") {
+                    Some(name) => (name, false, true),
+                    None => match option.strip_prefix("no-") {
+                        Some(name) => (name, false, false),
+                        None => (option, true, false),
+                    },
+                },
+            },
+        };
+        if !WARNING_NAMES.iter().any(|(_, known)| *known == name) {
+            return Some(Diagnostic::warning("", &format!("unknown warning option {}", arg)));
+        }
+        if is_error {
+            // -Werror=名字 顺便把它打开
+            self.errors.insert(name.to_string(), value);
+            if value {
+                self.enabled.insert(name.to_string(), true);
+            }
+        } else {
+            self.enabled.insert(name.to_string(), value);
+        }
+        return None;
+    }
+
+    // 去掉关掉的警告, 要当成错误的改成错误; 和gcc一样在后面标上是哪个选项
+    pub fn apply(&self, diags: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        let mut res = Vec::new();
+        for mut diag in diags {
+            let name = WARNING_NAMES.iter().find(|(code, _)| *code == diag.code).map(|(_, name)| *name);
+            let (Severity::Warning, Some(name)) = (diag.severity, name) else {
+                res.push(diag);
+                continue;
+            };
+            if self.suppress_all || !self.enabled.get(name).copied().unwrap_or(true) {
+                continue;
+            }
+            if self.errors.get(name).copied().unwrap_or(self.all_errors) {
+                diag.severity = Severity::Error;
+                diag.message = format!("{} [-Werror={}]", diag.message, name);
+            } else {
+                diag.message = format!("{} [-W{}]", diag.message, name);
+            }
+            res.push(diag);
+        }
+        return res;
+    }
+}
@@ -0,0 +1,179 @@
+// 控制流: 只看语法, 一条语句执行完能不能接着往下走.
+// return, break, continue之后走不下去; while(1)和for(;;)只有break才出得来, 别的条件都当成可能是假的.
+// 语义分析用它找走不到的代码和走到头也没有return的函数; 生成IR的时候用它跳过没有入口的基本块
+use crate::ast::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Jump {
+    Break,
+    Continue,
+}
+
+impl Exp {
+    // 整数字面量, 外面可以套括号
+    pub fn int_literal(&self) -> Option<i64> {
+        match self.as_unary_exp() {
+            Some(UnaryExp::PrimaryExp(primary_exp)) => match primary_exp.as_ref() {
+                PrimaryExp::Number(Number::IntConst(num)) => return Some(*num),
+                _ => return None,
+            },
+            _ => return None,
+        }
+    }
+
+    // 永远是真的条件, 比如while(1); 不是0的字面量都算
+    pub fn is_true_literal(&self) -> bool {
+        return self.int_literal().is_some_and(|num| num != 0);
+    }
+}
+
+pub fn items_can_complete(block_items: &[BlockItem]) -> bool {
+    return block_items.iter().all(|block_item| block_item.can_complete());
+}
+
+// 有没有跳出当前这层循环(或者switch)的break/continue, 里面的循环的不算;
+// 走不到的语句里的也不算, 生成IR的时候根本不会生成它们
+pub fn items_have_jump(block_items: &[BlockItem], jump: Jump) -> bool {
+    for block_item in block_items {
+        if let BlockItem::Stmt(stmt) = block_item {
+            if stmt.has_jump(jump) {
+                return true;
+            }
+        }
+        if !block_item.can_complete() {
+            return false;
+        }
+    }
+    return false;
+}
+
+impl Block {
+    pub fn can_complete(&self) -> bool {
+        return items_can_complete(&self.block_items);
+    }
+}
+
+impl BlockItem {
+    pub fn can_complete(&self) -> bool {
+        match self {
+            BlockItem::Decl(_) => return true,
+            BlockItem::Stmt(stmt) => return stmt.can_complete(),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            BlockItem::Decl(Decl::ConstDecl(const_decl)) => return const_decl.span,
+            BlockItem::Decl(Decl::VarDecl(var_decl)) => return var_decl.span,
+            BlockItem::Stmt(Stmt::ClosedStmt(_, span) | Stmt::OpenStmt(_, span)) => return *span,
+        }
+    }
+}
+
+impl Stmt {
+    pub fn can_complete(&self) -> bool {
+        match self {
+            Stmt::ClosedStmt(closed_stmt, _) => return closed_stmt.can_complete(),
+            Stmt::OpenStmt(open_stmt, _) => return open_stmt.can_complete(),
+        }
+    }
+
+    pub fn has_jump(&self, jump: Jump) -> bool {
+        match self {
+            Stmt::ClosedStmt(closed_stmt, _) => return closed_stmt.has_jump(jump),
+            Stmt::OpenStmt(open_stmt, _) => return open_stmt.has_jump(jump),
+        }
+    }
+}
+
+// 没有条件或者条件是while(1)的循环, 只有break能出来
+fn loop_can_complete(cond: Option<&Exp>, body_has_break: bool) -> bool {
+    return cond.is_some_and(|cond| !cond.is_true_literal()) || body_has_break;
+}
+
+impl ClosedStmt {
+    pub fn can_complete(&self) -> bool {
+        match self {
+            ClosedStmt::IfStmt(_, then_stmt, else_stmt) => return then_stmt.can_complete() || else_stmt.can_complete(),
+            ClosedStmt::WhileStmt(cond, body) => return loop_can_complete(Some(cond), body.has_jump(Jump::Break)),
+            ClosedStmt::ForStmt(for_head, body) => {
+                return loop_can_complete(for_head.cond.as_ref(), body.has_jump(Jump::Break));
+            }
+            ClosedStmt::DoWhileStmt(body, cond) => {
+                // 循环体要能走到判断条件的地方
+                let reaches_cond = body.can_complete() || body.has_jump(Jump::Continue);
+                return (reaches_cond && !cond.is_true_literal()) || body.has_jump(Jump::Break);
+            }
+            ClosedStmt::SwitchStmt(_, switch_cases) => {
+                // 没有default的话可能一个分支都不进; 否则只能从最后一个分支落出来或者break出来
+                let has_default = switch_cases
+                    .iter()
+                    .any(|switch_case| matches!(switch_case.label, CaseLabel::Default));
+                let Some(last) = switch_cases.last() else {
+                    return true;
+                };
+                return !has_default
+                    || items_can_complete(&last.block_items)
+                    || switch_cases
+                        .iter()
+                        .any(|switch_case| items_have_jump(&switch_case.block_items, Jump::Break));
+            }
+            ClosedStmt::BasicStmt(basic_stmt) => return basic_stmt.can_complete(),
+        }
+    }
+
+    pub fn has_jump(&self, jump: Jump) -> bool {
+        match self {
+            ClosedStmt::IfStmt(_, then_stmt, else_stmt) => return then_stmt.has_jump(jump) || else_stmt.has_jump(jump),
+            ClosedStmt::WhileStmt(_, _) | ClosedStmt::ForStmt(_, _) | ClosedStmt::DoWhileStmt(_, _) => return false,
+            // switch里的break是它自己的, continue是外面循环的
+            ClosedStmt::SwitchStmt(_, switch_cases) => {
+                return jump == Jump::Continue
+                    && switch_cases
+                        .iter()
+                        .any(|switch_case| items_have_jump(&switch_case.block_items, jump));
+            }
+            ClosedStmt::BasicStmt(basic_stmt) => return basic_stmt.has_jump(jump),
+        }
+    }
+}
+
+impl OpenStmt {
+    pub fn can_complete(&self) -> bool {
+        match self {
+            OpenStmt::IfStmtNoElse(_, _) => return true,
+            OpenStmt::IfStmtMitElse(_, then_stmt, else_stmt) => return then_stmt.can_complete() || else_stmt.can_complete(),
+            OpenStmt::WhileStmt(cond, body) => return loop_can_complete(Some(cond), body.has_jump(Jump::Break)),
+            OpenStmt::ForStmt(for_head, body) => {
+                return loop_can_complete(for_head.cond.as_ref(), body.has_jump(Jump::Break));
+            }
+        }
+    }
+
+    pub fn has_jump(&self, jump: Jump) -> bool {
+        match self {
+            OpenStmt::IfStmtNoElse(_, then_stmt) => return then_stmt.has_jump(jump),
+            OpenStmt::IfStmtMitElse(_, then_stmt, else_stmt) => return then_stmt.has_jump(jump) || else_stmt.has_jump(jump),
+            OpenStmt::WhileStmt(_, _) | OpenStmt::ForStmt(_, _) => return false,
+        }
+    }
+}
+
+impl BasicStmt {
+    pub fn can_complete(&self) -> bool {
+        match self {
+            BasicStmt::Return(_, _) | BasicStmt::Break(_) | BasicStmt::Continue(_) => return false,
+            BasicStmt::Block(block) => return block.can_complete(),
+            _ => return true,
+        }
+    }
+
+    pub fn has_jump(&self, jump: Jump) -> bool {
+        match self {
+            BasicStmt::Break(_) => return jump == Jump::Break,
+            BasicStmt::Continue(_) => return jump == Jump::Continue,
+            BasicStmt::Block(block) => return items_have_jump(&block.block_items, jump),
+            _ => return false,
+        }
+    }
+}
@@ -2,7 +2,7 @@
 use koopa::ir::ValueKind;
 use std::io::Write;
 use koopa::ir::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub struct GenerateAsmParams {
     pub current_register: i32, // 当前使用的register编号
@@ -10,10 +10,16 @@ pub struct GenerateAsmParams {
     pub register_data : HashMap<Value, i32>, // 指令对应的寄存器
     pub register_usage : HashMap<i32, bool>, // 寄存器的使用情况
     pub stack_state : HashMap<Value, i32>, // 对应的变量和偏移量
+    pub save_ra: bool, // 函数里有call, 需要保存ra
+    pub func_names: HashMap<Function, String>, // call的时候需要知道函数名
+    pub global_names: HashMap<Value, String>, // 全局变量要用la取地址
+    pub float_sigs: HashMap<String, (Vec<bool>, bool)>, // 哪些参数和返回值是float, 要放到浮点寄存器里
+    pub switch_chains: HashMap<String, Vec<Vec<String>>>, // 每个函数里switch的比较链上的基本块, 第一块是入口
+    pub label_count: i32, // 汇编里自己加的标号的编号
 }
 
 pub fn register_idx_to_name(register_idx: i32) -> String {
-    if register_idx >= 0 && register_idx <= 6 {
+    if (0..=6).contains(&register_idx) {
         return format!("t{}", register_idx);
     } else {
         return format!("a{}", register_idx - 7);
@@ -24,10 +30,10 @@ pub fn available_register(register_usage: &HashMap<i32, bool>) -> i32 {
     let mut i = 0;
     while i < 15 {
         let usage = register_usage.get(&i).unwrap();
-        if *usage == false {
+        if !*usage {
             return i
         }
-        i = i + 1;
+        i += 1;
     }
     /* `i32` value */
     return 0
@@ -48,10 +54,44 @@ pub trait GenerateAsm {
 
 impl GenerateAsm for koopa::ir::Program {
     fn generate_asm(&self, buf: &mut Vec<u8>, params: &mut GenerateAsmParams) {
+        // 先生成全局变量, 有初值的放.data, 全是0的放.bss
+        for &global in self.inst_layout() {
+            let global_data = self.borrow_value(global);
+            let global_name = global_data.name().as_ref().unwrap().replace("@", "");
+            params.global_names.insert(global, global_name.clone());
+            if let ValueKind::GlobalAlloc(global_alloc) = global_data.kind() {
+                let init_data = self.borrow_value(global_alloc.init());
+                match init_data.kind() {
+                    // 字符串字面量是只读的
+                    _ if global_name.starts_with("__str") => {
+                        writeln!(buf, "  .section .rodata").unwrap();
+                    }
+                    ValueKind::ZeroInit(_) => {
+                        writeln!(buf, "  .bss").unwrap();
+                    }
+                    _ => {
+                        writeln!(buf, "  .data").unwrap();
+                    }
+                }
+                writeln!(buf, "  .globl {}", global_name).unwrap();
+                writeln!(buf, "{}:", global_name).unwrap();
+                global_init_asm(self, global_alloc.init(), buf);
+                writeln!(buf).unwrap();
+            }
+        }
         writeln!(buf, "  .text").unwrap();
         // writeln!(buf, "  .globl main").unwrap();
         for &func in self.func_layout() {
-            self.func(func).generate_asm(buf, params);
+            params.func_names.insert(func, self.func(func).name().replace("@", ""));
+        }
+        for &func in self.func_layout() {
+            let func_data = self.func(func);
+            if func_data.layout().entry_bb().is_none() {
+                // 只有声明没有定义, 不需要生成代码
+                continue;
+            }
+            func_data.generate_asm(buf, params);
+            writeln!(buf).unwrap();
         }
     }
 }
@@ -62,17 +102,53 @@ impl GenerateAsm for koopa::ir::FunctionData {
         // 首先提供函数入口
         writeln!(buf, "  .globl {}", self.name().replace("@", "")).unwrap();
         writeln!(buf, "{}:", self.name().replace("@", "")).unwrap();
+        // 每个函数的栈帧是分开算的
+        params.stack_bytes = 0;
+        params.stack_state.clear();
+        params.save_ra = false;
+        // 先看有没有call, 以及call最多要往栈上放几个参数
+        // 浮点运算的函数会直接展开成指令, 不算真正的call
+        let mut max_stack_args: i32 = 0;
+        for (&_bb, node) in self.layout().bbs() {
+            for &inst in node.insts().keys() {
+                if let ValueKind::Call(call) = self.dfg().value(inst).kind() {
+                    let callee_name = params.func_names.get(&call.callee()).unwrap();
+                    if is_float_intrinsic(callee_name) || is_ptr_intrinsic(callee_name) {
+                        continue;
+                    }
+                    params.save_ra = true;
+                    if let Some(sig) = callee_name.strip_prefix("__putf_") {
+                        let words = putf_arg_positions(sig).last().map_or(1, |(pos, is_float)| pos + 1 + *is_float as i32);
+                        max_stack_args = max_stack_args.max(words - 8);
+                        continue;
+                    }
+                    let locs = arg_locations(&float_params(callee_name, call.args().len(), params));
+                    let stack_args = locs.iter().filter(|loc| matches!(loc, ArgLoc::Stack(_))).count();
+                    max_stack_args = max_stack_args.max(stack_args as i32);
+                }
+            }
+        }
+        // 放不进寄存器的参数放在栈底, 局部变量放在它上面
+        params.stack_bytes = max_stack_args * 4;
         // 首先计算出是否需要在栈上分配空间
         for (&_bb, node) in self.layout().bbs() {
             for &inst in node.insts().keys() {
                 let inst_data = self.dfg().value(inst);
                 // 计算出需要分配栈空间的指令.
-                if inst_data.ty().is_unit() == false {
+                if let ValueKind::Alloc(_) = inst_data.kind() {
+                    // alloc要分配它指向的类型那么大, 比如数组
+                    params.stack_state.insert(inst, params.stack_bytes);
+                    params.stack_bytes += alloc_size(inst_data.ty()) as i32;
+                } else if !inst_data.ty().is_unit() {
                     params.stack_state.insert(inst, params.stack_bytes);
                     params.stack_bytes += 4;
                 }
             }
         }
+        // ra放在栈顶
+        if params.save_ra {
+            params.stack_bytes += 4;
+        }
         // 首先计算出prologue, 对齐到16字节
         if params.stack_bytes % 16 != 0 {
             params.stack_bytes += 16 - params.stack_bytes % 16;
@@ -85,12 +161,23 @@ impl GenerateAsm for koopa::ir::FunctionData {
                 writeln!(buf, "  sub sp, sp, t0").unwrap();
             }
         }
-        // ...
+        if params.save_ra {
+            load_and_save_reg("sw", "ra", params.stack_bytes - 4, buf);
+        }
+        // switch的比较链会被整个换掉, 链上后面的块就不用再生成了
+        let mut skipped_bbs: HashSet<BasicBlock> = HashSet::new();
         // 访问基本块
         for (&bb, node) in self.layout().bbs() {
+            if skipped_bbs.contains(&bb) {
+                continue;
+            }
             // 访问指令列表
-            let bb_name = self.dfg().bb(bb).name().as_ref().unwrap().replace("%", "");
-            writeln!(buf, "{}:", bb_name).unwrap();
+            writeln!(buf, "{}:", bb_label(self, bb)).unwrap();
+            if let Some(chain) = switch_chain(self, bb, params) {
+                switch_asm(self, bb, &chain, buf, params);
+                skipped_bbs.extend(chain.bbs);
+                continue;
+            }
             for &inst in node.insts().keys() {
                 let inst_data = self.dfg().value(inst);
                 // value_data 是 &ValueData 类型
@@ -102,16 +189,15 @@ impl GenerateAsm for koopa::ir::FunctionData {
                     ValueKind::Return(ret_value) => {
                         // 获取返回值
                         // 在这里实现函数的epilogue
-                        let ret_value_data = ret_value.value().unwrap();
-                        match self.dfg().value(ret_value_data).kind() {
-                            ValueKind::Integer(int_num) => {
-                                writeln!(buf, "  li a0, {}", int_num.value()).unwrap();
-                            }
-                            _ => {
-                                let delta = * params.stack_state.get(&ret_value_data).unwrap();
-                                load_and_save("lw".to_string(), 7, delta, buf);
+                        if let Some(ret_value_data) = ret_value.value() {
+                            load_value(self, ret_value_data, "a0", buf, params);
+                            if returns_float(&self.name().replace("@", ""), params) {
+                                writeln!(buf, "  fmv.w.x fa0, a0").unwrap();
                             }
                         }
+                        if params.save_ra {
+                            load_and_save_reg("lw", "ra", params.stack_bytes - 4, buf);
+                        }
                         if params.stack_bytes > 0 {
                             if params.stack_bytes <= 2048 {
                                 writeln!(buf, "  addi sp, sp, {}", params.stack_bytes).unwrap();
@@ -132,45 +218,39 @@ impl GenerateAsm for koopa::ir::FunctionData {
                         let lhs_val = self.dfg().value(bin_lhs);
                         let rhs_val = self.dfg().value(bin_rhs);
 
-                        let lhs_reg: i32;
-                        let rhs_reg: i32;
-
-                        let lhs_reg_str: String;
-                        let rhs_reg_str: String;
-
-                        match lhs_val.kind() {
+                        let lhs_reg: i32 = match lhs_val.kind() {
                             ValueKind::Integer(int_num) => {
                                 // load_operation(int_num.value(), bin_lhs, buf, params);
                                 // lhs_reg = params.current_register;
                                 writeln!(buf, "  li t0, {}", int_num.value()).unwrap();
-                                lhs_reg = 0;
+                                0
                             }
                             _ => {
                                 // lhs_reg = * params.register_data.get(&bin_lhs).unwrap();
                                 // 直接从栈里面读出来
                                 let delta = * params.stack_state.get(&bin_lhs).unwrap();
                                 load_and_save("lw".to_string(), 0, delta, buf);
-                                lhs_reg = 0;
+                                0
                             }
-                        }
+                        };
 
-                        match rhs_val.kind() {
+                        let rhs_reg: i32 = match rhs_val.kind() {
                             ValueKind::Integer(int_num) => {
                                 // load_operation(int_num.value(), bin_rhs, buf, params);
                                 // rhs_reg = params.current_register;
                                 writeln!(buf, "  li t1, {}", int_num.value()).unwrap();
-                                rhs_reg = 1;
+                                1
                             }
                             _ => {
                                 // rhs_reg = * params.register_data.get(&bin_rhs).unwrap();
                                 let delta = * params.stack_state.get(&bin_rhs).unwrap();
                                 load_and_save("lw".to_string(), 1, delta, buf);
-                                rhs_reg = 1;
+                                1
                             }
-                        }
+                        };
 
-                        lhs_reg_str = register_idx_to_name(lhs_reg);
-                        rhs_reg_str = register_idx_to_name(rhs_reg);
+                        let lhs_reg_str: String = register_idx_to_name(lhs_reg);
+                        let rhs_reg_str: String = register_idx_to_name(rhs_reg);
 
                         match bin_op {
                             BinaryOp::Add => {
@@ -210,17 +290,25 @@ impl GenerateAsm for koopa::ir::FunctionData {
                                 writeln!(buf, "  sub {}, {}, {}", rhs_reg_str, lhs_reg_str, rhs_reg_str).unwrap();
                                 writeln!(buf, "  snez {}, {}", rhs_reg_str, rhs_reg_str).unwrap();
                             }
+                            // Koopa的and/or是按位的, 逻辑与或在前端已经先和0比较过了
                             BinaryOp::And => {
-                                writeln!(buf, "  snez {}, {}", lhs_reg_str, lhs_reg_str).unwrap();
-                                writeln!(buf, "  snez {}, {}", rhs_reg_str, rhs_reg_str).unwrap();
                                 writeln!(buf, "  and {}, {}, {}", rhs_reg_str, lhs_reg_str, rhs_reg_str).unwrap();
                             }
                             BinaryOp::Or => {
-                                writeln!(buf, "  snez {}, {}", lhs_reg_str, lhs_reg_str).unwrap();
-                                writeln!(buf, "  snez {}, {}", rhs_reg_str, rhs_reg_str).unwrap();
                                 writeln!(buf, "  or {}, {}, {}", rhs_reg_str, lhs_reg_str, rhs_reg_str).unwrap();
                             }
-                            _ => {}
+                            BinaryOp::Xor => {
+                                writeln!(buf, "  xor {}, {}, {}", rhs_reg_str, lhs_reg_str, rhs_reg_str).unwrap();
+                            }
+                            BinaryOp::Shl => {
+                                writeln!(buf, "  sll {}, {}, {}", rhs_reg_str, lhs_reg_str, rhs_reg_str).unwrap();
+                            }
+                            BinaryOp::Shr => {
+                                writeln!(buf, "  srl {}, {}, {}", rhs_reg_str, lhs_reg_str, rhs_reg_str).unwrap();
+                            }
+                            BinaryOp::Sar => {
+                                writeln!(buf, "  sra {}, {}, {}", rhs_reg_str, lhs_reg_str, rhs_reg_str).unwrap();
+                            }
                         }
                         let delta = * params.stack_state.get(&inst).unwrap();
                         load_and_save("sw".to_string(), rhs_reg, delta, buf);
@@ -244,33 +332,46 @@ impl GenerateAsm for koopa::ir::FunctionData {
                                 writeln!(buf, "  li t0, {}", int_num.value()).unwrap();
                             }
                             _ => {
-                                // 这里是对上一个指令的指针
-                                let delta = * params.stack_state.get(&store_value).unwrap();
-                                load_and_save("lw".to_string(), 0, delta, buf);
+                                // 这里是对上一个指令的指针, 或者是函数的参数
+                                load_value(self, store_value, "t0", buf, params);
                             }
                         }
                         // store_dest 不可能是一个整数
-                        let delta = * params.stack_state.get(&store_dest).unwrap();
-                        load_and_save("sw".to_string(), 0, delta, buf);
+                        if is_local_alloc(self, store_dest, params) {
+                            let delta = * params.stack_state.get(&store_dest).unwrap();
+                            load_and_save("sw".to_string(), 0, delta, buf);
+                        } else {
+                            // 全局变量或者算出来的指针, 先拿到地址
+                            load_addr(self, store_dest, "t1", buf, params);
+                            writeln!(buf, "  sw t0, 0(t1)").unwrap();
+                        }
                     }
                     ValueKind::Load(load) => {
                         let load_src = load.src();
-                        let delta = * params.stack_state.get(&load_src).unwrap();
-                        load_and_save("lw".to_string(), 0, delta, buf);
+                        if is_local_alloc(self, load_src, params) {
+                            let delta = * params.stack_state.get(&load_src).unwrap();
+                            load_and_save("lw".to_string(), 0, delta, buf);
+                        } else {
+                            load_addr(self, load_src, "t0", buf, params);
+                            writeln!(buf, "  lw t0, 0(t0)").unwrap();
+                        }
                         let delta = * params.stack_state.get(&inst).unwrap();
                         load_and_save("sw".to_string(), 0, delta, buf);
                     }
+                    ValueKind::GetElemPtr(get_elem_ptr) => {
+                        get_ptr_asm(self, inst, get_elem_ptr.src(), get_elem_ptr.index(), buf, params);
+                    }
+                    ValueKind::GetPtr(get_ptr) => {
+                        get_ptr_asm(self, inst, get_ptr.src(), get_ptr.index(), buf, params);
+                    }
                     ValueKind::Branch(branch) => {
                         let cond = branch.cond();
                         let true_dst = branch.true_bb();
                         let false_dst = branch.false_bb();
                         let cond_val = self.dfg().value(cond);
-                        let cond_str: String;
-                        let true_name = self.dfg().bb(true_dst).name().as_ref().unwrap()
-                                                    .replace("%", "");
-                        let false_name = self.dfg().bb(false_dst).name().as_ref().unwrap()
-                                                     .replace("%", "");
-                        match cond_val.kind() {
+                        let true_name = bb_label(self, true_dst);
+                        let false_name = bb_label(self, false_dst);
+                        let cond_str: String = match cond_val.kind() {
                             ValueKind::Integer(i) => {
                                 if i.value() != 0 {
                                     writeln!(buf, "  j {}", true_name).unwrap();
@@ -282,9 +383,9 @@ impl GenerateAsm for koopa::ir::FunctionData {
                             _ => {
                                 let delta = * params.stack_state.get(&cond).unwrap();
                                 load_and_save("lw".to_string(), 0, delta, buf);
-                                cond_str = register_idx_to_name(0);
+                                register_idx_to_name(0)
                             }
-                        }
+                        };
                         // 去掉名字里的%号
                         
                         writeln!(buf, "  bnez {}, {}", cond_str, true_name).unwrap();
@@ -292,10 +393,49 @@ impl GenerateAsm for koopa::ir::FunctionData {
                     }
                     ValueKind::Jump(jump) => {
                         let target = jump.target();
-                        let target_name = self.dfg().bb(target).name().as_ref().unwrap()
-                                                      .replace("%", "");
+                        let target_name = bb_label(self, target);
                         writeln!(buf, "  j {}", target_name).unwrap();
                     }
+                    ValueKind::Call(call) => {
+                        let callee_name = params.func_names.get(&call.callee()).unwrap().clone();
+                        if is_float_intrinsic(&callee_name) {
+                            float_intrinsic_asm(self, inst, &callee_name, call.args(), buf, params);
+                            continue;
+                        }
+                        if is_ptr_intrinsic(&callee_name) {
+                            ptr_intrinsic_asm(self, inst, call.args(), buf, params);
+                            continue;
+                        }
+                        if let Some(sig) = callee_name.strip_prefix("__putf_") {
+                            putf_call_asm(self, sig, call.args(), buf, params);
+                            continue;
+                        }
+                        // int参数放在a0-a7, float参数放在fa0-fa7, 剩下的放在栈底
+                        let locs = arg_locations(&float_params(&callee_name, call.args().len(), params));
+                        for (&arg, loc) in call.args().iter().zip(locs.iter()) {
+                            match loc {
+                                ArgLoc::Reg(reg) if reg.starts_with('f') => {
+                                    load_value(self, arg, "t0", buf, params);
+                                    writeln!(buf, "  fmv.w.x {}, t0", reg).unwrap();
+                                }
+                                ArgLoc::Reg(reg) => {
+                                    load_value(self, arg, reg, buf, params);
+                                }
+                                ArgLoc::Stack(slot) => {
+                                    load_value(self, arg, "t0", buf, params);
+                                    load_and_save("sw".to_string(), 0, slot * 4, buf);
+                                }
+                            }
+                        }
+                        writeln!(buf, "  call {}", callee_name).unwrap();
+                        if !inst_data.ty().is_unit() {
+                            if returns_float(&callee_name, params) {
+                                writeln!(buf, "  fmv.x.w a0, fa0").unwrap();
+                            }
+                            let delta = * params.stack_state.get(&inst).unwrap();
+                            load_and_save("sw".to_string(), 7, delta, buf);
+                        }
+                    }
                     _ => unreachable!(),
                 }
             }
@@ -305,12 +445,474 @@ impl GenerateAsm for koopa::ir::FunctionData {
 
 pub fn load_and_save(mode: String, target_reg: i32, delta: i32, buf: &mut Vec<u8>) {
     let reg_str = register_idx_to_name(target_reg);
+    load_and_save_reg(&mode, &reg_str, delta, buf);
+}
+
+pub fn load_and_save_reg(mode: &str, reg_str: &str, delta: i32, buf: &mut Vec<u8>) {
     if delta >= 2048 {
-        writeln!(buf, "  li t0, {}", delta).unwrap();
-        writeln!(buf, "  add t0, sp, t0").unwrap();
-        writeln!(buf, "  {} {}, t0", mode, reg_str).unwrap();
+        // 偏移量放不进12位立即数, 用t6算出地址
+        writeln!(buf, "  li t6, {}", delta).unwrap();
+        writeln!(buf, "  add t6, sp, t6").unwrap();
+        writeln!(buf, "  {} {}, 0(t6)", mode, reg_str).unwrap();
     }
     else {
         writeln!(buf, "  {} {}, {}(sp)", mode, reg_str, delta).unwrap();
     }
-}
\ No newline at end of file
+}
+
+// 把一个操作数放进指定的寄存器
+pub fn load_value(func_data: &FunctionData, value: Value, reg_str: &str, buf: &mut Vec<u8>, params: &mut GenerateAsmParams) {
+    match func_data.dfg().value(value).kind() {
+        ValueKind::Integer(int_num) => {
+            writeln!(buf, "  li {}, {}", reg_str, int_num.value()).unwrap();
+        }
+        ValueKind::Undef(_) => {}
+        ValueKind::FuncArgRef(arg) => {
+            let func_name = func_data.name().replace("@", "");
+            let locs = arg_locations(&float_params(&func_name, func_data.params().len(), params));
+            match &locs[arg.index()] {
+                ArgLoc::Reg(reg) if reg.starts_with('f') => {
+                    writeln!(buf, "  fmv.x.w {}, {}", reg_str, reg).unwrap();
+                }
+                ArgLoc::Reg(reg) => {
+                    writeln!(buf, "  mv {}, {}", reg_str, reg).unwrap();
+                }
+                ArgLoc::Stack(slot) => {
+                    // 调用者放在它的栈底, 也就是我们栈帧的上面
+                    let delta = params.stack_bytes + slot * 4;
+                    load_and_save_reg("lw", reg_str, delta, buf);
+                }
+            }
+        }
+        _ => {
+            let delta = * params.stack_state.get(&value).unwrap();
+            load_and_save_reg("lw", reg_str, delta, buf);
+        }
+    }
+}
+
+// 一个参数按调用约定放在哪里
+pub enum ArgLoc {
+    Reg(String),
+    Stack(i32), // 栈底的第几个位置
+}
+
+// 和ilp32f一样: int用a0-a7, float先用fa0-fa7, 用完了再用剩下的a寄存器, 最后才放栈上
+pub fn arg_locations(float_params: &[bool]) -> Vec<ArgLoc> {
+    let mut int_regs = 0;
+    let mut float_regs = 0;
+    let mut stack_slots = 0;
+    let mut locs = Vec::new();
+    for &is_float in float_params {
+        if is_float && float_regs < 8 {
+            locs.push(ArgLoc::Reg(format!("fa{}", float_regs)));
+            float_regs += 1;
+        } else if int_regs < 8 {
+            locs.push(ArgLoc::Reg(format!("a{}", int_regs)));
+            int_regs += 1;
+        } else {
+            locs.push(ArgLoc::Stack(stack_slots));
+            stack_slots += 1;
+        }
+    }
+    return locs;
+}
+
+pub fn float_params(func_name: &str, arg_count: usize, params: &GenerateAsmParams) -> Vec<bool> {
+    match params.float_sigs.get(func_name) {
+        Some((float_params, _)) => return float_params.clone(),
+        None => return vec![false; arg_count],
+    }
+}
+
+pub fn returns_float(func_name: &str, params: &GenerateAsmParams) -> bool {
+    return matches!(params.float_sigs.get(func_name), Some((_, true)));
+}
+
+// 前端把浮点运算写成对这些函数的调用
+pub fn is_float_intrinsic(func_name: &str) -> bool {
+    return matches!(
+        func_name,
+        "__fadd" | "__fsub" | "__fmul" | "__fdiv" | "__fneg" | "__feq" | "__fne"
+            | "__flt" | "__fle" | "__fgt" | "__fge" | "__itof" | "__ftoi"
+    );
+}
+
+// 指针的比较和空指针, 见前端的generate_ptr_intrinsic
+pub fn is_ptr_intrinsic(func_name: &str) -> bool {
+    return func_name.starts_with("__ptoi_") || func_name.starts_with("__null_");
+}
+
+// 指针本来就是地址, __ptoi原样存下来; __null没有参数, 存0
+pub fn ptr_intrinsic_asm(func_data: &FunctionData, inst: Value, args: &[Value], buf: &mut Vec<u8>, params: &mut GenerateAsmParams) {
+    match args.first() {
+        Some(&arg) => load_value(func_data, arg, "t0", buf, params),
+        None => writeln!(buf, "  li t0, 0").unwrap(),
+    }
+    let delta = *params.stack_state.get(&inst).unwrap();
+    load_and_save("sw".to_string(), 0, delta, buf);
+}
+
+// 操作数放到ft0, ft1里; 结果是float的从ft0用fsw存回栈上, 是int的从t0存
+pub fn float_intrinsic_asm(func_data: &FunctionData, inst: Value, func_name: &str, args: &[Value], buf: &mut Vec<u8>, params: &mut GenerateAsmParams) {
+    for (i, &arg) in args.iter().enumerate() {
+        if func_name == "__itof" {
+            load_value(func_data, arg, "t0", buf, params);
+        } else if let Some(&delta) = params.stack_state.get(&arg) {
+            load_and_save_reg("flw", &format!("ft{}", i), delta, buf);
+        } else {
+            // 立即数或者形参, 先放到整数寄存器里再搬过去
+            load_value(func_data, arg, "t0", buf, params);
+            writeln!(buf, "  fmv.w.x ft{}, t0", i).unwrap();
+        }
+    }
+    let delta = * params.stack_state.get(&inst).unwrap();
+    match func_name {
+        "__fadd" | "__fsub" | "__fmul" | "__fdiv" => {
+            writeln!(buf, "  f{}.s ft0, ft0, ft1", &func_name[3..]).unwrap();
+            load_and_save_reg("fsw", "ft0", delta, buf);
+            return;
+        }
+        "__fneg" => {
+            writeln!(buf, "  fneg.s ft0, ft0").unwrap();
+            load_and_save_reg("fsw", "ft0", delta, buf);
+            return;
+        }
+        "__feq" | "__flt" | "__fle" => {
+            writeln!(buf, "  f{}.s t0, ft0, ft1", &func_name[3..]).unwrap();
+        }
+        "__fne" => {
+            writeln!(buf, "  feq.s t0, ft0, ft1").unwrap();
+            writeln!(buf, "  seqz t0, t0").unwrap();
+        }
+        // a > b 就是 b < a
+        "__fgt" => {
+            writeln!(buf, "  flt.s t0, ft1, ft0").unwrap();
+        }
+        "__fge" => {
+            writeln!(buf, "  fle.s t0, ft1, ft0").unwrap();
+        }
+        "__itof" => {
+            writeln!(buf, "  fcvt.s.w ft0, t0").unwrap();
+            load_and_save_reg("fsw", "ft0", delta, buf);
+            return;
+        }
+        // 和C一样向0取整
+        "__ftoi" => {
+            writeln!(buf, "  fcvt.w.s t0, ft0, rtz").unwrap();
+        }
+        _ => unreachable!(),
+    }
+    load_and_save("sw".to_string(), 0, delta, buf);
+}
+
+// putf的每个参数从第几个字开始放, 以及是不是float
+// 和C的变参一样: 全部用a0-a7和栈, float要先变成double, 占对齐的两个字
+pub fn putf_arg_positions(sig: &str) -> Vec<(i32, bool)> {
+    let mut positions = vec![(0, false)]; // 格式串
+    let mut pos = 1;
+    for c in sig.chars() {
+        if c == 'f' {
+            pos = pos + pos % 2;
+            positions.push((pos, true));
+            pos += 2;
+        } else {
+            positions.push((pos, false));
+            pos += 1;
+        }
+    }
+    return positions;
+}
+
+// 把t0里的一个字放到第pos个位置
+fn putf_word_asm(reg_str: &str, pos: i32, buf: &mut Vec<u8>) {
+    if pos < 8 {
+        writeln!(buf, "  mv a{}, {}", pos, reg_str).unwrap();
+    } else {
+        load_and_save_reg("sw", reg_str, (pos - 8) * 4, buf);
+    }
+}
+
+// 调用putf, 被调用的是库里真正的putf
+pub fn putf_call_asm(func_data: &FunctionData, sig: &str, args: &[Value], buf: &mut Vec<u8>, params: &mut GenerateAsmParams) {
+    for (&arg, (pos, is_float)) in args.iter().zip(putf_arg_positions(sig)) {
+        load_value(func_data, arg, "t0", buf, params);
+        if is_float {
+            float_to_double_asm(buf, params);
+            putf_word_asm("t1", pos, buf);
+            putf_word_asm("t2", pos + 1, buf);
+        } else {
+            putf_word_asm("t0", pos, buf);
+        }
+    }
+    writeln!(buf, "  call putf").unwrap();
+}
+
+// 没有D扩展, 只好用整数指令把t0里的float拆开再拼成double: t1是低32位, t2是高32位
+// 指数的偏移从127换成1023, 尾数左移29位; 非规格化数要先规格化, inf和nan的指数全是1
+fn float_to_double_asm(buf: &mut Vec<u8>, params: &mut GenerateAsmParams) {
+    params.label_count += 1;
+    let label = format!(".Lf2d{}", params.label_count);
+    writeln!(buf, "  srli t2, t0, 23").unwrap();
+    writeln!(buf, "  andi t2, t2, 255").unwrap();
+    writeln!(buf, "  slli t3, t0, 9").unwrap();
+    writeln!(buf, "  srli t3, t3, 9").unwrap();
+    writeln!(buf, "  srli t4, t0, 31").unwrap();
+    writeln!(buf, "  slli t4, t4, 31").unwrap();
+    writeln!(buf, "  beqz t2, {}_small", label).unwrap();
+    writeln!(buf, "  li t5, 255").unwrap();
+    writeln!(buf, "  beq t2, t5, {}_inf", label).unwrap();
+    writeln!(buf, "  addi t2, t2, 896").unwrap();
+    writeln!(buf, "  j {}_pack", label).unwrap();
+    writeln!(buf, "{}_inf:", label).unwrap();
+    writeln!(buf, "  li t2, 2047").unwrap();
+    writeln!(buf, "  j {}_pack", label).unwrap();
+    writeln!(buf, "{}_small:", label).unwrap();
+    // 0的指数和尾数都是0, 直接拼
+    writeln!(buf, "  beqz t3, {}_pack", label).unwrap();
+    writeln!(buf, "  li t2, 897").unwrap();
+    writeln!(buf, "{}_norm:", label).unwrap();
+    writeln!(buf, "  slli t3, t3, 1").unwrap();
+    writeln!(buf, "  addi t2, t2, -1").unwrap();
+    writeln!(buf, "  srli t5, t3, 23").unwrap();
+    writeln!(buf, "  beqz t5, {}_norm", label).unwrap();
+    writeln!(buf, "  slli t3, t3, 9").unwrap();
+    writeln!(buf, "  srli t3, t3, 9").unwrap();
+    writeln!(buf, "{}_pack:", label).unwrap();
+    writeln!(buf, "  slli t1, t3, 29").unwrap();
+    writeln!(buf, "  srli t3, t3, 3").unwrap();
+    writeln!(buf, "  slli t2, t2, 20").unwrap();
+    writeln!(buf, "  or t2, t2, t3").unwrap();
+    writeln!(buf, "  or t2, t2, t4").unwrap();
+}
+
+// 全局变量的初值
+pub fn global_init_asm(program: &Program, init: Value, buf: &mut Vec<u8>) {
+    let init_data = program.borrow_value(init);
+    match init_data.kind() {
+        ValueKind::Integer(int_num) => {
+            writeln!(buf, "  .word {}", int_num.value()).unwrap();
+        }
+        ValueKind::ZeroInit(_) => {
+            writeln!(buf, "  .zero {}", init_data.ty().size()).unwrap();
+        }
+        ValueKind::Aggregate(aggregate) => {
+            for &elem in aggregate.elems() {
+                global_init_asm(program, elem, buf);
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+// alloc的类型是指针, 要分配的是它指向的那个类型
+pub fn alloc_size(ty: &Type) -> usize {
+    match ty.kind() {
+        TypeKind::Pointer(base) => base.size(),
+        _ => unreachable!(),
+    }
+}
+
+// 局部变量的alloc, 它的栈空间里直接就是数据
+pub fn is_local_alloc(func_data: &FunctionData, value: Value, params: &GenerateAsmParams) -> bool {
+    if params.global_names.contains_key(&value) {
+        return false;
+    }
+    return matches!(func_data.dfg().value(value).kind(), ValueKind::Alloc(_));
+}
+
+// 把一个指针指向的地址放进寄存器
+pub fn load_addr(func_data: &FunctionData, ptr: Value, reg_str: &str, buf: &mut Vec<u8>, params: &mut GenerateAsmParams) {
+    if let Some(global_name) = params.global_names.get(&ptr) {
+        writeln!(buf, "  la {}, {}", reg_str, global_name).unwrap();
+    } else if is_local_alloc(func_data, ptr, params) {
+        let delta = * params.stack_state.get(&ptr).unwrap();
+        if delta >= 2048 {
+            writeln!(buf, "  li {}, {}", reg_str, delta).unwrap();
+            writeln!(buf, "  add {}, sp, {}", reg_str, reg_str).unwrap();
+        } else {
+            writeln!(buf, "  addi {}, sp, {}", reg_str, delta).unwrap();
+        }
+    } else {
+        // getelemptr之类算出来的指针, 栈上存的是地址
+        load_value(func_data, ptr, reg_str, buf, params);
+    }
+}
+
+// getelemptr和getptr都是 src + index * 元素大小
+pub fn get_ptr_asm(func_data: &FunctionData, inst: Value, src: Value, index: Value, buf: &mut Vec<u8>, params: &mut GenerateAsmParams) {
+    let stride = match func_data.dfg().value(inst).ty().kind() {
+        TypeKind::Pointer(base) => base.size(),
+        _ => unreachable!(),
+    };
+    load_addr(func_data, src, "t0", buf, params);
+    match func_data.dfg().value(index).kind() {
+        ValueKind::Integer(int_num) => {
+            let offset = int_num.value() * stride as i32;
+            if offset != 0 {
+                writeln!(buf, "  li t1, {}", offset).unwrap();
+                writeln!(buf, "  add t0, t0, t1").unwrap();
+            }
+        }
+        _ => {
+            load_value(func_data, index, "t1", buf, params);
+            writeln!(buf, "  li t2, {}", stride).unwrap();
+            writeln!(buf, "  mul t1, t1, t2").unwrap();
+            writeln!(buf, "  add t0, t0, t1").unwrap();
+        }
+    }
+    let delta = * params.stack_state.get(&inst).unwrap();
+    load_and_save("sw".to_string(), 0, delta, buf);
+}
+
+// 基本块的名字只在函数内唯一, 所以要带上函数名
+pub fn bb_label(func_data: &FunctionData, bb: BasicBlock) -> String {
+    let func_name = func_data.name().replace("@", "");
+    let bb_name = func_data.dfg().bb(bb).name().as_ref().unwrap().replace("%", "");
+    format!(".L{}_{}", func_name, bb_name)
+}
+
+// IR里的switch是一串比较块: 每块里 eq + br, 不相等就到链上的下一块, 最后一块直接jump到default;
+// 哪几块是一条链, 生成IR的时候记在switch_chains里
+pub struct SwitchChain {
+    pub scrutinee: Value,
+    pub cases: Vec<(i32, BasicBlock)>,
+    pub default: BasicBlock,
+    pub bbs: Vec<BasicBlock>, // 链上除了第一块以外的块
+}
+
+// 两个操作数是不是同一个值, 常数每次都是新的Value, 要比较数值
+fn same_operand(func_data: &FunctionData, lhs: Value, rhs: Value) -> bool {
+    if lhs == rhs {
+        return true;
+    }
+    match (func_data.dfg().value(lhs).kind(), func_data.dfg().value(rhs).kind()) {
+        (ValueKind::Integer(l), ValueKind::Integer(r)) => return l.value() == r.value(),
+        _ => return false,
+    }
+}
+
+// head是某条比较链的第一块的话, 沿着链把case和default取出来; 不是或者形状不对就返回None, 按普通的块生成
+pub fn switch_chain(func_data: &FunctionData, head: BasicBlock, params: &GenerateAsmParams) -> Option<SwitchChain> {
+    let head_name = func_data.dfg().bb(head).name().clone()?;
+    let names = params
+        .switch_chains
+        .get(&func_data.name().replace("@", ""))?
+        .iter()
+        .find(|names| names[0] == head_name)?;
+    let mut scrutinee: Option<Value> = None;
+    let mut cases: Vec<(i32, BasicBlock)> = Vec::new();
+    let mut bbs: Vec<BasicBlock> = Vec::new();
+    let mut bb = head;
+    for next_name in &names[1..] {
+        let insts: Vec<Value> = func_data.layout().bbs().node(&bb)?.insts().keys().copied().collect();
+        let [eq, br] = insts.as_slice() else {
+            return None;
+        };
+        let ValueKind::Binary(binary) = func_data.dfg().value(*eq).kind() else {
+            return None;
+        };
+        let ValueKind::Branch(branch) = func_data.dfg().value(*br).kind() else {
+            return None;
+        };
+        let ValueKind::Integer(case_val) = func_data.dfg().value(binary.rhs()).kind() else {
+            return None;
+        };
+        if binary.op() != BinaryOp::Eq || branch.cond() != *eq {
+            return None;
+        }
+        match scrutinee {
+            Some(value) if !same_operand(func_data, value, binary.lhs()) => return None,
+            _ => scrutinee = Some(binary.lhs()),
+        }
+        cases.push((case_val.value(), branch.true_bb()));
+        bb = branch.false_bb();
+        if func_data.dfg().bb(bb).name().as_ref() != Some(next_name) {
+            return None;
+        }
+        bbs.push(bb);
+    }
+    // 最后一块跳到default
+    let insts: Vec<Value> = func_data.layout().bbs().node(&bb)?.insts().keys().copied().collect();
+    let [jump] = insts.as_slice() else {
+        return None;
+    };
+    let ValueKind::Jump(jump) = func_data.dfg().value(*jump).kind() else {
+        return None;
+    };
+    return Some(SwitchChain {
+        scrutinee: scrutinee?,
+        cases,
+        default: jump.target(),
+        bbs,
+    });
+}
+
+// case比较密的时候用跳转表, 否则二分比较
+pub fn switch_asm(func_data: &FunctionData, head: BasicBlock, chain: &SwitchChain, buf: &mut Vec<u8>, params: &mut GenerateAsmParams) {
+    let head_label = bb_label(func_data, head);
+    let default_label = bb_label(func_data, chain.default);
+    let mut cases: Vec<(i32, String)> = chain
+        .cases
+        .iter()
+        .map(|(val, bb)| (*val, bb_label(func_data, *bb)))
+        .collect();
+    cases.sort_by_key(|(val, _)| *val);
+    load_value(func_data, chain.scrutinee, "t0", buf, params);
+    if cases.is_empty() {
+        writeln!(buf, "  j {}", default_label).unwrap();
+        return;
+    }
+    let min = cases[0].0 as i64;
+    let range = cases[cases.len() - 1].0 as i64 - min + 1;
+    if cases.len() >= 4 && range <= 3 * cases.len() as i64 {
+        // 减去最小值之后按无符号比较, 负数也一起跳到default
+        writeln!(buf, "  li t1, {}", min).unwrap();
+        writeln!(buf, "  sub t0, t0, t1").unwrap();
+        writeln!(buf, "  li t1, {}", range).unwrap();
+        writeln!(buf, "  bgeu t0, t1, {}", default_label).unwrap();
+        writeln!(buf, "  slli t0, t0, 2").unwrap();
+        writeln!(buf, "  la t1, {}_table", head_label).unwrap();
+        writeln!(buf, "  add t1, t1, t0").unwrap();
+        writeln!(buf, "  lw t1, 0(t1)").unwrap();
+        writeln!(buf, "  jr t1").unwrap();
+        writeln!(buf, "  .section .rodata").unwrap();
+        writeln!(buf, "  .p2align 2").unwrap();
+        writeln!(buf, "{}_table:", head_label).unwrap();
+        let mut cases = cases.iter().peekable();
+        for val in min..min + range {
+            match cases.peek() {
+                Some((case_val, case_label)) if *case_val as i64 == val => {
+                    writeln!(buf, "  .word {}", case_label).unwrap();
+                    cases.next();
+                }
+                _ => {
+                    writeln!(buf, "  .word {}", default_label).unwrap();
+                }
+            }
+        }
+        writeln!(buf, "  .text").unwrap();
+    } else {
+        switch_tree_asm(&cases, &default_label, &head_label, buf);
+    }
+}
+
+// 二分比较, 值在t0里; 小于中间值的跳到左边, 右边直接接着往下走
+fn switch_tree_asm(cases: &[(i32, String)], default_label: &str, label: &str, buf: &mut Vec<u8>) {
+    if cases.len() <= 3 {
+        for (val, case_label) in cases {
+            writeln!(buf, "  li t1, {}", val).unwrap();
+            writeln!(buf, "  beq t0, t1, {}", case_label).unwrap();
+        }
+        writeln!(buf, "  j {}", default_label).unwrap();
+        return;
+    }
+    let mid = cases.len() / 2;
+    let left_label = format!("{}_l", label);
+    writeln!(buf, "  li t1, {}", cases[mid].0).unwrap();
+    writeln!(buf, "  beq t0, t1, {}", cases[mid].1).unwrap();
+    writeln!(buf, "  blt t0, t1, {}", left_label).unwrap();
+    switch_tree_asm(&cases[mid + 1..], default_label, &format!("{}_r", label), buf);
+    writeln!(buf, "{}:", left_label).unwrap();
+    switch_tree_asm(&cases[..mid], default_label, &left_label, buf);
+}
@@ -1,16 +1,20 @@
 use std::{
     collections::HashMap,
-    io::{stderr, Write},
+    io::Write,
 };
 
 use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::flow::Jump;
+use crate::sema::{DeclId, SemaInfo};
 
 pub struct GenerateIRParams {
     pub var_count: i32,
     pub func_returned: bool,
     pub if_level: i32, // 判断当前if的层数
     // pub first_num: i32,
-    pub sym_tab: SymTable,
+    pub sema: SemaInfo, // 语义分析的结果, 每个名字指向哪个声明
+    pub symbols: HashMap<DeclId, SymVal>, // 每个声明在IR里的名字和类型
     pub cur_var_idx: HashMap<String, i32>, // 当前IR变量的下标
     pub else_idx: i32,
     pub then_idx: i32,
@@ -19,113 +23,634 @@ pub struct GenerateIRParams {
     pub jump_true_branch: i32, // 短路要跳转的地方
     pub jump_false_branch: i32,
     pub while_idx: i32,
-    pub while_level: i32,
+    pub loop_stack: Vec<(i32, String)>, // 所在的各层循环: 序号, 以及continue要跳到的标号
+    pub break_stack: Vec<String>, // break要跳到的标号, 循环和switch都会压进来
+    pub switch_idx: i32,
+    pub cond_idx: i32, // 条件表达式的序号
+    pub cond_stack: Vec<i32>, // 正在求值条件的那些条件表达式
+    pub logic_idx: i32, // 不在条件里的 && 和 || 的序号
+    pub logic_stack: Vec<i32>, // 正在短路求值的那些 && 和 ||
+    pub lib_funcs: HashMap<String, bool>, // 运行时库的函数有没有被用到
+    pub func_type: FuncType, // 当前函数的返回值类型
+    pub func_ident: String, // 当前函数的名字
+    pub switch_chains: SwitchChains,
+    pub decl_type: BType, // 当前声明的变量的类型
+    pub str_lits: Vec<Vec<u8>>, // 字符串字面量, 最后作为全局数组放到最前面
+    pub putf_decls: Vec<String>, // putf用到的每种变参组合, i是int, f是float
+    pub ptr_decls: Vec<String>, // 比较过或者当成空指针用过的指针类型, 见generate_ptr_intrinsic
+    pub structs: HashMap<String, StructInfo>, // 定义过的结构体的布局
+    pub func_decls: Vec<(String, FuncType, Vec<String>)>, // 只声明了还没定义的函数, 最后写成decl
+    pub span: Span, // 正在生成的语句或表达式在源代码里的位置, 报错的时候指向它
+    pub diags: Vec<Diagnostic>, // 生成过程中的报错和警告
+}
+
+impl GenerateIRParams {
+    // 报在当前的位置上
+    pub fn error(&mut self, code: &str, message: &str) {
+        let span = self.span;
+        self.error_at(span, code, message);
+    }
+    pub fn error_at(&mut self, span: Span, code: &str, message: &str) {
+        self.diags.push(Diagnostic::error(code, message).with_primary(span, ""));
+    }
+    pub fn warning(&mut self, code: &str, message: &str) {
+        self.diags
+            .push(Diagnostic::warning(code, message).with_primary(self.span, ""));
+    }
+    // 展开初值的时候同一个定义里可能碰到好几次, 只报一次
+    pub fn warning_once(&mut self, code: &str, message: &str) {
+        let span = self.span;
+        let reported = self
+            .diags
+            .iter()
+            .any(|diag| diag.code == code && diag.primary.as_ref().is_some_and(|label| label.span == span));
+        if !reported {
+            self.warning(code, message);
+        }
+    }
+    pub fn report(&mut self, diag: Diagnostic) {
+        self.diags.push(diag);
+    }
+    // 声明的位置就是它在SemaInfo里的DeclId
+    pub fn declare(&mut self, span: Span, sym_val: SymVal) {
+        let id = self.sema.def(span);
+        self.symbols.insert(id, sym_val);
+    }
+    pub fn symbol(&self, id: DeclId) -> SymVal {
+        return self.symbols[&id].clone();
+    }
+    // 生成一个节点的时候把位置换成它的, 生成完再换回来
+    pub fn with_span<T>(&mut self, span: Span, f: impl FnOnce(&mut GenerateIRParams) -> T) -> T {
+        let outer = self.span;
+        self.span = span;
+        let res = f(self);
+        self.span = outer;
+        return res;
+    }
+}
+
+// 结构体的布局: Koopa里没有结构体类型, 整个结构体是一个 [i32, n],
+// 成员按偏移用getelemptr去取. 偏移和大小都是字节, 和C一样按对齐要求往后排.
+// 结构体类型的成员直接把里面那个结构体的字摊开放在它的偏移上
+#[derive(Clone)]
+pub struct StructInfo {
+    pub fields: Vec<FieldInfo>,
+    pub size: i32,
+    pub align: i32,
+    pub word_types: Vec<BType>, // 每个字是int还是float, 初始化的时候要按成员的类型转换
+}
+
+#[derive(Clone)]
+pub struct FieldInfo {
+    pub ident: String,
+    pub btype: BType,
+    pub dims: Vec<i32>, // 数组成员的各个维度
+    pub offset: i32,
+}
+
+impl StructInfo {
+    pub fn words(&self) -> i32 {
+        return self.size / 4;
+    }
+
+    pub fn field(&self, ident: &str) -> Option<&FieldInfo> {
+        return self.fields.iter().find(|field| field.ident == ident);
+    }
+}
+
+// 函数名 -> (每个参数是不是float, 返回值是不是float)
+pub type FloatSigs = HashMap<String, (Vec<bool>, bool)>;
+
+// 函数名 -> 里面每个switch的比较链上的基本块, 第一块是入口; 后端照着它把比较链换成跳转表或者二分比较
+pub type SwitchChains = HashMap<String, Vec<Vec<String>>>;
+
+// SysY运行时库里的函数: 名字, 参数类型, 返回值类型
+// f32在Koopa里写成i32, 见koopa_type_str
+pub const SYSY_LIB_FUNCS: [(&str, &[&str], FuncType); 12] = [
+    ("getint", &[], FuncType::Int),
+    ("getch", &[], FuncType::Int),
+    ("getfloat", &[], FuncType::Float),
+    ("getarray", &["*i32"], FuncType::Int),
+    ("getfarray", &["*f32"], FuncType::Int),
+    ("putint", &["i32"], FuncType::Void),
+    ("putch", &["i32"], FuncType::Void),
+    ("putfloat", &["f32"], FuncType::Void),
+    ("putarray", &["i32", "*i32"], FuncType::Void),
+    ("putfarray", &["i32", "*f32"], FuncType::Void),
+    ("_sysy_starttime", &["i32"], FuncType::Void),
+    ("_sysy_stoptime", &["i32"], FuncType::Void),
+];
+
+// Koopa没有浮点类型, float在IR里存的是它的二进制表示(i32),
+// 浮点运算写成对下面这些函数的调用, 后端再换成RV32F的指令.
+// 名字, 参数个数, 结果是不是float
+pub const FLOAT_INTRINSICS: [(&str, usize, bool); 13] = [
+    ("__fadd", 2, true),
+    ("__fsub", 2, true),
+    ("__fmul", 2, true),
+    ("__fdiv", 2, true),
+    ("__fneg", 1, true),
+    ("__feq", 2, false),
+    ("__fne", 2, false),
+    ("__flt", 2, false),
+    ("__fle", 2, false),
+    ("__fgt", 2, false),
+    ("__fge", 2, false),
+    ("__itof", 1, true),
+    ("__ftoi", 1, false),
+];
+
+// 局部变量在Koopa里叫 @x_N, 同名的变量N往上加, 不会重名
+pub fn load_var_to_sym_tab(var_name: String, span: Span, params: &mut GenerateIRParams) -> i32 {
+    let idx = params.cur_var_idx.get(&var_name).map_or(1, |pre_idx| *pre_idx + 1);
+    params.cur_var_idx.insert(var_name, idx);
+    params.declare(span, SymVal::VarName(idx, params.decl_type.clone()));
+    return idx;
 }
 
 #[derive(Clone)]
-pub struct SymTable {
-    pub table: HashMap<String, SymVal>,
-    pub prev: Option<Box<SymTable>>,
-    pub next: Option<Box<SymTable>>,
-    pub level: i32,
-    // 符号表是一层一层的解开的, 应该类似于一个列表
-    // 而不是一个树状结构
+pub enum ExpResult {
+    RegCount(i32),
+    IntResult(i32),
+    FloatRegCount(i32), // 结果是float的临时变量, 编号和RegCount一样
+    FloatResult(f32),
+    PtrRegCount(i32, BType), // 结果是指针的临时变量, 后面是这个指针的类型
 }
 
-impl SymTable {
-    pub fn new(level: i32) -> Self {
-        SymTable {
-            table: HashMap::new(),
-            prev: None,
-            next: None,
-            level: level,
+impl ExpResult {
+    pub fn btype(&self) -> BType {
+        match self {
+            ExpResult::RegCount(_) | ExpResult::IntResult(_) => return BType::Int,
+            ExpResult::FloatRegCount(_) | ExpResult::FloatResult(_) => return BType::Float,
+            ExpResult::PtrRegCount(_, btype) => return btype.clone(),
         }
     }
-    pub fn insert(&mut self, name: String, val: SymVal) {
-        self.table.insert(name, val);
+
+    pub fn const_value(&self) -> Option<ConstValue> {
+        match self {
+            ExpResult::IntResult(int_res) => return Some(ConstValue::Int(*int_res)),
+            ExpResult::FloatResult(float_res) => return Some(ConstValue::Float(*float_res)),
+            _ => return None,
+        }
     }
-    pub fn query(&self, name: String) -> Option<SymVal> {
-        if let Some(sym_val) = self.table.get(&name) {
-            return Some(sym_val.clone());
+}
+
+// 编译期算出来的常量, 可能是int也可能是float
+#[derive(Clone, Copy)]
+pub enum ConstValue {
+    Int(i32),
+    Float(f32),
+}
+
+impl ConstValue {
+    pub fn btype(&self) -> BType {
+        match self {
+            ConstValue::Int(_) => return BType::Int,
+            ConstValue::Float(_) => return BType::Float,
         }
-        if let Some(prev_table) = &self.prev {
-            return prev_table.query(name);
+    }
+
+    // 转成int是向0取整, 和C一样
+    pub fn to_int(&self) -> i32 {
+        match self {
+            ConstValue::Int(val) => return *val,
+            ConstValue::Float(val) => return *val as i32,
         }
-        return None;
     }
-    pub fn insert_table(&mut self, level: i32) -> &mut SymTable {
-        let mut new_table = Box::new(SymTable::new(level));
-        new_table.prev = Some(Box::new(self.clone()));
 
-        if let Some(ref mut next_table) = self.next {
-            next_table.prev = Some(new_table.clone());
+    pub fn to_float(&self) -> f32 {
+        match self {
+            ConstValue::Int(val) => return *val as f32,
+            ConstValue::Float(val) => return *val,
         }
-        self.next = Some(new_table);
-        // 返回这个新建立的符号表
-        self.next.as_mut().unwrap().as_mut()
     }
-    pub fn delete_table(&mut self) -> Option<&mut SymTable> {
-        if let Some(ref mut prev_table) = self.prev {
-            // 断开前一个表与当前表的链接
-            prev_table.next = None;
-            // 返回前一个表的可变引用
-            Some(&mut **prev_table)
-        } else {
-            // 如果没有前一个表，则返回 None
-            None
+
+    // 指针没有编译期的值, 只有全局的0, 见VarDef::generate_global_koopa_ir
+    pub fn convert(&self, btype: &BType) -> ConstValue {
+        match btype {
+            BType::Int | BType::Pointer(_) | BType::Struct(_) => return ConstValue::Int(self.to_int()),
+            BType::Float => return ConstValue::Float(self.to_float()),
         }
     }
+
+    pub fn is_true(&self) -> bool {
+        match self {
+            ConstValue::Int(val) => return *val != 0,
+            ConstValue::Float(val) => return *val != 0.0,
+        }
+    }
+
+    // 写到Koopa里的样子, float写它的二进制表示
+    pub fn bits(&self) -> i32 {
+        match self {
+            ConstValue::Int(val) => return *val,
+            ConstValue::Float(val) => return val.to_bits() as i32,
+        }
+    }
+
+    pub fn to_exp_result(&self) -> ExpResult {
+        match self {
+            ConstValue::Int(val) => return ExpResult::IntResult(*val),
+            ConstValue::Float(val) => return ExpResult::FloatResult(*val),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum SymVal {
+    ConstVal(ConstValue),
+    VarName(i32, BType), // 标记是同名的第几个变量.
+    ArrayName(i32, Vec<i32>, BType), // 数组, 以及它的各个维度
+    ConstArray(i32, Vec<i32>, Vec<ConstValue>, BType), // 常量数组, 维度和展开后的值, 编译期就能取
+    PointerName(i32, Vec<i32>, BType), // 数组形参, 是指向后面几维的指针
+    FuncName(FuncType, Vec<String>), // 函数名, 记录返回值类型和参数类型
+}
+
+// 沿着数组一层一层getelemptr下去, 返回最后的指针
+pub fn getelemptr_chain(base: String, indices: &[ExpResult], buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> String {
+    let mut ptr = base;
+    for index in indices {
+        writeln!(
+            buf,
+            "  %{} = getelemptr {}, {}",
+            params.var_count,
+            ptr,
+            exp_result_str(index)
+        )
+        .unwrap();
+        ptr = format!("%{}", params.var_count);
+        params.var_count += 1;
+    }
+    return ptr;
 }
 
-pub fn load_var_to_sym_tab(var_name: String, params: &mut GenerateIRParams) -> i32 {
-    let idx_val = params.cur_var_idx.get(&var_name);
-    let idx: i32;
-    match idx_val {
-        Some(pre_idx) => {
-            idx = *pre_idx + 1;
+pub fn exp_result_str(exp_res: &ExpResult) -> String {
+    match exp_res {
+        ExpResult::RegCount(reg_count) | ExpResult::FloatRegCount(reg_count) | ExpResult::PtrRegCount(reg_count, _) => {
+            format!("%{}", reg_count - 1)
         }
-        None => {
-            idx = 1;
+        ExpResult::IntResult(int_res) => format!("{}", int_res),
+        ExpResult::FloatResult(float_res) => format!("{}", float_res.to_bits() as i32),
+    }
+}
+
+// 类型检查用的类型里float写成f32, 结构体写成struct S,
+// 写到Koopa里要换成i32和同样大小的 [i32, n]
+pub fn koopa_type_str(type_str: &str, params: &GenerateIRParams) -> String {
+    let mut res = String::new();
+    let mut rest = type_str;
+    while let Some(pos) = rest.find("struct ") {
+        res.push_str(&rest[..pos]);
+        rest = &rest[pos + "struct ".len()..];
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let words = params.structs.get(&rest[..end]).map_or(0, |info| info.words());
+        res.push_str(&format!("[i32, {}]", words));
+        rest = &rest[end..];
+    }
+    res.push_str(rest);
+    return res.replace("f32", "i32");
+}
+
+pub fn btype_str(btype: &BType) -> String {
+    match btype {
+        BType::Int => return "i32".to_string(),
+        BType::Float => return "f32".to_string(),
+        BType::Pointer(base) => return format!("*{}", btype_str(base)),
+        BType::Struct(ident) => return format!("struct {}", ident),
+    }
+}
+
+// 结构体占几个字, 不是结构体或者没定义过就是0
+pub fn struct_words(btype: &BType, params: &GenerateIRParams) -> i32 {
+    match btype {
+        BType::Struct(ident) => return params.structs.get(ident).map_or(0, |info| info.words()),
+        _ => return 0,
+    }
+}
+
+pub fn struct_info(btype: &BType, params: &GenerateIRParams) -> Option<StructInfo> {
+    match btype {
+        BType::Struct(ident) => return params.structs.get(ident).cloned(),
+        _ => return None,
+    }
+}
+
+// 声明变量的时候结构体要已经定义过了, 不然不知道要分配多大
+pub fn check_complete_type(var_name: &str, btype: &BType, params: &mut GenerateIRParams) {
+    if let BType::Struct(ident) = btype {
+        if !params.structs.contains_key(ident) {
+            params.error(
+                "E0503",
+                &format!("storage size of {} isn't known (struct {} is not defined)", var_name, ident),
+            );
         }
     }
-    params.cur_var_idx.insert(var_name.clone(), idx);
-    let mut alias = params.sym_tab.query(var_name.clone());
-    if let Some(sym_val) = alias.take() {
-        match sym_val {
-            SymVal::ConstVal(_) => {
-                params
-                    .sym_tab
-                    .insert(var_name.clone(), SymVal::VarName(idx));
-                return idx;
-            }
-            SymVal::VarName(_idx) => {
-                params
-                    .sym_tab
-                    .insert(var_name.clone(), SymVal::VarName(idx));
-                return idx;
-            }
+}
+
+// 结构体赋值是整个复制一遍: 右边也要是同类型的左值, 一个字一个字地搬过去
+// dst_member是结构体类型的成员的话, 地址是 *i32, 用getptr按字偏移
+pub fn generate_struct_copy(dst: &str, dst_member: bool, btype: &BType, exp: &Exp, buf: &mut Vec<u8>, params: &mut GenerateIRParams) {
+    let (src, src_member) = match exp.as_unary_exp() {
+        Some(unary_exp) => (unary_exp.generate_addr(buf, params), unary_exp.is_struct_member(params)),
+        None => (None, false),
+    };
+    let src = match src {
+        Some((src, src_type)) if src_type == *btype => src,
+        _ => {
+            params.error("E0201", &format!("incompatible types when assigning to type {}", btype_str(btype)));
+            return;
+        }
+    };
+    let word_inst = |is_member: bool| if is_member { "getptr" } else { "getelemptr" };
+    for i in 0..struct_words(btype, params) {
+        writeln!(buf, "  %{} = {} {}, {}", params.var_count, word_inst(src_member), src, i).unwrap();
+        writeln!(buf, "  %{} = load %{}", params.var_count + 1, params.var_count).unwrap();
+        writeln!(buf, "  %{} = {} {}, {}", params.var_count + 2, word_inst(dst_member), dst, i).unwrap();
+        writeln!(buf, "  store %{}, %{}", params.var_count + 1, params.var_count + 2).unwrap();
+        params.var_count += 3;
+    }
+}
+
+// 调用一个浮点运算的函数, 参数都是float的二进制表示
+pub fn generate_float_intrinsic(
+    name: &str,
+    args: &[ExpResult],
+    buf: &mut Vec<u8>,
+    params: &mut GenerateIRParams,
+) -> ExpResult {
+    params.lib_funcs.insert(name.to_string(), true);
+    let args: Vec<String> = args.iter().map(exp_result_str).collect();
+    writeln!(buf, "  %{} = call @{}({})", params.var_count, name, args.join(", ")).unwrap();
+    params.var_count += 1;
+    let returns_float = FLOAT_INTRINSICS
+        .iter()
+        .any(|(intrinsic, _, returns_float)| *intrinsic == name && *returns_float);
+    if returns_float {
+        return ExpResult::FloatRegCount(params.var_count);
+    }
+    return ExpResult::RegCount(params.var_count);
+}
+
+// Koopa里指针和整数之间不能转换, 指针的比较和空指针交给后端:
+// __ptoi_X(p)把X类型的指针p当成地址的整数, __null_X()是X类型的空指针, 每种指针类型单独声明一个
+pub fn generate_ptr_intrinsic(name: &str, ptr_type: &str, args: &[ExpResult], buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> String {
+    if !params.ptr_decls.iter().any(|ty| ty == ptr_type) {
+        params.ptr_decls.push(ptr_type.to_string());
+    }
+    let name = format!("{}_{}", name, ptr_type_sig(ptr_type));
+    params.lib_funcs.insert(name.clone(), true);
+    let args: Vec<String> = args.iter().map(exp_result_str).collect();
+    writeln!(buf, "  %{} = call @{}({})", params.var_count, name, args.join(", ")).unwrap();
+    params.var_count += 1;
+    return format!("%{}", params.var_count - 1);
+}
+
+// 指针类型写成能放进名字里的样子: *[i32, 3] 变成 pai32_3_
+pub fn ptr_type_sig(ptr_type: &str) -> String {
+    return ptr_type.replace('*', "p").replace('[', "a").replace(']', "_").replace(", ", "_");
+}
+
+// 指针比较先变成整数再比
+pub fn generate_ptr_to_int(ptr: &ExpResult, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
+    let ptr_type = koopa_type_str(&btype_str(&ptr.btype()), params);
+    generate_ptr_intrinsic("__ptoi", &ptr_type, std::slice::from_ref(ptr), buf, params);
+    return ExpResult::RegCount(params.var_count);
+}
+
+// 和C一样, 值是0的整数常量可以当成任何类型的空指针
+pub fn is_null_pointer_constant(exp_res: &ExpResult) -> bool {
+    return matches!(exp_res, ExpResult::IntResult(0));
+}
+
+pub fn generate_null(btype: &BType, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
+    let ptr_type = koopa_type_str(&btype_str(btype), params);
+    generate_ptr_intrinsic("__null", &ptr_type, &[], buf, params);
+    return ExpResult::PtrRegCount(params.var_count, btype.clone());
+}
+
+// int和float之间的隐式转换, 常数直接在编译期转
+// 指针只能给同样类型的指针, 或者是空指针常量0; 别的整数不能变成指针
+pub fn convert_exp_result(exp_res: ExpResult, btype: &BType, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
+    if matches!(btype, BType::Pointer(_)) && is_null_pointer_constant(&exp_res) {
+        return generate_null(btype, buf, params);
+    }
+    if matches!(exp_res, ExpResult::PtrRegCount(_, _)) || matches!(btype, BType::Pointer(_) | BType::Struct(_)) {
+        if exp_res.btype() != *btype {
+            params.error("E0201", &format!("cannot convert {} to {}", btype_str(&exp_res.btype()), btype_str(btype)));
+        }
+        return exp_res;
+    }
+    match (&exp_res, btype) {
+        (ExpResult::IntResult(int_res), BType::Float) => return ExpResult::FloatResult(*int_res as f32),
+        (ExpResult::FloatResult(float_res), BType::Int) => return ExpResult::IntResult(*float_res as i32),
+        (ExpResult::RegCount(_), BType::Float) => return generate_float_intrinsic("__itof", &[exp_res], buf, params),
+        (ExpResult::FloatRegCount(_), BType::Int) => return generate_float_intrinsic("__ftoi", &[exp_res], buf, params),
+        _ => return exp_res,
+    }
+}
+
+// 二元运算, op是Koopa里的名字; 有一边是float的话另一边也转成float
+pub fn generate_arith(op: &str, lhs: ExpResult, rhs: ExpResult, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
+    if matches!(lhs, ExpResult::PtrRegCount(_, _)) || matches!(rhs, ExpResult::PtrRegCount(_, _)) {
+        return generate_pointer_arith(op, lhs, rhs, buf, params);
+    }
+    // 两边都是常数就直接算出来; 除以0留到运行的时候
+    if let (Some(l), Some(r)) = (lhs.const_value(), rhs.const_value()) {
+        let div_by_zero = (op == "div" || op == "mod") && r.btype() == BType::Int && r.to_int() == 0;
+        if !div_by_zero {
+            return calc_const_arith(op, l, r, params).to_exp_result();
         }
+    }
+    if lhs.btype() == BType::Int && rhs.btype() == BType::Int {
+        return generate_binary_op(op, &lhs, &rhs, buf, params);
+    }
+    let lhs = convert_exp_result(lhs, &BType::Float, buf, params);
+    let rhs = convert_exp_result(rhs, &BType::Float, buf, params);
+    match op {
+        "add" | "sub" | "mul" | "div" | "eq" | "ne" | "lt" | "le" | "gt" | "ge" => {
+            return generate_float_intrinsic(&format!("__f{}", op), &[lhs, rhs], buf, params);
+        }
+        _ => {
+            params.error("E0202", &format!("invalid operands of type float to {}", op));
+            return ExpResult::IntResult(0);
+        }
+    }
+}
+
+// 指针只能加减一个整数, getptr会按指向的类型的大小去乘;
+// ==和!=可以比两个同样类型的指针, 或者指针和空指针常量0; 相减和大小比较不支持
+pub fn generate_pointer_arith(op: &str, lhs: ExpResult, rhs: ExpResult, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
+    if op == "eq" || op == "ne" {
+        let comparable = match (&lhs, &rhs) {
+            (ExpResult::PtrRegCount(_, l), ExpResult::PtrRegCount(_, r)) => l == r,
+            (ExpResult::PtrRegCount(_, _), other) | (other, ExpResult::PtrRegCount(_, _)) => is_null_pointer_constant(other),
+            _ => false,
+        };
+        if comparable {
+            let lhs = match lhs {
+                ExpResult::PtrRegCount(_, _) => generate_ptr_to_int(&lhs, buf, params),
+                _ => lhs,
+            };
+            let rhs = match rhs {
+                ExpResult::PtrRegCount(_, _) => generate_ptr_to_int(&rhs, buf, params),
+                _ => rhs,
+            };
+            return generate_binary_op(op, &lhs, &rhs, buf, params);
+        }
+    }
+    let (ptr, index) = match (op, &lhs, &rhs) {
+        ("add" | "sub", ExpResult::PtrRegCount(_, _), ExpResult::RegCount(_) | ExpResult::IntResult(_)) => (lhs, rhs),
+        ("add", ExpResult::RegCount(_) | ExpResult::IntResult(_), ExpResult::PtrRegCount(_, _)) => (rhs, lhs),
+        _ => {
+            params.error(
+                "E0202",
+                &format!("invalid operands to {} (have {} and {})", op, btype_str(&lhs.btype()), btype_str(&rhs.btype())),
+            );
+            return ExpResult::IntResult(0);
+        }
+    };
+    let index = if op == "sub" {
+        generate_arith("sub", ExpResult::IntResult(0), index, buf, params)
     } else {
-        params
-            .sym_tab
-            .insert(var_name.clone(), SymVal::VarName(idx));
-        return idx;
+        index
+    };
+    writeln!(
+        buf,
+        "  %{} = getptr {}, {}",
+        params.var_count,
+        exp_result_str(&ptr),
+        exp_result_str(&index)
+    )
+    .unwrap();
+    params.var_count += 1;
+    return ExpResult::PtrRegCount(params.var_count, ptr.btype());
+}
+
+// 编译期的二元运算, 规则和generate_arith一样
+// int和RISC-V一样按32位回绕, 溢出了给出警告
+pub fn calc_const_arith(op: &str, lhs: ConstValue, rhs: ConstValue, params: &mut GenerateIRParams) -> ConstValue {
+    if let (ConstValue::Int(l), ConstValue::Int(r)) = (lhs, rhs) {
+        if (op == "div" || op == "mod") && r == 0 {
+            params.error("E0505", "division by zero in constant expression");
+            return ConstValue::Int(0);
+        }
+        let checked = match op {
+            "add" => l.checked_add(r),
+            "sub" => l.checked_sub(r),
+            "mul" => l.checked_mul(r),
+            "div" => l.checked_div(r),
+            "mod" => l.checked_rem(r),
+            _ => Some(0),
+        };
+        if checked.is_none() {
+            warn_int_overflow(op, l, r, params);
+        }
+        let res = match op {
+            "add" => l.wrapping_add(r),
+            "sub" => l.wrapping_sub(r),
+            "mul" => l.wrapping_mul(r),
+            "div" => l.wrapping_div(r),
+            "mod" => l.wrapping_rem(r),
+            "and" => l & r,
+            "or" => l | r,
+            "xor" => l ^ r,
+            // 和RISC-V一样, 移位量只看低5位
+            "shl" => l.wrapping_shl(r as u32),
+            "sar" => l.wrapping_shr(r as u32),
+            "eq" => (l == r) as i32,
+            "ne" => (l != r) as i32,
+            "lt" => (l < r) as i32,
+            "le" => (l <= r) as i32,
+            "gt" => (l > r) as i32,
+            "ge" => (l >= r) as i32,
+            _ => unreachable!(),
+        };
+        return ConstValue::Int(res);
+    }
+    let (l, r) = (lhs.to_float(), rhs.to_float());
+    match op {
+        "add" => return ConstValue::Float(l + r),
+        "sub" => return ConstValue::Float(l - r),
+        "mul" => return ConstValue::Float(l * r),
+        "div" => return ConstValue::Float(l / r),
+        "eq" => return ConstValue::Int((l == r) as i32),
+        "ne" => return ConstValue::Int((l != r) as i32),
+        "lt" => return ConstValue::Int((l < r) as i32),
+        "le" => return ConstValue::Int((l <= r) as i32),
+        "gt" => return ConstValue::Int((l > r) as i32),
+        "ge" => return ConstValue::Int((l >= r) as i32),
+        _ => {
+            params.error("E0202", &format!("invalid operands of type float to {}", op));
+            return ConstValue::Int(0);
+        }
     }
 }
 
-pub enum ExpResult {
-    RegCount(i32),
-    IntResult(i32),
+pub fn warn_int_overflow(op: &str, lhs: i32, rhs: i32, params: &mut GenerateIRParams) {
+    params.warning("W0001", &format!("integer overflow in constant expression ({} {}, {})", op, lhs, rhs));
 }
 
-#[derive(Clone)]
-pub enum SymVal {
-    ConstVal(i32),
-    VarName(i32), // 标记是同名的第几个变量.
+// 超出int范围的整数字面量按32位截断, 并给出警告
+pub fn int_literal(num: i64, params: &mut GenerateIRParams) -> i32 {
+    if num < i32::MIN as i64 || num > i32::MAX as i64 {
+        params.warning("W0002", &format!("integer constant {} is too large for int, truncated to {}", num, num as i32));
+    }
+    return num as i32;
+}
+
+// br只看二进制是不是0, float要先和0.0比较 (-0.0也是假)
+// 指针没法和0比较, 交给generate_arith报错
+pub fn generate_cond(exp_res: ExpResult, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
+    if exp_res.btype() != BType::Int {
+        return generate_arith("ne", exp_res, ExpResult::IntResult(0), buf, params);
+    }
+    return exp_res;
+}
+
+// 从地址里读出一个值, 按类型标记结果
+pub fn generate_load(addr: &str, btype: &BType, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
+    if !matches!(btype, BType::Struct(_)) {
+        writeln!(buf, "  %{} = load {}", params.var_count, addr).unwrap();
+        params.var_count += 1;
+    }
+    match btype {
+        BType::Int => return ExpResult::RegCount(params.var_count),
+        BType::Float => return ExpResult::FloatRegCount(params.var_count),
+        BType::Pointer(_) => return ExpResult::PtrRegCount(params.var_count, btype.clone()),
+        BType::Struct(_) => {
+            // 结构体只能整个赋值, 或者取它的成员
+            params.error("E0203", &format!("{} cannot be used as a value", btype_str(btype)));
+            return ExpResult::IntResult(0);
+        }
+    }
+}
+
+// 检查实参的个数和类型是不是和形参对得上
+pub fn check_func_r_params(ident: &str, param_types: &[String], arg_types: &[String], params: &mut GenerateIRParams) {
+    if param_types.len() != arg_types.len() {
+        params.error(
+            "E0601",
+            &format!("{} takes {} arguments but {} were supplied", ident, param_types.len(), arg_types.len()),
+        );
+        return;
+    }
+    for (i, (param_type, arg_type)) in param_types.iter().zip(arg_types).enumerate() {
+        // 标量的int和float之间可以隐式转换
+        let both_scalar = !param_type.starts_with('*') && !arg_type.starts_with('*');
+        if param_type != arg_type && !both_scalar {
+            params.error(
+                "E0602",
+                &format!("argument {} of {} expects {} but got {}", i + 1, ident, param_type, arg_type),
+            );
+        }
+    }
 }
 
 impl CompUnit {
-    pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>) {
+    // 返回生成过程中的报错和警告, 有错误的话生成出来的IR不能用; 还有给后端用的switch的比较链
+    pub fn generate_koopa_ir(&self, sema: SemaInfo, buf: &mut Vec<u8>) -> (Vec<Diagnostic>, SwitchChains) {
         // do nothing
         // 计算整个程序的变量计数
         let mut params = GenerateIRParams {
@@ -133,12 +658,8 @@ impl CompUnit {
             func_returned: false,
             if_level: 0,
             // first_num: 0,
-            sym_tab: SymTable {
-                table: HashMap::new(),
-                prev: None,
-                next: None,
-                level: 0,
-            }, // 这个符号表就相当于一个全局的符号表
+            sema,
+            symbols: HashMap::new(),
             cur_var_idx: HashMap::new(),
             else_idx: 0,
             then_idx: 0,
@@ -147,27 +668,318 @@ impl CompUnit {
             jump_false_branch: 0,
             jump_true_branch: 0,
             while_idx: 0,
-            while_level: 0,
+            loop_stack: Vec::new(),
+            break_stack: Vec::new(),
+            switch_idx: 0,
+            cond_idx: 0,
+            cond_stack: Vec::new(),
+            logic_idx: 0,
+            logic_stack: Vec::new(),
+            lib_funcs: HashMap::new(),
+            func_type: FuncType::Int,
+            func_ident: String::new(),
+            switch_chains: HashMap::new(),
+            decl_type: BType::Int,
+            str_lits: Vec::new(),
+            putf_decls: Vec::new(),
+            ptr_decls: Vec::new(),
+            structs: HashMap::new(),
+            func_decls: Vec::new(),
+            span: Span::default(),
+            diags: Vec::new(),
         };
-        self.func_def.generate_koopa_ir(buf, &mut params);
+        // 运行时库的函数一开始就在全局符号表里
+        for (name, param_types, func_type) in SYSY_LIB_FUNCS.iter() {
+            let param_types = param_types.iter().map(|ty| ty.to_string()).collect();
+            let id = params.sema.builtins[*name];
+            params
+                .symbols
+                .insert(id, SymVal::FuncName(func_type.clone(), param_types));
+            params.lib_funcs.insert(name.to_string(), false);
+        }
+        // putf是变参的, 每种实参组合单独声明一个, 见generate_putf_call
+        let id = params.sema.builtins["putf"];
+        params
+            .symbols
+            .insert(id, SymVal::FuncName(FuncType::Void, Vec::new()));
+        params.lib_funcs.insert("putf".to_string(), false);
+        // 用到了哪些库函数要生成完才知道, 所以先写到另一个缓冲区里
+        let mut body_buf: Vec<u8> = Vec::new();
+        for global_item in &self.global_items {
+            match global_item {
+                GlobalItem::Decl(decl) => {
+                    decl.generate_global_koopa_ir(&mut body_buf, &mut params);
+                }
+                GlobalItem::FuncDef(func_def) => {
+                    writeln!(body_buf).unwrap();
+                    func_def.generate_koopa_ir(&mut body_buf, &mut params);
+                }
+                GlobalItem::FuncDecl(func_decl) => {
+                    func_decl.generate_koopa_ir(&mut params);
+                }
+                GlobalItem::StructDef(struct_def) => {
+                    struct_def.generate_koopa_ir(&mut params);
+                }
+            }
+        }
+        for (name, param_types, func_type) in SYSY_LIB_FUNCS.iter() {
+            if params.lib_funcs.get(*name) != Some(&true) {
+                continue;
+            }
+            let param_types = koopa_type_str(&param_types.join(", "), &params);
+            match func_type {
+                FuncType::Int | FuncType::Float => {
+                    writeln!(buf, "decl @{}({}): i32", name, param_types).unwrap();
+                }
+                FuncType::Void => {
+                    writeln!(buf, "decl @{}({})", name, param_types).unwrap();
+                }
+            }
+        }
+        // 到最后也没有定义的函数要到链接的时候去别的文件里找
+        for (name, func_type, param_types) in &params.func_decls {
+            let param_types = koopa_type_str(&param_types.join(", "), &params);
+            match func_type {
+                FuncType::Int | FuncType::Float => {
+                    writeln!(buf, "decl @{}({}): i32", name, param_types).unwrap();
+                }
+                FuncType::Void => {
+                    writeln!(buf, "decl @{}({})", name, param_types).unwrap();
+                }
+            }
+        }
+        for (name, arity, _) in FLOAT_INTRINSICS.iter() {
+            if params.lib_funcs.get(*name) != Some(&true) {
+                continue;
+            }
+            writeln!(buf, "decl @{}({}): i32", name, vec!["i32"; *arity].join(", ")).unwrap();
+        }
+        for sig in &params.putf_decls {
+            let mut param_types = vec!["*i32"];
+            param_types.extend(vec!["i32"; sig.len()]);
+            writeln!(buf, "decl @__putf_{}({})", sig, param_types.join(", ")).unwrap();
+        }
+        for ptr_type in &params.ptr_decls {
+            let sig = ptr_type_sig(ptr_type);
+            if params.lib_funcs.get(&format!("__ptoi_{}", sig)) == Some(&true) {
+                writeln!(buf, "decl @__ptoi_{}({}): i32", sig, ptr_type).unwrap();
+            }
+            if params.lib_funcs.get(&format!("__null_{}", sig)) == Some(&true) {
+                writeln!(buf, "decl @__null_{}(): {}", sig, ptr_type).unwrap();
+            }
+        }
+        // Koopa里没有i8, 字符串按小端四个字节拼成一个i32
+        for (i, str_lit) in params.str_lits.iter().enumerate() {
+            let words = pack_str(str_lit);
+            writeln!(
+                buf,
+                "global @__str{} = alloc {}, {}",
+                i,
+                array_type_str(&[words.len() as i32]),
+                aggregate_str(&words, &[words.len() as i32])
+            )
+            .unwrap();
+        }
+        buf.extend(body_buf);
+        return (params.diags, params.switch_chains);
+    }
+
+    // 后端要知道哪些参数和返回值是float, 才能按调用约定放到浮点寄存器里
+    pub fn float_signatures(&self) -> FloatSigs {
+        let mut sigs = HashMap::new();
+        for (name, param_types, func_type) in SYSY_LIB_FUNCS.iter() {
+            let float_params = param_types.iter().map(|ty| *ty == "f32").collect();
+            sigs.insert(name.to_string(), (float_params, matches!(func_type, FuncType::Float)));
+        }
+        for global_item in &self.global_items {
+            let (ident, func_type, func_f_params) = match global_item {
+                GlobalItem::FuncDef(func_def) => (&func_def.ident, &func_def.func_type, &func_def.func_f_params),
+                GlobalItem::FuncDecl(func_decl) => (&func_decl.ident, &func_decl.func_type, &func_decl.func_f_params),
+                _ => continue,
+            };
+            let float_params = func_f_params
+                .iter()
+                .map(|func_f_param| func_f_param.dims.is_none() && func_f_param.btype == BType::Float)
+                .collect();
+            sigs.insert(ident.clone(), (float_params, matches!(func_type, FuncType::Float)));
+        }
+        return sigs;
+    }
+}
+
+impl StructDef {
+    // Koopa里没有类型定义, 这里只是算出布局记下来
+    pub fn generate_koopa_ir(&self, params: &mut GenerateIRParams) {
+        if params.structs.contains_key(&self.ident) {
+            params.error_at(self.span, "E0504", &format!("redefinition of struct {}", self.ident));
+            return;
+        }
+        let mut fields: Vec<FieldInfo> = Vec::new();
+        let mut word_types: Vec<BType> = Vec::new();
+        let mut offset = 0;
+        let mut align = 1;
+        for field in &self.fields {
+            // int和float都是4个字节, 按4对齐; 结构体成员要在前面定义好
+            let (elem_size, elem_align, elem_word_types) = match &field.btype {
+                BType::Int | BType::Float => (4, 4, vec![field.btype.clone()]),
+                BType::Struct(ident) => match params.structs.get(ident) {
+                    Some(info) => (info.size, info.align, info.word_types.clone()),
+                    None => {
+                        params.error_at(
+                            field.span,
+                            "E0503",
+                            &format!("member {} has incomplete type struct {}", field.ident, ident),
+                        );
+                        continue;
+                    }
+                },
+                // 指针成员语义分析的时候已经报过错了, 有错误就不会生成IR
+                BType::Pointer(_) => unreachable!(),
+            };
+            if fields.iter().any(|prev| prev.ident == field.ident) {
+                params.error_at(field.span, "E0504", &format!("duplicate member {} in struct {}", field.ident, self.ident));
+                continue;
+            }
+            let dims = calc_dims(&field.dims, params);
+            offset = (offset + elem_align - 1) / elem_align * elem_align;
+            fields.push(FieldInfo {
+                ident: field.ident.clone(),
+                btype: field.btype.clone(),
+                dims: dims.clone(),
+                offset,
+            });
+            let count = dims.iter().product::<i32>();
+            word_types.resize((offset / 4) as usize, BType::Int);
+            for _ in 0..count {
+                word_types.extend(elem_word_types.iter().cloned());
+            }
+            offset += elem_size * count;
+            align = align.max(elem_align);
+        }
+        if fields.is_empty() {
+            params.error_at(self.span, "E0504", &format!("struct {} has no members", self.ident));
+            return;
+        }
+        let size = (offset + align - 1) / align * align;
+        word_types.resize((size / 4) as usize, BType::Int);
+        params
+            .structs
+            .insert(self.ident.clone(), StructInfo { fields, size, align, word_types });
+    }
+}
+
+// 形参的维度和类型, 数组形参 a[][4] 是 *[i32, 4]
+// 符号表里记的类型区分i32和f32, 写到Koopa里都是i32
+pub fn func_param_types(
+    func_f_params: &[FuncFParam],
+    params: &mut GenerateIRParams,
+) -> (Vec<Option<Vec<i32>>>, Vec<String>) {
+    let mut param_dims: Vec<Option<Vec<i32>>> = Vec::new();
+    for func_f_param in func_f_params {
+        match &func_f_param.dims {
+            Some(dims) => param_dims.push(Some(calc_dims(dims, params))),
+            None => param_dims.push(None),
+        }
+    }
+    let param_types: Vec<String> = param_dims
+        .iter()
+        .zip(func_f_params.iter())
+        .map(|(dims, func_f_param)| match dims {
+            Some(dims) => format!("*{}", typed_array_str(dims, &func_f_param.btype)),
+            None => btype_str(&func_f_param.btype),
+        })
+        .collect();
+    return (param_dims, param_types);
+}
+
+impl FuncDecl {
+    // 声明只是放进符号表; 同一个函数可以声明好几次, 但类型要一致.
+    // 同名的声明和定义在语义分析里是同一个DeclId
+    pub fn generate_koopa_ir(&self, params: &mut GenerateIRParams) {
+        let (_, param_types) = func_param_types(&self.func_f_params, params);
+        let id = params.sema.def(self.span);
+        match params.symbols.get(&id).cloned() {
+            Some(SymVal::FuncName(func_type, old_types)) => {
+                if func_type != self.func_type || old_types != param_types {
+                    params.error_at(self.span, "E0603", &format!("conflicting types for {}", self.ident));
+                }
+            }
+            _ => {
+                params.declare(self.span, SymVal::FuncName(self.func_type.clone(), param_types.clone()));
+                params
+                    .func_decls
+                    .push((self.ident.clone(), self.func_type.clone(), param_types));
+            }
+        }
     }
 }
 
 impl FuncDef {
     pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) {
-        if self.ident != "main" {
-            stderr()
-                .write_all(b"Error: only support main function\n")
-                .unwrap();
-            return;
+        params.func_returned = false;
+        params.func_type = self.func_type.clone();
+        params.func_ident = self.ident.clone();
+        // 形参在Koopa里叫 @x_0, 变量的下标从1开始, 不会重名
+        let (param_dims, param_types) = func_param_types(&self.func_f_params, params);
+        if let Some(func_f_param) = self.func_f_params.iter().find(|func_f_param| func_f_param.ident.is_empty()) {
+            params.error_at(
+                func_f_param.span,
+                "E0604",
+                &format!("parameter name omitted in definition of {}", self.ident),
+            );
         }
-        write!(buf, "fun @{}(): ", self.ident).unwrap();
+        // 前面声明过的话类型要一样, 定义了就不用再写decl了
+        let declared = params
+            .func_decls
+            .iter()
+            .find(|(ident, _, _)| *ident == self.ident);
+        if let Some((_, func_type, decl_types)) = declared {
+            if *func_type != self.func_type || *decl_types != param_types {
+                params.error_at(self.span, "E0603", &format!("conflicting types for {}", self.ident));
+            }
+        }
+        params.func_decls.retain(|(ident, _, _)| *ident != self.ident);
+        // 先放进全局符号表, 这样函数体里可以递归调用自己
+        // 和库函数同名的话, 就用自己定义的
+        params.lib_funcs.remove(&self.ident);
+        params.declare(self.span, SymVal::FuncName(self.func_type.clone(), param_types.clone()));
+        let func_f_params: Vec<String> = self
+            .func_f_params
+            .iter()
+            .zip(param_types.iter())
+            .map(|(func_f_param, param_type)| format!("@{}_0: {}", func_f_param.ident, koopa_type_str(param_type, params)))
+            .collect();
+        write!(buf, "fun @{}({})", self.ident, func_f_params.join(", ")).unwrap();
         self.func_type.generate_koopa_ir(buf);
         writeln!(buf, "%entry:").unwrap();
+        // 把形参复制到栈上
+        for (i, func_f_param) in self.func_f_params.iter().enumerate() {
+            if func_f_param.dims.is_none() && matches!(func_f_param.btype, BType::Struct(_)) {
+                params.report(
+                    Diagnostic::error("E0604", &format!("struct parameter {} must be passed by pointer", func_f_param.ident))
+                        .with_primary(func_f_param.span, "")
+                        .with_help("declare the parameter as a pointer to the struct and pass its address"),
+                );
+            }
+            params.decl_type = func_f_param.btype.clone();
+            let idx = load_var_to_sym_tab(func_f_param.ident.clone(), func_f_param.span, params);
+            writeln!(buf, "  @{}_{} = alloc {}", func_f_param.ident, idx, koopa_type_str(&param_types[i], params)).unwrap();
+            writeln!(buf, "  store @{}_0, @{}_{}", func_f_param.ident, func_f_param.ident, idx).unwrap();
+            if let Some(dims) = &param_dims[i] {
+                params.declare(func_f_param.span, SymVal::PointerName(idx, dims.clone(), func_f_param.btype.clone()));
+            }
+        }
         self.block.generate_koopa_ir(buf, params);
-        if params.func_returned == false {
-            // 没有return语句
-            writeln!(buf, "  ret undef").unwrap();
+        if !params.func_returned {
+            // 没有return语句, void直接返回, int返回0, float返回0.0 (二进制也是0)
+            match self.func_type {
+                FuncType::Void => {
+                    writeln!(buf, "  ret").unwrap();
+                }
+                FuncType::Int | FuncType::Float => {
+                    writeln!(buf, "  ret 0").unwrap();
+                }
+            }
         }
         writeln!(buf, "}}").unwrap();
     }
@@ -177,10 +989,10 @@ impl FuncType {
     pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>) {
         match self {
             FuncType::Void => {
-                writeln!(buf, "void {{").unwrap();
+                writeln!(buf, " {{").unwrap();
             }
-            FuncType::Int => {
-                writeln!(buf, "i32 {{").unwrap();
+            FuncType::Int | FuncType::Float => {
+                writeln!(buf, ": i32 {{").unwrap();
             }
         }
     }
@@ -188,18 +1000,12 @@ impl FuncType {
 
 impl Block {
     pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) {
-        // 新建一个符号表
-        params.sym_tab = params
-            .sym_tab
-            .insert_table(params.sym_tab.level + 1)
-            .clone();
         for block_item in &self.block_items {
             block_item.generate_koopa_ir(buf, params);
-            if params.func_returned == true {
+            if params.func_returned {
                 break;
             }
         }
-        params.sym_tab = params.sym_tab.delete_table().unwrap().clone();
     }
 }
 
@@ -220,7 +1026,7 @@ impl Decl {
     pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) {
         match self {
             Decl::ConstDecl(const_decl) => {
-                const_decl.calc_const(params);
+                const_decl.generate_koopa_ir(buf, params);
             }
             Decl::VarDecl(var_decl) => {
                 var_decl.generate_koopa_ir(buf, params);
@@ -229,33 +1035,240 @@ impl Decl {
     }
 }
 
+impl Decl {
+    // 全局的声明, 初值都必须在编译期算出来
+    pub fn generate_global_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) {
+        match self {
+            Decl::ConstDecl(const_decl) => {
+                if let BType::Struct(_) = const_decl.btype {
+                    params.error_at(const_decl.span, "E0504", "const structs are not supported");
+                    return;
+                }
+                for const_def in &const_decl.const_defs {
+                    params.decl_type = const_decl.btype.clone();
+                    params.with_span(const_def.span, |params| const_def.generate_global_koopa_ir(buf, params));
+                }
+            }
+            Decl::VarDecl(var_decl) => {
+                for var_def in &var_decl.var_defs {
+                    params.decl_type = var_decl.btype.clone();
+                    params.with_span(var_def.span(), |params| var_def.generate_global_koopa_ir(buf, params));
+                }
+            }
+        }
+    }
+}
+
+// 数组的类型, 比如 a[2][3] 是 [[i32, 3], 2]
+pub fn array_type_str(dims: &[i32]) -> String {
+    return typed_array_str(dims, &BType::Int);
+}
+
+// 检查类型用的, float数组的元素写成f32
+pub fn typed_array_str(dims: &[i32], btype: &BType) -> String {
+    let mut type_str = btype_str(btype);
+    for dim in dims.iter().rev() {
+        type_str = format!("[{}, {}]", type_str, dim);
+    }
+    return type_str;
+}
+
+// 全局数组的初值, 比如 {{1, 2, 3}, {4, 5, 6}}
+pub fn aggregate_str(vals: &[i32], dims: &[i32]) -> String {
+    if dims.is_empty() {
+        return format!("{}", vals[0]);
+    }
+    let stride = vals.len() / dims[0] as usize;
+    let elems: Vec<String> = vals
+        .chunks(stride)
+        .map(|chunk| aggregate_str(chunk, &dims[1..]))
+        .collect();
+    return format!("{{{}}}", elems.join(", "));
+}
+
+pub fn global_init_str(vals: &[i32], dims: &[i32]) -> String {
+    if vals.iter().all(|val| *val == 0) {
+        return "zeroinit".to_string();
+    }
+    return aggregate_str(vals, dims);
+}
+
+// 字符串加上结尾的0, 补齐到4的倍数, 每4个字节拼成一个i32
+pub fn pack_str(str_lit: &[u8]) -> Vec<i32> {
+    let mut bytes = str_lit.to_vec();
+    bytes.push(0);
+    while !bytes.len().is_multiple_of(4) {
+        bytes.push(0);
+    }
+    return bytes
+        .chunks(4)
+        .map(|chunk| i32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect();
+}
+
+pub fn calc_dims(dims: &[ConstExp], params: &mut GenerateIRParams) -> Vec<i32> {
+    let mut res = Vec::new();
+    for dim in dims {
+        let span = dim.exp.span;
+        let size = dim.calc_const(params);
+        if size.btype() == BType::Float {
+            params.error_at(span, "E0204", "size of array has non-integer type");
+        }
+        // 报错以后当成1接着往下走, 免得后面按0或者负数去分配
+        let size = size.to_int();
+        if size <= 0 {
+            let what = if size == 0 { "zero" } else { "negative" };
+            params.error_at(span, "E0204", &format!("size of array is {}", what));
+            res.push(1);
+            continue;
+        }
+        res.push(size);
+    }
+    return res;
+}
+
+// 遇到一个子列表时, 它要对齐到当前已经填了的元素个数能整除的最大的那一维
+// 标量外面多套的花括号也走到这里, 这时候子列表还是对应这一个标量
+pub fn sub_list_dims(filled: usize, dims: &[i32]) -> &[i32] {
+    if dims.is_empty() {
+        return dims;
+    }
+    let mut i = 1;
+    while i < dims.len() {
+        let size: i32 = dims[i..].iter().product();
+        if filled.is_multiple_of(size as usize) {
+            break;
+        }
+        i += 1;
+    }
+    return &dims[i..];
+}
+
+// 数组初始化: 先取到第一个元素的指针, 然后用getptr一个一个往里存
+pub fn store_array_init(array_ptr: &str, dims: &[i32], vals: &[String], buf: &mut Vec<u8>, params: &mut GenerateIRParams) {
+    let mut ptr = array_ptr.to_string();
+    for _ in dims {
+        writeln!(buf, "  %{} = getelemptr {}, 0", params.var_count, ptr).unwrap();
+        ptr = format!("%{}", params.var_count);
+        params.var_count += 1;
+    }
+    for (i, val) in vals.iter().enumerate() {
+        writeln!(buf, "  %{} = getptr {}, {}", params.var_count, ptr, i).unwrap();
+        writeln!(buf, "  store {}, %{}", val, params.var_count).unwrap();
+        params.var_count += 1;
+    }
+}
+
 impl ConstDecl {
-    pub fn calc_const(&self, params: &mut GenerateIRParams) {
-        // 直接存到符号表里
+    pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) {
+        // 常量不能是结构体, 语义分析里检查过了
         for const_def in &self.const_defs {
-            const_def.calc_const(params);
+            params.decl_type = self.btype.clone();
+            params.with_span(const_def.span, |params| const_def.generate_koopa_ir(buf, params));
         }
     }
 }
 
 impl ConstDef {
-    pub fn calc_const(&self, params: &mut GenerateIRParams) {
-        // 直接存到符号表里
-        let init_val = self.const_init_val.calc_const(params);
-        params
-            .sym_tab
-            .insert(self.ident.clone(), SymVal::ConstVal(init_val));
+    pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) {
+        let btype = params.decl_type.clone();
+        if self.dims.is_empty() {
+            // 直接存到符号表里
+            let init_val = self.const_init_val.calc_const(params).convert(&btype);
+            params.declare(self.span, SymVal::ConstVal(init_val));
+            return;
+        }
+        // 常量数组的值记在符号表里, 下标是常量时直接折叠;
+        // 栈上仍然留一份, 给变量下标和传参用
+        let dims = calc_dims(&self.dims, params);
+        let vals = self.const_init_val.calc_array(&dims, &btype, params);
+        let val_strs: Vec<String> = vals.iter().map(|val| format!("{}", val.bits())).collect();
+        let idx = load_var_to_sym_tab(self.ident.clone(), self.span, params);
+        writeln!(buf, "  @{}_{} = alloc {}", self.ident, idx, array_type_str(&dims)).unwrap();
+        store_array_init(&format!("@{}_{}", self.ident, idx), &dims, &val_strs, buf, params);
+        params.declare(self.span, SymVal::ConstArray(idx, dims, vals, btype));
+    }
+
+    pub fn generate_global_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) {
+        let btype = params.decl_type.clone();
+        if self.dims.is_empty() {
+            let init_val = self.const_init_val.calc_const(params).convert(&btype);
+            params.declare(self.span, SymVal::ConstVal(init_val));
+            return;
+        }
+        let dims = calc_dims(&self.dims, params);
+        let vals = self.const_init_val.calc_array(&dims, &btype, params);
+        let bits: Vec<i32> = vals.iter().map(|val| val.bits()).collect();
+        let idx = load_var_to_sym_tab(self.ident.clone(), self.span, params);
+        writeln!(
+            buf,
+            "global @{}_{} = alloc {}, {}",
+            self.ident,
+            idx,
+            array_type_str(&dims),
+            global_init_str(&bits, &dims)
+        )
+        .unwrap();
+        params.declare(self.span, SymVal::ConstArray(idx, dims, vals, btype));
     }
 }
 
 impl ConstInitVal {
-    pub fn calc_const(&self, params: &mut GenerateIRParams) -> i32 {
-        return self.const_exp.calc_const(params);
+    pub fn calc_const(&self, params: &mut GenerateIRParams) -> ConstValue {
+        match self.flatten(&[], params)[0] {
+            Some(const_exp) => return const_exp.calc_const(params),
+            None => return ConstValue::Int(0),
+        }
+    }
+
+    // 展开后的每个元素都算出来, 转成数组元素的类型
+    pub fn calc_array(&self, dims: &[i32], btype: &BType, params: &mut GenerateIRParams) -> Vec<ConstValue> {
+        return self
+            .flatten(dims, params)
+            .iter()
+            .map(|const_exp| match const_exp {
+                Some(const_exp) => const_exp.calc_const(params).convert(btype),
+                None => ConstValue::Int(0).convert(btype),
+            })
+            .collect();
+    }
+
+    // 按照SysY的规则把初始化列表展开成一维, 没写的地方是None, 也就是0
+    pub fn flatten(&self, dims: &[i32], params: &mut GenerateIRParams) -> Vec<Option<&ConstExp>> {
+        let total: i32 = dims.iter().product();
+        let mut flat: Vec<Option<&ConstExp>> = Vec::new();
+        match self {
+            ConstInitVal::ConstExp(const_exp) => {
+                flat.push(Some(const_exp));
+            }
+            ConstInitVal::InitList(const_init_vals) => {
+                for const_init_val in const_init_vals {
+                    match const_init_val {
+                        ConstInitVal::ConstExp(const_exp) => {
+                            flat.push(Some(const_exp));
+                        }
+                        ConstInitVal::InitList(_) => {
+                            let sub_dims = sub_list_dims(flat.len(), dims);
+                            if sub_dims.is_empty() {
+                                params.warning_once("W0010", "braces around scalar initializer");
+                            }
+                            flat.extend(const_init_val.flatten(sub_dims, params));
+                        }
+                    }
+                }
+            }
+        }
+        if flat.len() > total as usize {
+            let kind = if dims.is_empty() { "scalar" } else { "array" };
+            params.warning("W0004", &format!("excess elements in {} initializer", kind));
+        }
+        flat.resize(total as usize, None);
+        return flat;
     }
 }
 
 impl ConstExp {
-    pub fn calc_const(&self, params: &mut GenerateIRParams) -> i32 {
+    pub fn calc_const(&self, params: &mut GenerateIRParams) -> ConstValue {
         return self.exp.calc_const(params);
     }
 }
@@ -263,105 +1276,451 @@ impl ConstExp {
 impl VarDecl {
     pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) {
         for var_def in &self.var_defs {
-            var_def.generate_koopa_ir(buf, params);
+            params.decl_type = self.btype.clone();
+            params.with_span(var_def.span(), |params| var_def.generate_koopa_ir(buf, params));
         }
     }
 }
 
 impl VarDef {
+    pub fn span(&self) -> Span {
+        match self {
+            VarDef::VarDefUninit(_, _, _, span) | VarDef::VarDefInit(_, _, _, _, span) => return *span,
+        }
+    }
+
     pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) {
         // 首先使用alloc命令, 接着根据是否有初值来计算.
+        // 前面的*和基本类型合起来才是变量(或者数组元素)的类型
+        let (depth, var_name, dims) = match self {
+            VarDef::VarDefUninit(depth, var_name, dims, _) | VarDef::VarDefInit(depth, var_name, dims, _, _) => {
+                (*depth, var_name, dims)
+            }
+        };
+        let btype = pointer_type(params.decl_type.clone(), depth);
+        params.decl_type = btype.clone();
+        check_complete_type(var_name, &btype, params);
         match self {
-            VarDef::VarDefUninit(var_name) => {
+            VarDef::VarDefUninit(..) if dims.is_empty() => {
                 // 存入符号表中
-                let idx = load_var_to_sym_tab(var_name.clone(), params);
-                writeln!(buf, "  @{}_{} = alloc i32", var_name, idx).unwrap();
+                let idx = load_var_to_sym_tab(var_name.clone(), self.span(), params);
+                writeln!(buf, "  @{}_{} = alloc {}", var_name, idx, koopa_type_str(&btype_str(&btype), params)).unwrap();
+            }
+            VarDef::VarDefUninit(..) => {
+                let dims = calc_dims(dims, params);
+                let idx = load_var_to_sym_tab(var_name.clone(), self.span(), params);
+                writeln!(buf, "  @{}_{} = alloc {}", var_name, idx, koopa_type_str(&typed_array_str(&dims, &btype), params)).unwrap();
+                params.declare(self.span(), SymVal::ArrayName(idx, dims, btype));
+            }
+            VarDef::VarDefInit(_, _, _, init_val, _) if dims.is_empty() && matches!(btype, BType::Struct(_)) => {
+                let idx = load_var_to_sym_tab(var_name.clone(), self.span(), params);
+                writeln!(buf, "  @{}_{} = alloc {}", var_name, idx, koopa_type_str(&btype_str(&btype), params)).unwrap();
+                let addr = format!("@{}_{}", var_name, idx);
+                let InitVal::InitList(_) = init_val else {
+                    // struct S b = a; 和赋值一样整个复制
+                    let InitVal::Exp(exp) = init_val else { unreachable!() };
+                    generate_struct_copy(&addr, false, &btype, exp, buf, params);
+                    return;
+                };
+                let Some(info) = struct_info(&btype, params) else {
+                    return;
+                };
+                let mut vals: Vec<String> = Vec::new();
+                for (exp, word_type) in init_val.flatten_struct(&info, params).into_iter().zip(info.word_types.clone()) {
+                    match exp {
+                        Some(exp) => {
+                            let exp_res = exp.generate_koopa_ir(buf, params);
+                            let exp_res = convert_exp_result(exp_res, &word_type, buf, params);
+                            vals.push(exp_result_str(&exp_res));
+                        }
+                        None => vals.push("0".to_string()),
+                    }
+                }
+                store_array_init(&addr, &[info.words()], &vals, buf, params);
+            }
+            VarDef::VarDefInit(_, _, _, init_val, _) if dims.is_empty() => {
+                // 存入符号表中
+                let idx = load_var_to_sym_tab(var_name.clone(), self.span(), params);
+                writeln!(buf, "  @{}_{} = alloc {}", var_name, idx, koopa_type_str(&btype_str(&btype), params)).unwrap();
+                let val_result = init_val.generate_koopa_ir(buf, params);
+                let val_result = convert_exp_result(val_result, &btype, buf, params);
+                writeln!(buf, "  store {}, @{}_{}", exp_result_str(&val_result), var_name, idx).unwrap();
+            }
+            VarDef::VarDefInit(_, _, _, init_val, _) => {
+                let dims = calc_dims(dims, params);
+                let idx = load_var_to_sym_tab(var_name.clone(), self.span(), params);
+                writeln!(buf, "  @{}_{} = alloc {}", var_name, idx, koopa_type_str(&typed_array_str(&dims, &btype), params)).unwrap();
+                params.declare(self.span(), SymVal::ArrayName(idx, dims.clone(), btype.clone()));
+                if matches!(btype, BType::Pointer(_) | BType::Struct(_)) {
+                    // 指针数组没写的元素要一个一个补成空指针, 没有做; 结构体的数组也只能一个一个赋值
+                    params.error("E0502", "arrays of pointers or structs cannot have an initializer");
+                    return;
+                }
+                let mut vals: Vec<String> = Vec::new();
+                for exp in init_val.flatten(&dims, params) {
+                    match exp {
+                        Some(exp) => {
+                            let exp_res = exp.generate_koopa_ir(buf, params);
+                            let exp_res = convert_exp_result(exp_res, &btype, buf, params);
+                            vals.push(exp_result_str(&exp_res));
+                        }
+                        None => vals.push("0".to_string()),
+                    }
+                }
+                store_array_init(&format!("@{}_{}", var_name, idx), &dims, &vals, buf, params);
+            }
+        }
+    }
+}
+
+impl VarDef {
+    pub fn generate_global_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) {
+        let (depth, var_name, dims) = match self {
+            VarDef::VarDefUninit(depth, var_name, dims, _) | VarDef::VarDefInit(depth, var_name, dims, _, _) => {
+                (*depth, var_name, dims)
+            }
+        };
+        let btype = pointer_type(params.decl_type.clone(), depth);
+        params.decl_type = btype.clone();
+        check_complete_type(var_name, &btype, params);
+        match self {
+            VarDef::VarDefUninit(..) if dims.is_empty() => {
+                let idx = load_var_to_sym_tab(var_name.clone(), self.span(), params);
+                writeln!(buf, "global @{}_{} = alloc {}, zeroinit", var_name, idx, koopa_type_str(&btype_str(&btype), params)).unwrap();
+            }
+            VarDef::VarDefUninit(..) => {
+                let dims = calc_dims(dims, params);
+                let idx = load_var_to_sym_tab(var_name.clone(), self.span(), params);
+                writeln!(
+                    buf,
+                    "global @{}_{} = alloc {}, zeroinit",
+                    var_name,
+                    idx,
+                    koopa_type_str(&typed_array_str(&dims, &btype), params)
+                )
+                .unwrap();
+                params.declare(self.span(), SymVal::ArrayName(idx, dims, btype));
+            }
+            VarDef::VarDefInit(_, _, _, init_val, _) if matches!(btype, BType::Struct(_)) => {
+                let words = struct_words(&btype, params);
+                let mut vals = vec![0; words as usize];
+                match (init_val, struct_info(&btype, params)) {
+                    (InitVal::InitList(_), Some(info)) if dims.is_empty() => {
+                        vals = init_val
+                            .flatten_struct(&info, params)
+                            .into_iter()
+                            .zip(info.word_types.clone())
+                            .map(|(exp, word_type)| match exp {
+                                Some(exp) => exp.calc_const(params).convert(&word_type).bits(),
+                                None => 0,
+                            })
+                            .collect();
+                    }
+                    _ => {
+                        params.error(
+                            "E0502",
+                            &format!("global struct {} can only be initialized with a constant list", var_name),
+                        );
+                    }
+                }
+                let idx = load_var_to_sym_tab(var_name.clone(), self.span(), params);
+                if dims.is_empty() {
+                    writeln!(
+                        buf,
+                        "global @{}_{} = alloc {}, {}",
+                        var_name,
+                        idx,
+                        koopa_type_str(&btype_str(&btype), params),
+                        global_init_str(&vals, &[words])
+                    )
+                    .unwrap();
+                } else {
+                    let dims = calc_dims(dims, params);
+                    writeln!(
+                        buf,
+                        "global @{}_{} = alloc {}, zeroinit",
+                        var_name,
+                        idx,
+                        koopa_type_str(&typed_array_str(&dims, &btype), params)
+                    )
+                    .unwrap();
+                    params.declare(self.span(), SymVal::ArrayName(idx, dims, btype));
+                }
+            }
+            VarDef::VarDefInit(_, _, _, init_val, _) => {
+                // 先算初值, 初值里不能用到自己
+                let dims = calc_dims(dims, params);
+                let vals: Vec<i32> = init_val
+                    .flatten(&dims, params)
+                    .iter()
+                    .map(|exp| match exp {
+                        Some(exp) => exp.calc_const(params).convert(&btype).bits(),
+                        None => 0,
+                    })
+                    .collect();
+                // 全局的指针只能初始化成0, 也就是空指针
+                if matches!(btype, BType::Pointer(_)) && vals.iter().any(|val| *val != 0) {
+                    params.error("E0502", "global pointers can only be initialized to 0");
+                }
+                let idx = load_var_to_sym_tab(var_name.clone(), self.span(), params);
+                if let BType::Pointer(_) = btype {
+                    writeln!(
+                        buf,
+                        "global @{}_{} = alloc {}, zeroinit",
+                        var_name,
+                        idx,
+                        koopa_type_str(&typed_array_str(&dims, &btype), params)
+                    )
+                    .unwrap();
+                } else if dims.is_empty() {
+                    writeln!(buf, "global @{}_{} = alloc {}, {}", var_name, idx, koopa_type_str(&btype_str(&btype), params), vals[0]).unwrap();
+                } else {
+                    writeln!(
+                        buf,
+                        "global @{}_{} = alloc {}, {}",
+                        var_name,
+                        idx,
+                        array_type_str(&dims),
+                        global_init_str(&vals, &dims)
+                    )
+                    .unwrap();
+                }
+                if !dims.is_empty() {
+                    params.declare(self.span(), SymVal::ArrayName(idx, dims, btype));
+                }
+            }
+        }
+    }
+}
+
+impl InitVal {
+    pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
+        match self.flatten(&[], params)[0] {
+            Some(exp) => return exp.generate_koopa_ir(buf, params),
+            None => return ExpResult::IntResult(0),
+        }
+    }
+
+    // 结构体的初始化列表按成员的顺序展开成一个一个字;
+    // 数组成员和结构体成员可以用一个子列表, 也可以省掉花括号直接接着写
+    pub fn flatten_struct(&self, info: &StructInfo, params: &mut GenerateIRParams) -> Vec<Option<&Exp>> {
+        let mut flat: Vec<Option<&Exp>> = vec![None; info.words() as usize];
+        let InitVal::InitList(init_vals) = self else {
+            return flat;
+        };
+        let mut init_vals = init_vals.iter().peekable();
+        flatten_fields(info, &mut init_vals, &mut flat, 0, params);
+        if init_vals.next().is_some() {
+            params.warning("W0004", "excess elements in struct initializer");
+        }
+        return flat;
+    }
+
+    // 和ConstInitVal的展开规则一样
+    pub fn flatten(&self, dims: &[i32], params: &mut GenerateIRParams) -> Vec<Option<&Exp>> {
+        let total: i32 = dims.iter().product();
+        let mut flat: Vec<Option<&Exp>> = Vec::new();
+        match self {
+            InitVal::Exp(exp) => {
+                flat.push(Some(exp));
+            }
+            InitVal::InitList(init_vals) => {
+                for init_val in init_vals {
+                    match init_val {
+                        InitVal::Exp(exp) => {
+                            flat.push(Some(exp));
+                        }
+                        InitVal::InitList(_) => {
+                            let sub_dims = sub_list_dims(flat.len(), dims);
+                            if sub_dims.is_empty() {
+                                params.warning_once("W0010", "braces around scalar initializer");
+                            }
+                            flat.extend(init_val.flatten(sub_dims, params));
+                        }
+                    }
+                }
+            }
+        }
+        if flat.len() > total as usize {
+            let kind = if dims.is_empty() { "scalar" } else { "array" };
+            params.warning("W0004", &format!("excess elements in {} initializer", kind));
+        }
+        flat.resize(total as usize, None);
+        return flat;
+    }
+}
+
+// 从init_vals里按顺序取出一个结构体的各个成员, 放到flat里从base开始的地方;
+// 省掉花括号的结构体成员也用它接着往下取
+fn flatten_fields<'a>(
+    info: &StructInfo,
+    init_vals: &mut std::iter::Peekable<std::slice::Iter<'a, InitVal>>,
+    flat: &mut [Option<&'a Exp>],
+    base: usize,
+    params: &mut GenerateIRParams,
+) {
+    for field in &info.fields {
+        let base = base + (field.offset / 4) as usize;
+        if init_vals.peek().is_none() {
+            return;
+        }
+        let count: i32 = field.dims.iter().product();
+        if let Some(inner) = struct_info(&field.btype, params) {
+            // 结构体或者结构体的数组, 每个元素要么是一个子列表, 要么省掉花括号
+            let words = inner.words() as usize;
+            if field.dims.is_empty() {
+                flatten_struct_elem(&inner, init_vals, flat, base, params);
+                continue;
             }
-            VarDef::VarDefInit(var_name, init_val) => {
-                // 存入符号表中
-                let idx = load_var_to_sym_tab(var_name.clone(), params);
-                writeln!(buf, "  @{}_{} = alloc i32", var_name, idx).unwrap();
-                let val_result = init_val.generate_koopa_ir(buf, params);
-                match val_result {
-                    ExpResult::RegCount(reg) => {
-                        writeln!(buf, "  store %{}, @{}_{}", reg - 1, var_name, idx).unwrap();
-                    }
-                    ExpResult::IntResult(int_val) => {
-                        writeln!(buf, "  store {}, @{}_{}", int_val, var_name, idx).unwrap();
-                    }
+            let elems = match init_vals.peek() {
+                Some(InitVal::InitList(elems)) => elems,
+                _ => {
+                    for i in 0..count as usize {
+                        if init_vals.peek().is_none() {
+                            break;
+                        }
+                        flatten_struct_elem(&inner, init_vals, flat, base + i * words, params);
+                    }
+                    continue;
+                }
+            };
+            init_vals.next();
+            let mut elems = elems.iter().peekable();
+            for i in 0..count as usize {
+                if elems.peek().is_none() {
+                    break;
+                }
+                flatten_struct_elem(&inner, &mut elems, flat, base + i * words, params);
+            }
+            if elems.next().is_some() {
+                params.warning("W0004", "excess elements in array initializer");
+            }
+            continue;
+        }
+        if field.dims.is_empty() {
+            flat[base] = init_vals.next().unwrap().flatten(&[], params)[0];
+            continue;
+        }
+        if let Some(InitVal::InitList(_)) = init_vals.peek() {
+            let sub_list = init_vals.next().unwrap().flatten(&field.dims, params);
+            for (i, exp) in sub_list.into_iter().enumerate() {
+                flat[base + i] = exp;
+            }
+            continue;
+        }
+        for i in 0..count as usize {
+            match init_vals.peek().copied() {
+                Some(InitVal::Exp(exp)) => {
+                    flat[base + i] = Some(exp);
+                    init_vals.next();
                 }
+                _ => break,
             }
         }
     }
 }
 
-impl InitVal {
-    pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
-        return self.exp.generate_koopa_ir(buf, params);
+// 一个结构体类型的元素: 有花括号的话是它自己的列表, 否则接着从外面的列表里取
+fn flatten_struct_elem<'a>(
+    info: &StructInfo,
+    init_vals: &mut std::iter::Peekable<std::slice::Iter<'a, InitVal>>,
+    flat: &mut [Option<&'a Exp>],
+    base: usize,
+    params: &mut GenerateIRParams,
+) {
+    if let Some(InitVal::InitList(_)) = init_vals.peek() {
+        let sub_list = init_vals.next().unwrap().flatten_struct(info, params);
+        for (i, exp) in sub_list.into_iter().enumerate() {
+            flat[base + i] = exp;
+        }
+        return;
     }
+    flatten_fields(info, init_vals, flat, base, params);
 }
 
 impl BasicStmt {
     pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) {
         match self {
-            BasicStmt::Return(exp) => {
-                let exp_res = exp.generate_koopa_ir(buf, params);
-                match exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        writeln!(buf, "  ret %{}", reg_count - 1).unwrap();
+            BasicStmt::Return(exp, _) => {
+                match (exp, &params.func_type) {
+                    (Some(exp), FuncType::Int | FuncType::Float) => {
+                        let ret_type = match params.func_type {
+                            FuncType::Float => BType::Float,
+                            _ => BType::Int,
+                        };
+                        let exp_res = exp.generate_koopa_ir(buf, params);
+                        let exp_res = convert_exp_result(exp_res, &ret_type, buf, params);
+                        writeln!(buf, "  ret {}", exp_result_str(&exp_res)).unwrap();
+                    }
+                    (None, FuncType::Void) => {
+                        writeln!(buf, "  ret").unwrap();
                     }
-                    ExpResult::IntResult(int_res) => {
-                        writeln!(buf, "  ret {}", int_res).unwrap();
+                    (Some(_), FuncType::Void) => {
+                        params.error("E0403", "void function should not return a value");
+                        writeln!(buf, "  ret").unwrap();
+                    }
+                    (None, FuncType::Int | FuncType::Float) => {
+                        params.error("E0403", "non-void function should return a value");
+                        writeln!(buf, "  ret 0").unwrap();
                     }
                 }
                 params.func_returned = true;
             }
-            BasicStmt::Assgn(l_val, exp) => {
-                let idx_val = params.sym_tab.query(l_val.ident.clone()).unwrap();
-                let mut idx: i32 = 0;
-                match idx_val {
-                    SymVal::ConstVal(_) => {}
-                    SymVal::VarName(index) => {
-                        idx = index;
-                    }
+            BasicStmt::Assgn(lhs, exp, _) => {
+                // 是不是左值语义分析里检查过了, 拿不到地址只会是*的类型不对, 那里已经报过错
+                let Some((l_val_addr, btype)) = lhs.generate_addr(buf, params) else {
+                    return;
+                };
+                if let BType::Struct(_) = btype {
+                    generate_struct_copy(&l_val_addr, lhs.is_struct_member(params), &btype, exp, buf, params);
+                    return;
                 }
                 let exp_res = exp.generate_koopa_ir(buf, params);
-                match exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        writeln!(buf, "  store %{}, @{}_{}", reg_count - 1, l_val.ident, idx)
-                            .unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        writeln!(buf, "  store {}, @{}_{}", int_res, l_val.ident, idx).unwrap();
+                let exp_res = convert_exp_result(exp_res, &btype, buf, params);
+                writeln!(buf, "  store {}, {}", exp_result_str(&exp_res), l_val_addr).unwrap();
+            }
+            BasicStmt::CompoundAssgn(lhs, compound_op, exp, _) => {
+                // 先算地址再读出旧值, 然后才算右边
+                let Some((l_val_addr, btype)) = lhs.generate_addr(buf, params) else {
+                    return;
+                };
+                let old_val = generate_load(&l_val_addr, &btype, buf, params);
+                let exp_res = exp.generate_koopa_ir(buf, params);
+                let op = match compound_op {
+                    CompoundOp::Add => "add",
+                    CompoundOp::Sub => "sub",
+                    CompoundOp::Mul => "mul",
+                    CompoundOp::Div => "div",
+                    CompoundOp::Mod => "mod",
+                };
+                let new_val = generate_arith(op, old_val, exp_res, buf, params);
+                let new_val = convert_exp_result(new_val, &btype, buf, params);
+                writeln!(buf, "  store {}, {}", exp_result_str(&new_val), l_val_addr).unwrap();
+            }
+            BasicStmt::Exp(exp) => {
+                if let Some(some_exp) = exp {
+                    if let Some(UnaryExp::FuncCall(ident, func_r_params, span)) = some_exp.as_unary_exp() {
+                        // 单独的函数调用语句, 返回值不用, 可以是void
+                        params.with_span(*span, |params| generate_func_call(ident, func_r_params, *span, false, buf, params));
+                    } else {
+                        let _exp_res = some_exp.generate_koopa_ir(buf, params);
                     }
                 }
             }
-            BasicStmt::Exp(exp) => match exp {
-                Some(some_exp) => {
-                    let _exp_res = some_exp.generate_koopa_ir(buf, params);
-                }
-                None => {}
-            },
             BasicStmt::Block(block) => {
                 block.generate_koopa_ir(buf, params);
             }
-            BasicStmt::Break => {
-                if params.while_level <= 0 {
-                    stderr()
-                        .write_all(b"Error: break statement not in loop\n")
-                        .unwrap();
-                    return;
-                }
-                writeln!(buf, "  jump %while_end{}", params.while_idx).unwrap();
+            BasicStmt::Break(_) => {
+                // 在不在循环或者switch里, 语义分析里检查过了
+                let break_label = params.break_stack.last().unwrap();
+                writeln!(buf, "  jump {}", break_label).unwrap();
+                // 跳走之后这个基本块就结束了
+                params.func_returned = true;
             }
-            BasicStmt::Continue => {
-                if params.while_level <= 0 {
-                    stderr()
-                        .write_all(b"Error: continue statement not in loop\n")
-                        .unwrap();
-                    return;
-                }
-                writeln!(buf, "  jump %while_entry{}", params.while_idx).unwrap();
+            BasicStmt::Continue(_) => {
+                let (_, continue_label) = params.loop_stack.last().unwrap();
+                writeln!(buf, "  jump {}", continue_label).unwrap();
+                params.func_returned = true;
             }
         }
     }
@@ -385,75 +1744,182 @@ impl ClosedStmt {
                 params.jump_true_branch = -1; // 是then
                 let res = exp.short_circuit_eval(buf, params);
                 // 插入条件跳转语句
-                match res {
-                    ExpResult::IntResult(int_num) => {
-                        writeln!(
-                            buf,
-                            "  br {}, %then{}, %else{}",
-                            int_num, then_idx, else_idx
-                        )
-                        .unwrap();
-                    }
-                    ExpResult::RegCount(reg) => {
-                        writeln!(
-                            buf,
-                            "  br %{}, %then{}, %else{}",
-                            reg - 1,
-                            then_idx,
-                            else_idx
-                        )
-                        .unwrap();
-                    }
-                }
-                let func_retuened = params.func_returned;
+                writeln!(buf, "  br {}, %then{}, %else{}", exp_result_str(&res), then_idx, else_idx).unwrap();
                 params.func_returned = false;
                 writeln!(buf, "%then{}:", then_idx).unwrap();
                 closed_stmt1.generate_koopa_ir(buf, params);
-                if params.func_returned == false {
+                if !params.func_returned {
                     writeln!(buf, "  jump %end{}", end_idx).unwrap();
                 }
                 params.func_returned = false;
                 writeln!(buf, "%else{}:", else_idx).unwrap();
                 closed_stmt2.generate_koopa_ir(buf, params);
-                if params.func_returned == false {
+                if !params.func_returned {
                     writeln!(buf, "  jump %end{}", end_idx).unwrap();
                 }
-                writeln!(buf, "%end{}:", end_idx).unwrap();
-                params.func_returned = func_retuened;
+                // 两边都走不下去的话没有跳到end的地方, 这个基本块就不要了
+                params.func_returned = !self.can_complete();
+                if !params.func_returned {
+                    writeln!(buf, "%end{}:", end_idx).unwrap();
+                }
                 params.if_level -= 1;
             }
             ClosedStmt::BasicStmt(non_if_stmt) => {
                 non_if_stmt.generate_koopa_ir(buf, params);
             }
+            ClosedStmt::ForStmt(for_head, body) => {
+                let while_idx = for_head.generate_head(buf, params);
+                params.func_returned = false;
+                body.generate_koopa_ir(buf, params);
+                for_head.generate_tail(while_idx, body.has_jump(Jump::Continue), self.can_complete(), buf, params);
+            }
+            ClosedStmt::SwitchStmt(exp, switch_cases) => {
+                params.switch_idx += 1;
+                let switch_idx = params.switch_idx;
+                let exp_res = exp.generate_koopa_ir(buf, params);
+                if exp_res.btype() == BType::Float {
+                    params.error("E0204", "switch quantity not an integer");
+                }
+                let exp_res = convert_exp_result(exp_res, &BType::Int, buf, params);
+                // 每个case的值, 以及它是第几个分支
+                let mut case_vals: Vec<(i32, usize)> = Vec::new();
+                let mut default_label = format!("%switch_end{}", switch_idx);
+                // 重复的标号指向后面那个, 再带上前面那个的位置
+                let mut first_default: Option<Span> = None;
+                for (i, switch_case) in switch_cases.iter().enumerate() {
+                    match &switch_case.label {
+                        CaseLabel::Case(const_exp) => {
+                            let val = const_exp.calc_const(params);
+                            if val.btype() == BType::Float {
+                                params.error_at(switch_case.span, "E0404", "case label does not reduce to an integer constant");
+                                continue;
+                            }
+                            let val = val.to_int();
+                            if let Some((_, first)) = case_vals.iter().find(|(case_val, _)| *case_val == val) {
+                                params.report(
+                                    Diagnostic::error("E0404", &format!("duplicate case value {}", val))
+                                        .with_primary(switch_case.span, "")
+                                        .with_secondary(switch_cases[*first].span, "previously used here"),
+                                );
+                            }
+                            case_vals.push((val, i));
+                        }
+                        CaseLabel::Default => {
+                            match first_default {
+                                Some(first) => params.report(
+                                    Diagnostic::error("E0404", "multiple default labels in one switch")
+                                        .with_primary(switch_case.span, "")
+                                        .with_secondary(first, "first default label here"),
+                                ),
+                                None => first_default = Some(switch_case.span),
+                            }
+                            default_label = format!("%case{}_{}", switch_idx, i);
+                        }
+                    }
+                }
+                // 先生成一串比较: %switchN, %switchN_1, ..., 最后一块跳到default
+                // 这些块记在switch_chains里, 后端会换成跳转表或者二分比较
+                writeln!(buf, "  jump %switch{}", switch_idx).unwrap();
+                writeln!(buf, "%switch{}:", switch_idx).unwrap();
+                let mut chain = vec![format!("%switch{}", switch_idx)];
+                for (k, (val, i)) in case_vals.iter().enumerate() {
+                    writeln!(buf, "  %{} = eq {}, {}", params.var_count, exp_result_str(&exp_res), val).unwrap();
+                    writeln!(
+                        buf,
+                        "  br %{}, %case{}_{}, %switch{}_{}",
+                        params.var_count,
+                        switch_idx,
+                        i,
+                        switch_idx,
+                        k + 1
+                    )
+                    .unwrap();
+                    params.var_count += 1;
+                    writeln!(buf, "%switch{}_{}:", switch_idx, k + 1).unwrap();
+                    chain.push(format!("%switch{}_{}", switch_idx, k + 1));
+                }
+                writeln!(buf, "  jump {}", default_label).unwrap();
+                params
+                    .switch_chains
+                    .entry(params.func_ident.clone())
+                    .or_default()
+                    .push(chain);
+                // switch里面是同一个作用域, case之间可以直接落下去
+                params.break_stack.push(format!("%switch_end{}", switch_idx));
+                params.func_returned = true;
+                for (i, switch_case) in switch_cases.iter().enumerate() {
+                    if !params.func_returned {
+                        writeln!(buf, "  jump %case{}_{}", switch_idx, i).unwrap();
+                    }
+                    params.func_returned = false;
+                    writeln!(buf, "%case{}_{}:", switch_idx, i).unwrap();
+                    for block_item in &switch_case.block_items {
+                        block_item.generate_koopa_ir(buf, params);
+                        if params.func_returned {
+                            break;
+                        }
+                    }
+                }
+                if !params.func_returned {
+                    writeln!(buf, "  jump %switch_end{}", switch_idx).unwrap();
+                }
+                // 有default并且每个分支都return了的话, 没有东西会跳到switch_end
+                params.func_returned = !self.can_complete();
+                if !params.func_returned {
+                    writeln!(buf, "%switch_end{}:", switch_idx).unwrap();
+                }
+                params.break_stack.pop();
+            }
+            ClosedStmt::DoWhileStmt(body, cond) => {
+                // 先执行一遍循环体, continue跳到条件判断
+                params.while_idx += 1;
+                let while_idx = params.while_idx;
+                params
+                    .loop_stack
+                    .push((while_idx, format!("%while_entry{}", while_idx)));
+                params.break_stack.push(format!("%while_end{}", while_idx));
+                writeln!(buf, "  jump %while_body{}", while_idx).unwrap();
+                writeln!(buf, "%while_body{}:", while_idx).unwrap();
+                params.func_returned = false;
+                body.generate_koopa_ir(buf, params);
+                if !params.func_returned {
+                    writeln!(buf, "  jump %while_entry{}", while_idx).unwrap();
+                }
+                // 循环体既走不到头又没有continue的话, 条件判断就走不到了
+                if !params.func_returned || body.has_jump(Jump::Continue) {
+                    writeln!(buf, "%while_entry{}:", while_idx).unwrap();
+                    generate_loop_cond(Some(cond), while_idx, buf, params);
+                }
+                params.func_returned = !self.can_complete();
+                if !params.func_returned {
+                    writeln!(buf, "%while_end{}:", while_idx).unwrap();
+                }
+                params.loop_stack.pop();
+                params.break_stack.pop();
+            }
             ClosedStmt::WhileStmt(cond, body) => {
                 params.while_idx += 1;
-                params.while_level += 1;
                 let while_idx = params.while_idx;
+                params
+                    .loop_stack
+                    .push((while_idx, format!("%while_entry{}", while_idx)));
+                params.break_stack.push(format!("%while_end{}", while_idx));
                 writeln!(buf, "  jump %while_entry{}", while_idx).unwrap();
                 writeln!(buf, "%while_entry{}:", while_idx).unwrap();
-                params.jump_true_branch = -4; // 是while_body
-                params.jump_false_branch = -5; // 是while_end
-                let res = cond.short_circuit_eval(buf, params);
-                match res {
-                    ExpResult::IntResult(int_num) => {
-                        writeln!(buf, "  br {}, %while_body{}, %while_end{}", int_num, while_idx, while_idx)
-                            .unwrap();
-                    }
-                    ExpResult::RegCount(reg) => {
-                        writeln!(buf, "  br %{}, %while_body{}, %while_end{}", reg - 1, while_idx, while_idx)
-                            .unwrap();
-                    }
-                }
+                generate_loop_cond(Some(cond), while_idx, buf, params);
                 writeln!(buf, "%while_body{}:", while_idx).unwrap();
-                let func_returned = params.func_returned;
                 params.func_returned = false;
                 body.generate_koopa_ir(buf, params);
                 if !params.func_returned {
                     writeln!(buf, "  jump %while_entry{}", while_idx).unwrap();
                 }
-                params.func_returned = func_returned;
-                writeln!(buf, "%while_end{}:", while_idx).unwrap();
-                params.while_level -= 1;
+                // while(1)又没有break的话出不来
+                params.func_returned = !self.can_complete();
+                if !params.func_returned {
+                    writeln!(buf, "%while_end{}:", while_idx).unwrap();
+                }
+                params.loop_stack.pop();
+                params.break_stack.pop();
             }
         }
     }
@@ -476,21 +1942,12 @@ impl OpenStmt {
                 params.jump_true_branch = -1; // 是then 
                 let res = exp.short_circuit_eval(buf, params);
                 // 插入条件跳转语句
-                match res {
-                    ExpResult::IntResult(int_num) => {
-                        writeln!(buf, "  br {}, %then{}, %end{}", int_num, then_idx, end_idx)
-                            .unwrap();
-                    }
-                    ExpResult::RegCount(reg) => {
-                        writeln!(buf, "  br %{}, %then{}, %end{}", reg - 1, then_idx, end_idx)
-                            .unwrap();
-                    }
-                }
+                writeln!(buf, "  br {}, %then{}, %end{}", exp_result_str(&res), then_idx, end_idx).unwrap();
                 let func_returned = params.func_returned;
                 params.func_returned = false;
                 writeln!(buf, "%then{}:", then_idx).unwrap();
                 stmt.generate_koopa_ir(buf, params);
-                if params.func_returned == false {
+                if !params.func_returned {
                     writeln!(buf, "  jump %end{}", end_idx).unwrap();
                 }
                 params.func_returned = func_returned;
@@ -511,85 +1968,130 @@ impl OpenStmt {
                 let else_idx = params.else_idx;
                 let end_idx = params.end_idx;
                 // 插入条件跳转语句
-                match res {
-                    ExpResult::IntResult(int_num) => {
-                        writeln!(
-                            buf,
-                            "  br {}, %then{}, %else{}",
-                            int_num, then_idx, else_idx
-                        )
-                        .unwrap();
-                    }
-                    ExpResult::RegCount(reg) => {
-                        writeln!(
-                            buf,
-                            "  br %{}, %then{}, %else{}",
-                            reg - 1,
-                            then_idx,
-                            else_idx
-                        )
-                        .unwrap();
-                    }
-                }
-                let func_returned = params.func_returned;
+                writeln!(buf, "  br {}, %then{}, %else{}", exp_result_str(&res), then_idx, else_idx).unwrap();
                 params.func_returned = false;
                 writeln!(buf, "%then{}:", then_idx).unwrap();
                 closed_stmt.generate_koopa_ir(buf, params);
-                if params.func_returned == false {
+                if !params.func_returned {
                     writeln!(buf, "  jump %end{}", end_idx).unwrap();
                 }
-                params.func_returned = func_returned;
+                params.func_returned = false;
                 writeln!(buf, "%else{}:", else_idx).unwrap();
                 open_stmt.generate_koopa_ir(buf, params);
-                if params.func_returned == false {
+                if !params.func_returned {
                     writeln!(buf, "  jump %end{}", end_idx).unwrap();
                 }
-                params.func_returned = func_returned;
-                writeln!(buf, "%end{}:", end_idx).unwrap();
+                params.func_returned = !self.can_complete();
+                if !params.func_returned {
+                    writeln!(buf, "%end{}:", end_idx).unwrap();
+                }
                 params.if_level -= 1;
             }
             OpenStmt::WhileStmt(cond, body) => {
                 params.while_idx += 1;
-                params.while_level += 1;
                 let while_idx = params.while_idx;
+                params
+                    .loop_stack
+                    .push((while_idx, format!("%while_entry{}", while_idx)));
+                params.break_stack.push(format!("%while_end{}", while_idx));
                 writeln!(buf, "  jump %while_entry{}", while_idx).unwrap();
                 writeln!(buf, "%while_entry{}:", while_idx).unwrap();
-                params.jump_true_branch = -4; // 是while_body
-                params.jump_false_branch = -5; // 是while_end
-                let res = cond.short_circuit_eval(buf, params);
-                match res {
-                    ExpResult::IntResult(int_num) => {
-                        writeln!(buf, "  br {}, %while_body{}, %while_end{}", int_num, while_idx, while_idx)
-                            .unwrap();
-                    }
-                    ExpResult::RegCount(reg) => {
-                        writeln!(buf, "  br %{}, %while_body{}, %while_end{}", reg - 1, while_idx, while_idx)
-                            .unwrap();
-                    }
-                }
+                generate_loop_cond(Some(cond), while_idx, buf, params);
                 writeln!(buf, "%while_body{}:", while_idx).unwrap();
-                let func_returned = params.func_returned;
                 params.func_returned = false;
                 body.generate_koopa_ir(buf, params);
                 if !params.func_returned {
                     writeln!(buf, "  jump %while_entry{}", while_idx).unwrap();
                 }
-                writeln!(buf, "%while_end{}:", while_idx).unwrap();
-                params.func_returned = func_returned;
-                params.while_level -= 1;
+                params.func_returned = !self.can_complete();
+                if !params.func_returned {
+                    writeln!(buf, "%while_end{}:", while_idx).unwrap();
+                }
+                params.loop_stack.pop();
+                params.break_stack.pop();
+            }
+            OpenStmt::ForStmt(for_head, body) => {
+                let while_idx = for_head.generate_head(buf, params);
+                params.func_returned = false;
+                body.generate_koopa_ir(buf, params);
+                for_head.generate_tail(while_idx, body.has_jump(Jump::Continue), self.can_complete(), buf, params);
             }
         }
     }
 }
 
+// for循环和while共用一套标号, 多了一个while_step给continue用
+// 头部: init, 条件判断, 然后进入循环体
+// 尾部: step, 跳回条件判断, 最后是循环出口
+impl ForHead {
+    pub fn generate_head(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> i32 {
+        // init里声明的变量只在循环里可见
+        match &self.init {
+            ForInit::Decl(decl) => decl.generate_koopa_ir(buf, params),
+            ForInit::Stmt(stmt) => stmt.generate_koopa_ir(buf, params),
+        }
+        params.while_idx += 1;
+        let while_idx = params.while_idx;
+        params
+            .loop_stack
+            .push((while_idx, format!("%while_step{}", while_idx)));
+        params.break_stack.push(format!("%while_end{}", while_idx));
+        writeln!(buf, "  jump %while_entry{}", while_idx).unwrap();
+        writeln!(buf, "%while_entry{}:", while_idx).unwrap();
+        generate_loop_cond(self.cond.as_ref(), while_idx, buf, params);
+        writeln!(buf, "%while_body{}:", while_idx).unwrap();
+        return while_idx;
+    }
+
+    // 循环体走不到头又没有continue的话就没有step; 整个循环出不来的话就没有出口
+    pub fn generate_tail(
+        &self,
+        while_idx: i32,
+        has_continue: bool,
+        can_complete: bool,
+        buf: &mut Vec<u8>,
+        params: &mut GenerateIRParams,
+    ) {
+        if !params.func_returned {
+            writeln!(buf, "  jump %while_step{}", while_idx).unwrap();
+        }
+        if !params.func_returned || has_continue {
+            params.func_returned = false;
+            writeln!(buf, "%while_step{}:", while_idx).unwrap();
+            if let Some(step) = &self.step {
+                step.generate_koopa_ir(buf, params);
+            }
+            writeln!(buf, "  jump %while_entry{}", while_idx).unwrap();
+        }
+        params.func_returned = !can_complete;
+        if !params.func_returned {
+            writeln!(buf, "%while_end{}:", while_idx).unwrap();
+        }
+        params.loop_stack.pop();
+        params.break_stack.pop();
+    }
+}
+
+// 循环的条件: 没写或者是while(1)这样的字面量就直接进循环体, 不然短路求值以后跳到循环体或者出口
+fn generate_loop_cond(cond: Option<&Exp>, while_idx: i32, buf: &mut Vec<u8>, params: &mut GenerateIRParams) {
+    let Some(cond) = cond.filter(|cond| !cond.is_true_literal()) else {
+        writeln!(buf, "  jump %while_body{}", while_idx).unwrap();
+        return;
+    };
+    params.jump_true_branch = -4; // 是while_body
+    params.jump_false_branch = -5; // 是while_end
+    let res = cond.short_circuit_eval(buf, params);
+    writeln!(buf, "  br {}, %while_body{}, %while_end{}", exp_result_str(&res), while_idx, while_idx).unwrap();
+}
+
 impl Stmt {
     pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) {
         match self {
-            Stmt::ClosedStmt(closed_stmt) => {
-                closed_stmt.generate_koopa_ir(buf, params);
+            Stmt::ClosedStmt(closed_stmt, span) => {
+                params.with_span(*span, |params| closed_stmt.generate_koopa_ir(buf, params));
             }
-            Stmt::OpenStmt(open_stmt) => {
-                open_stmt.generate_koopa_ir(buf, params);
+            Stmt::OpenStmt(open_stmt, span) => {
+                params.with_span(*span, |params| open_stmt.generate_koopa_ir(buf, params));
             }
         }
     }
@@ -597,86 +2099,458 @@ impl Stmt {
 
 impl Exp {
     pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
-        return self.l_or_exp.generate_koopa_ir(buf, params);
+        return params.with_span(self.span, |params| self.cond_exp.generate_koopa_ir(buf, params));
     }
-    pub fn calc_const(&self, params: &mut GenerateIRParams) -> i32 {
-        return self.l_or_exp.calc_const(params);
+    pub fn calc_const(&self, params: &mut GenerateIRParams) -> ConstValue {
+        return params.with_span(self.span, |params| self.cond_exp.calc_const(params));
     }
     pub fn short_circuit_eval(
         &self,
         buf: &mut Vec<u8>,
         params: &mut GenerateIRParams,
     ) -> ExpResult {
-        return self.l_or_exp.short_circuit_eval(buf, params);
+        return params.with_span(self.span, |params| {
+            let res = match &self.cond_exp {
+                CondExp::LOrExp(l_or_exp) => l_or_exp.short_circuit_eval(buf, params),
+                CondExp::CondExp(_, _, _) => self.cond_exp.generate_koopa_ir(buf, params),
+            };
+            return generate_cond(res, buf, params);
+        });
     }
 }
 
-impl UnaryExp {
+impl CondExp {
     pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
         match self {
-            UnaryExp::UnaryExp(unary_op, unary_exp) => {
-                let unary_exp_res = unary_exp.generate_koopa_ir(buf, params); // 先计算里层的表达式
-
-                let mut unary_exp_buf: Vec<u8> = Vec::new();
-                match unary_exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        write!(unary_exp_buf, "%{}", reg_count - 1).unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        write!(unary_exp_buf, "{}", int_res).unwrap();
+            CondExp::LOrExp(l_or_exp) => {
+                return l_or_exp.generate_koopa_ir(buf, params);
+            }
+            CondExp::CondExp(cond, true_exp, false_exp) => {
+                params.cond_idx += 1;
+                let cond_idx = params.cond_idx;
+                // 外面可能正在短路求值, 跳转目标要保存下来
+                let jump_true_branch = params.jump_true_branch;
+                let jump_false_branch = params.jump_false_branch;
+                params.jump_true_branch = -6; // 是cond_true
+                params.jump_false_branch = -7; // 是cond_false
+                params.cond_stack.push(cond_idx);
+                let cond_res = cond.short_circuit_eval(buf, params);
+                params.cond_stack.pop();
+                params.jump_true_branch = jump_true_branch;
+                params.jump_false_branch = jump_false_branch;
+                // 条件是常数就只生成选中的那一边
+                if let ExpResult::IntResult(int_res) = cond_res {
+                    if int_res != 0 {
+                        return true_exp.generate_koopa_ir(buf, params);
                     }
+                    return false_exp.generate_koopa_ir(buf, params);
+                }
+                // 两边的结果都存到一个栈上的位置, 最后再读出来
+                // 有一边是float的话结果就是float, 要两边都生成完才知道, 所以先写到别的缓冲区里
+                let mut true_buf: Vec<u8> = Vec::new();
+                let true_res = true_exp.generate_koopa_ir(&mut true_buf, params);
+                let mut false_buf: Vec<u8> = Vec::new();
+                let false_res = false_exp.generate_koopa_ir(&mut false_buf, params);
+                // 指针的话两边要是一样的类型, convert_exp_result会检查
+                let btype = match (true_res.btype(), false_res.btype()) {
+                    (BType::Pointer(base), _) | (_, BType::Pointer(base)) => BType::Pointer(base),
+                    (BType::Float, _) | (_, BType::Float) => BType::Float,
+                    _ => BType::Int,
+                };
+                let true_res = convert_exp_result(true_res, &btype, &mut true_buf, params);
+                let false_res = convert_exp_result(false_res, &btype, &mut false_buf, params);
+                writeln!(buf, "  %ternary{} = alloc {}", cond_idx, koopa_type_str(&btype_str(&btype), params)).unwrap();
+                writeln!(
+                    buf,
+                    "  br {}, %cond_true{}, %cond_false{}",
+                    exp_result_str(&cond_res),
+                    cond_idx,
+                    cond_idx
+                )
+                .unwrap();
+                writeln!(buf, "%cond_true{}:", cond_idx).unwrap();
+                buf.extend(true_buf);
+                writeln!(buf, "  store {}, %ternary{}", exp_result_str(&true_res), cond_idx).unwrap();
+                writeln!(buf, "  jump %cond_end{}", cond_idx).unwrap();
+                writeln!(buf, "%cond_false{}:", cond_idx).unwrap();
+                buf.extend(false_buf);
+                writeln!(buf, "  store {}, %ternary{}", exp_result_str(&false_res), cond_idx).unwrap();
+                writeln!(buf, "  jump %cond_end{}", cond_idx).unwrap();
+                writeln!(buf, "%cond_end{}:", cond_idx).unwrap();
+                return generate_load(&format!("%ternary{}", cond_idx), &btype, buf, params);
+            }
+        }
+    }
+
+    pub fn calc_const(&self, params: &mut GenerateIRParams) -> ConstValue {
+        match self {
+            CondExp::LOrExp(l_or_exp) => {
+                return l_or_exp.calc_const(params);
+            }
+            CondExp::CondExp(cond, true_exp, false_exp) => {
+                if cond.calc_const(params).is_true() {
+                    return true_exp.calc_const(params);
+                }
+                return false_exp.calc_const(params);
+            }
+        }
+    }
+}
+
+impl Exp {
+    // 求值之后的类型: 数组名(或者没取到底的数组)退化成的指针要看左值, 其他的看结果
+    pub fn koopa_type(&self, exp_res: &ExpResult, params: &mut GenerateIRParams) -> String {
+        match self.as_l_val() {
+            Some(l_val) => return l_val.koopa_type(params),
+            None => return btype_str(&exp_res.btype()),
+        }
+    }
+
+    // 如果这个表达式只是一个左值(可能带括号), 就把它拿出来
+    pub fn as_l_val(&self) -> Option<&LVal> {
+        if let Some(UnaryExp::PrimaryExp(primary_exp)) = self.as_unary_exp() {
+            if let PrimaryExp::LVal(l_val) = primary_exp.as_ref() {
+                return Some(l_val);
+            }
+        }
+        return None;
+    }
+
+    // 如果这个表达式只是一个一元表达式, 就把它拿出来, 括号也拆掉
+    pub fn as_unary_exp(&self) -> Option<&UnaryExp> {
+        if let CondExp::LOrExp(LOrExp::LAndExp(LAndExp::BitOrExp(BitOrExp::BitXorExp(BitXorExp::BitAndExp(
+            BitAndExp::EqExp(EqExp::RelExp(RelExp::ShiftExp(ShiftExp::AddExp(AddExp::MulExp(MulExp::UnaryExp(
+                unary_exp,
+            )))))),
+        ))))) = &self.cond_exp
+        {
+            if let UnaryExp::PrimaryExp(primary_exp) = unary_exp {
+                if let PrimaryExp::Exp(exp) = primary_exp.as_ref() {
+                    return exp.as_unary_exp();
                 }
+            }
+            return Some(unary_exp);
+        }
+        return None;
+    }
+}
+
+// 函数调用, value_used表示返回值是不是要被用到
+pub fn generate_func_call(
+    ident: &str,
+    func_r_params: &[Exp],
+    span: Span,
+    value_used: bool,
+    buf: &mut Vec<u8>,
+    params: &mut GenerateIRParams,
+) -> ExpResult {
+    if ident == "putf" && params.lib_funcs.contains_key("putf") {
+        return generate_putf_call(func_r_params, value_used, buf, params);
+    }
+    // 名字一定是个函数, 语义分析里检查过了
+    let SymVal::FuncName(func_type, param_types) = params.symbol(params.sema.call(span)) else {
+        unreachable!()
+    };
+    // 实参从左往右求值, 指针的类型要算出来才知道, 所以先求值再检查
+    let mut arg_results: Vec<ExpResult> = Vec::new();
+    let mut arg_types: Vec<String> = Vec::new();
+    for (i, exp) in func_r_params.iter().enumerate() {
+        let exp_res = exp.generate_koopa_ir(buf, params);
+        // 0传给指针形参就是空指针
+        match param_types.get(i) {
+            Some(param_type) if param_type.starts_with('*') && is_null_pointer_constant(&exp_res) => {
+                arg_types.push(param_type.clone());
+            }
+            _ => arg_types.push(exp.koopa_type(&exp_res, params)),
+        }
+        arg_results.push(exp_res);
+    }
+    check_func_r_params(ident, &param_types, &arg_types, params);
+    if let Some(used) = params.lib_funcs.get_mut(ident) {
+        *used = true;
+    }
+    // int和float的形参要转换类型
+    let mut args: Vec<String> = Vec::new();
+    for (i, mut exp_res) in arg_results.into_iter().enumerate() {
+        match param_types.get(i).map(|ty| ty.as_str()) {
+            Some("i32") => exp_res = convert_exp_result(exp_res, &BType::Int, buf, params),
+            Some("f32") => exp_res = convert_exp_result(exp_res, &BType::Float, buf, params),
+            Some(param_type) if param_type.starts_with('*') && is_null_pointer_constant(&exp_res) => {
+                let ptr_type = koopa_type_str(param_type, params);
+                args.push(generate_ptr_intrinsic("__null", &ptr_type, &[], buf, params));
+                continue;
+            }
+            _ => {}
+        }
+        args.push(exp_result_str(&exp_res));
+    }
+    match func_type {
+        FuncType::Int | FuncType::Float => {
+            writeln!(
+                buf,
+                "  %{} = call @{}({})",
+                params.var_count,
+                ident,
+                args.join(", ")
+            )
+            .unwrap();
+            params.var_count += 1;
+            if func_type == FuncType::Float {
+                return ExpResult::FloatRegCount(params.var_count);
+            }
+            return ExpResult::RegCount(params.var_count);
+        }
+        FuncType::Void => {
+            if value_used {
+                params.error("E0203", &format!("{} returns void, its value cannot be used", ident));
+            }
+            writeln!(buf, "  call @{}({})", ident, args.join(", ")).unwrap();
+            return ExpResult::IntResult(0);
+        }
+    }
+}
+
+// putf(fmt, ...): 格式串放到全局, 按变参的类型选一个声明, 后端再按C的变参规则传参
+pub fn generate_putf_call(func_r_params: &[Exp], value_used: bool, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
+    if value_used {
+        params.error("E0203", "putf returns void, its value cannot be used");
+    }
+    let fmt = match func_r_params.first().and_then(|exp| exp.as_unary_exp()) {
+        Some(UnaryExp::PrimaryExp(primary_exp)) => match primary_exp.as_ref() {
+            PrimaryExp::StringLit(fmt) => Some(fmt.clone()),
+            _ => None,
+        },
+        _ => None,
+    };
+    let Some(fmt) = fmt else {
+        params.error("E0605", "the first argument of putf must be a string literal");
+        return ExpResult::IntResult(0);
+    };
+    // 一样的字符串只存一份
+    let str_idx = match params.str_lits.iter().position(|str_lit| *str_lit == fmt) {
+        Some(str_idx) => str_idx,
+        None => {
+            params.str_lits.push(fmt);
+            params.str_lits.len() - 1
+        }
+    };
+    writeln!(buf, "  %{} = getelemptr @__str{}, 0", params.var_count, str_idx).unwrap();
+    let mut args = vec![format!("%{}", params.var_count)];
+    params.var_count += 1;
+    let mut sig = String::new();
+    for exp in &func_r_params[1..] {
+        let exp_res = exp.generate_koopa_ir(buf, params);
+        if exp.koopa_type(&exp_res, params).starts_with('*') {
+            params.error("E0605", "arrays and pointers cannot be passed to putf");
+        }
+        match exp_res.btype() {
+            BType::Float => sig.push('f'),
+            _ => sig.push('i'),
+        }
+        args.push(exp_result_str(&exp_res));
+    }
+    if !params.putf_decls.contains(&sig) {
+        params.putf_decls.push(sig.clone());
+    }
+    writeln!(buf, "  call @__putf_{}({})", sig, args.join(", ")).unwrap();
+    return ExpResult::IntResult(0);
+}
 
-                let unary_exp_str = String::from_utf8(unary_exp_buf).unwrap();
+// ++和--: 读出来, 加减1, 再写回去; 前缀的结果是新值, 后缀的是旧值
+// target是算好的左值的地址和类型, 指针加减1就是挪到下一个元素
+pub fn generate_inc_dec(
+    target: Option<(String, BType)>,
+    inc_dec_op: &IncDecOp,
+    is_prefix: bool,
+    buf: &mut Vec<u8>,
+    params: &mut GenerateIRParams,
+) -> ExpResult {
+    let Some((l_val_addr, btype)) = target else {
+        return ExpResult::IntResult(0);
+    };
+    let old_val = generate_load(&l_val_addr, &btype, buf, params);
+    let op = match inc_dec_op {
+        IncDecOp::Inc => "add",
+        IncDecOp::Dec => "sub",
+    };
+    let new_val = generate_arith(op, old_val.clone(), ExpResult::IntResult(1), buf, params);
+    writeln!(buf, "  store {}, {}", exp_result_str(&new_val), l_val_addr).unwrap();
+    if is_prefix {
+        return new_val;
+    }
+    return old_val;
+}
 
+impl UnaryExp {
+    pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
+        match self {
+            UnaryExp::UnaryExp(unary_op, unary_exp) => {
+                if matches!(unary_op, UnaryOp::Sub) && unary_exp.is_int_min_literal() {
+                    return ExpResult::IntResult(i32::MIN);
+                }
+                match unary_op {
+                    UnaryOp::Addr => {
+                        // &x 不用读x的值, 只要它的地址
+                        if unary_exp.is_struct_member(params) {
+                            params.error("E0504", "cannot take the address of a member of struct type");
+                            return ExpResult::IntResult(0);
+                        }
+                        let Some((addr, btype)) = unary_exp.generate_addr(buf, params) else {
+                            return ExpResult::IntResult(0);
+                        };
+                        // 地址可能是 @x 这样的名字, 用getptr放到临时变量里
+                        writeln!(buf, "  %{} = getptr {}, 0", params.var_count, addr).unwrap();
+                        params.var_count += 1;
+                        return ExpResult::PtrRegCount(params.var_count, BType::Pointer(Box::new(btype)));
+                    }
+                    UnaryOp::Deref => match self.generate_addr(buf, params) {
+                        Some((addr, btype)) => return generate_load(&addr, &btype, buf, params),
+                        None => return ExpResult::IntResult(0),
+                    },
+                    _ => {}
+                }
+                let unary_exp_res = unary_exp.generate_koopa_ir(buf, params); // 先计算里层的表达式
                 match unary_op {
                     UnaryOp::Add => {
                         return unary_exp_res;
                     }
-                    UnaryOp::Sub => {
-                        writeln!(buf, "  %{} = sub 0, {}", params.var_count, unary_exp_str)
-                            .unwrap();
-                    }
+                    UnaryOp::Sub => match unary_exp_res {
+                        ExpResult::FloatResult(float_res) => {
+                            return ExpResult::FloatResult(-float_res);
+                        }
+                        ExpResult::FloatRegCount(_) => {
+                            return generate_float_intrinsic("__fneg", &[unary_exp_res], buf, params);
+                        }
+                        _ => {
+                            return generate_arith("sub", ExpResult::IntResult(0), unary_exp_res, buf, params);
+                        }
+                    },
                     UnaryOp::Rev => {
-                        writeln!(buf, "  %{} = eq {}, 0", params.var_count, unary_exp_str).unwrap();
+                        return generate_arith("eq", unary_exp_res, ExpResult::IntResult(0), buf, params);
                     }
+                    UnaryOp::Not => {
+                        if unary_exp_res.btype() == BType::Float {
+                            params.error("E0202", "wrong type argument to bit-complement");
+                        }
+                        let unary_exp_res = convert_exp_result(unary_exp_res, &BType::Int, buf, params);
+                        return generate_arith("xor", unary_exp_res, ExpResult::IntResult(-1), buf, params);
+                    }
+                    UnaryOp::Addr | UnaryOp::Deref => unreachable!(),
                 }
-                params.var_count = params.var_count + 1;
-                let exp_res = ExpResult::RegCount(params.var_count);
-                return exp_res;
             }
             UnaryExp::PrimaryExp(primary_exp) => {
                 return primary_exp.generate_koopa_ir(buf, params);
             }
+            UnaryExp::FuncCall(ident, func_r_params, span) => {
+                return params.with_span(*span, |params| generate_func_call(ident, func_r_params, *span, true, buf, params));
+            }
+            UnaryExp::PreIncDec(inc_dec_op, unary_exp) => {
+                let target = unary_exp.generate_addr(buf, params);
+                return generate_inc_dec(target, inc_dec_op, true, buf, params);
+            }
+            UnaryExp::PostIncDec(primary_exp, inc_dec_op) => {
+                let target = primary_exp.generate_addr(buf, params);
+                return generate_inc_dec(target, inc_dec_op, false, buf, params);
+            }
         }
     }
 
-    pub fn calc_const(&self, params: &mut GenerateIRParams) -> i32 {
+    pub fn calc_const(&self, params: &mut GenerateIRParams) -> ConstValue {
         match self {
             UnaryExp::UnaryExp(unary_op, unary_exp) => {
+                if matches!(unary_op, UnaryOp::Sub) && unary_exp.is_int_min_literal() {
+                    return ConstValue::Int(i32::MIN);
+                }
                 let unary_exp_res = unary_exp.calc_const(params); // 先计算里层的表达式
                 match unary_op {
                     UnaryOp::Add => {
                         return unary_exp_res;
                     }
-                    UnaryOp::Sub => {
-                        return -unary_exp_res;
-                    }
+                    UnaryOp::Sub => match unary_exp_res {
+                        ConstValue::Int(int_res) => return calc_const_arith("sub", ConstValue::Int(0), ConstValue::Int(int_res), params),
+                        ConstValue::Float(float_res) => return ConstValue::Float(-float_res),
+                    },
                     UnaryOp::Rev => {
-                        // println!("!{} = {}", unary_exp_res, 1);
-                        // stdout().write_all(b"rev is called\n").unwrap();
-                        return if unary_exp_res == 0 { 1 } else { 0 };
+                        return ConstValue::Int(if unary_exp_res.is_true() { 0 } else { 1 });
                     }
+                    UnaryOp::Not => {
+                        if unary_exp_res.btype() == BType::Float {
+                            params.error("E0202", "wrong type argument to bit-complement");
+                        }
+                        return ConstValue::Int(!unary_exp_res.to_int());
+                    }
+                    UnaryOp::Addr | UnaryOp::Deref => unreachable!(),
                 }
             }
             UnaryExp::PrimaryExp(primary_exp) => {
                 return primary_exp.calc_const(params);
             }
+            // 常量表达式里不能有这些, 语义分析里检查过了
+            UnaryExp::FuncCall(_, _, _) | UnaryExp::PreIncDec(_, _) | UnaryExp::PostIncDec(_, _) => unreachable!(),
+        }
+    }
+}
+
+impl UnaryExp {
+    // 作为左值时的地址和类型: 变量, 数组元素, 或者 *p; 不是左值就返回None
+    pub fn generate_addr(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> Option<(String, BType)> {
+        match self {
+            UnaryExp::PrimaryExp(primary_exp) => {
+                return primary_exp.generate_addr(buf, params);
+            }
+            UnaryExp::UnaryExp(UnaryOp::Deref, unary_exp) => {
+                let ptr = unary_exp.generate_koopa_ir(buf, params);
+                if let ExpResult::PtrRegCount(_, BType::Pointer(base)) = &ptr {
+                    return Some((exp_result_str(&ptr), base.as_ref().clone()));
+                }
+                params.error(
+                    "E0202",
+                    &format!("invalid type argument of unary '*' (have {})", btype_str(&ptr.btype())),
+                );
+                return None;
+            }
+            _ => return None,
+        }
+    }
+
+    // a.b 这样结构体类型的成员: 地址是指向它第一个字的 *i32, 不是整个结构体的 *[i32, n]
+    pub fn is_struct_member(&self, params: &GenerateIRParams) -> bool {
+        let UnaryExp::PrimaryExp(primary_exp) = self else {
+            return false;
+        };
+        match primary_exp.as_ref() {
+            PrimaryExp::LVal(l_val) => {
+                return !l_val.members.is_empty() && matches!(params.sema.lvals.get(&l_val.span), Some(info) if matches!(info.btype, BType::Struct(_)));
+            }
+            PrimaryExp::Exp(exp) => return exp.as_unary_exp().is_some_and(|unary_exp| unary_exp.is_struct_member(params)),
+            _ => return false,
+        }
+    }
+
+    // -2147483648: 字面量本身放不进int, 取负之后正好是INT_MIN, 不用警告
+    pub fn is_int_min_literal(&self) -> bool {
+        if let UnaryExp::PrimaryExp(primary_exp) = self {
+            return matches!(primary_exp.as_ref(), PrimaryExp::Number(Number::IntConst(2147483648)));
         }
+        return false;
     }
 }
 
 impl PrimaryExp {
+    pub fn generate_addr(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> Option<(String, BType)> {
+        match self {
+            PrimaryExp::LVal(l_val) if l_val.is_element(params) => {
+                let btype = l_val.btype(params);
+                return Some((l_val.generate_addr(buf, params), btype));
+            }
+            PrimaryExp::Exp(exp) => match exp.as_unary_exp() {
+                Some(unary_exp) => return unary_exp.generate_addr(buf, params),
+                None => return None,
+            },
+            _ => return None,
+        }
+    }
+
     pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
         match self {
             PrimaryExp::Exp(exp) => {
@@ -684,28 +2558,39 @@ impl PrimaryExp {
             }
             PrimaryExp::Number(num) => match num {
                 Number::IntConst(num) => {
-                    return ExpResult::IntResult(*num);
+                    return ExpResult::IntResult(int_literal(*num, params));
+                }
+                Number::FloatConst(num) => {
+                    return ExpResult::FloatResult(*num);
                 }
             },
             PrimaryExp::LVal(l_val) => {
                 return l_val.generate_koopa_ir(buf, params);
             }
+            PrimaryExp::StringLit(_) => {
+                params.error("E0605", "string literals can only be used as the format of putf");
+                return ExpResult::IntResult(0);
+            }
         }
     }
 
-    pub fn calc_const(&self, params: &mut GenerateIRParams) -> i32 {
+    pub fn calc_const(&self, params: &mut GenerateIRParams) -> ConstValue {
         match self {
             PrimaryExp::Exp(exp) => {
                 return exp.calc_const(params);
             }
             PrimaryExp::Number(num) => match num {
                 Number::IntConst(num) => {
-                    return *num;
+                    return ConstValue::Int(int_literal(*num, params));
+                }
+                Number::FloatConst(num) => {
+                    return ConstValue::Float(*num);
                 }
             },
             PrimaryExp::LVal(l_val) => {
                 return l_val.calc_const(params);
             }
+            PrimaryExp::StringLit(_) => unreachable!(),
         }
     }
 }
@@ -717,62 +2602,23 @@ impl AddExp {
                 return mul_exp.generate_koopa_ir(buf, params);
             }
             AddExp::AddExp(add_exp, add_op, mul_exp) => {
-                let mul_exp_res = mul_exp.generate_koopa_ir(buf, params);
+                // 从左往右求值, 函数调用可能有副作用
                 let add_exp_res = add_exp.generate_koopa_ir(buf, params);
-                match add_op {
-                    AddOp::Add => {
-                        write!(buf, "  %{} = add ", params.var_count).unwrap();
-                    }
-                    AddOp::Sub => {
-                        write!(buf, "  %{} = sub ", params.var_count).unwrap();
-                    }
-                }
-                params.var_count = params.var_count + 1;
-
-                match add_exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        write!(buf, "%{}", reg_count - 1).unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        write!(buf, "{}", int_res).unwrap();
-                    }
-                }
-                write!(buf, ", ").unwrap();
-                match mul_exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        writeln!(buf, "%{}", reg_count - 1).unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        writeln!(buf, "{}", int_res).unwrap();
-                    }
-                }
-                let res = ExpResult::RegCount(params.var_count);
-                return res;
+                let mul_exp_res = mul_exp.generate_koopa_ir(buf, params);
+                return generate_arith(add_op.koopa_op(), add_exp_res, mul_exp_res, buf, params);
             }
         }
     }
 
-    pub fn calc_const(&self, params: &mut GenerateIRParams) -> i32 {
+    pub fn calc_const(&self, params: &mut GenerateIRParams) -> ConstValue {
         match self {
             AddExp::MulExp(mul_exp) => {
                 return mul_exp.calc_const(params);
             }
             AddExp::AddExp(add_exp, add_op, mul_exp) => {
-                let mul_exp_res = mul_exp.calc_const(params);
                 let add_exp_res = add_exp.calc_const(params);
-
-                match add_op {
-                    AddOp::Add => {
-                        // println!("{} + {}", add_exp_res, mul_exp_res);
-                        // stdout().write_all(b"add is called\n").unwrap();
-                        return add_exp_res + mul_exp_res;
-                    }
-                    AddOp::Sub => {
-                        // println!("{} - {}", add_exp_res, mul_exp_res);
-                        // stdout().write_all(b"sub is called\n").unwrap();
-                        return add_exp_res - mul_exp_res;
-                    }
-                }
+                let mul_exp_res = mul_exp.calc_const(params);
+                return calc_const_arith(add_op.koopa_op(), add_exp_res, mul_exp_res, params);
             }
         }
     }
@@ -787,44 +2633,12 @@ impl MulExp {
             MulExp::MulExp(mul_exp, mul_op, unary_exp) => {
                 let mul_exp_res = mul_exp.generate_koopa_ir(buf, params);
                 let unary_exp_res = unary_exp.generate_koopa_ir(buf, params);
-
-                match mul_op {
-                    MulOp::Mul => {
-                        write!(buf, "  %{} = mul ", params.var_count).unwrap();
-                    }
-                    MulOp::Div => {
-                        write!(buf, "  %{} = div ", params.var_count).unwrap();
-                    }
-                    MulOp::Mod => {
-                        write!(buf, "  %{} = mod ", params.var_count).unwrap();
-                    }
-                }
-                params.var_count = params.var_count + 1;
-
-                match mul_exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        write!(buf, "%{}", reg_count - 1).unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        write!(buf, "{}", int_res).unwrap();
-                    }
-                }
-                write!(buf, ", ").unwrap();
-                match unary_exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        writeln!(buf, "%{}", reg_count - 1).unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        writeln!(buf, "{}", int_res).unwrap();
-                    }
-                }
-                let res = ExpResult::RegCount(params.var_count);
-                return res;
+                return generate_arith(mul_op.koopa_op(), mul_exp_res, unary_exp_res, buf, params);
             }
         }
     }
 
-    pub fn calc_const(&self, params: &mut GenerateIRParams) -> i32 {
+    pub fn calc_const(&self, params: &mut GenerateIRParams) -> ConstValue {
         match self {
             MulExp::UnaryExp(unary_exp) => {
                 return unary_exp.calc_const(params);
@@ -832,18 +2646,7 @@ impl MulExp {
             MulExp::MulExp(mul_exp, mul_op, unary_exp) => {
                 let mul_exp_res = mul_exp.calc_const(params);
                 let unary_exp_res = unary_exp.calc_const(params);
-
-                match mul_op {
-                    MulOp::Mul => {
-                        return mul_exp_res * unary_exp_res;
-                    }
-                    MulOp::Div => {
-                        return mul_exp_res / unary_exp_res;
-                    }
-                    MulOp::Mod => {
-                        return mul_exp_res % unary_exp_res;
-                    }
-                }
+                return calc_const_arith(mul_op.koopa_op(), mul_exp_res, unary_exp_res, params);
             }
         }
     }
@@ -852,75 +2655,26 @@ impl MulExp {
 impl RelExp {
     pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
         match self {
-            RelExp::AddExp(add_exp) => {
-                return add_exp.generate_koopa_ir(buf, params);
+            RelExp::ShiftExp(shift_exp) => {
+                return shift_exp.generate_koopa_ir(buf, params);
             }
-            RelExp::RelExp(rel_exp, rel_op, add_exp) => {
+            RelExp::RelExp(rel_exp, rel_op, shift_exp) => {
                 let rel_exp_res = rel_exp.generate_koopa_ir(buf, params);
-                let add_exp_res = add_exp.generate_koopa_ir(buf, params);
-
-                match rel_op {
-                    RelOp::Lt => {
-                        write!(buf, "  %{} = lt ", params.var_count).unwrap();
-                    }
-                    RelOp::Gt => {
-                        write!(buf, "  %{} = gt ", params.var_count).unwrap();
-                    }
-                    RelOp::Le => {
-                        write!(buf, "  %{} = le ", params.var_count).unwrap();
-                    }
-                    RelOp::Ge => {
-                        write!(buf, "  %{} = ge ", params.var_count).unwrap();
-                    }
-                }
-                params.var_count = params.var_count + 1;
-
-                match rel_exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        write!(buf, "%{}", reg_count - 1).unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        write!(buf, "{}", int_res).unwrap();
-                    }
-                }
-                write!(buf, ", ").unwrap();
-                match add_exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        writeln!(buf, "%{}", reg_count - 1).unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        writeln!(buf, "{}", int_res).unwrap();
-                    }
-                }
-                let res = ExpResult::RegCount(params.var_count);
-                return res;
+                let shift_exp_res = shift_exp.generate_koopa_ir(buf, params);
+                return generate_arith(rel_op.koopa_op(), rel_exp_res, shift_exp_res, buf, params);
             }
         }
     }
 
-    pub fn calc_const(&self, params: &mut GenerateIRParams) -> i32 {
+    pub fn calc_const(&self, params: &mut GenerateIRParams) -> ConstValue {
         match self {
-            RelExp::AddExp(add_exp) => {
-                return add_exp.calc_const(params);
+            RelExp::ShiftExp(shift_exp) => {
+                return shift_exp.calc_const(params);
             }
-            RelExp::RelExp(rel_exp, rel_op, add_exp) => {
+            RelExp::RelExp(rel_exp, rel_op, shift_exp) => {
                 let rel_exp_res = rel_exp.calc_const(params);
-                let add_exp_res = add_exp.calc_const(params);
-
-                match rel_op {
-                    RelOp::Lt => {
-                        return if rel_exp_res < add_exp_res { 1 } else { 0 };
-                    }
-                    RelOp::Gt => {
-                        return if rel_exp_res > add_exp_res { 1 } else { 0 };
-                    }
-                    RelOp::Le => {
-                        return if rel_exp_res <= add_exp_res { 1 } else { 0 };
-                    }
-                    RelOp::Ge => {
-                        return if rel_exp_res >= add_exp_res { 1 } else { 0 };
-                    }
-                }
+                let shift_exp_res = shift_exp.calc_const(params);
+                return calc_const_arith(rel_op.koopa_op(), rel_exp_res, shift_exp_res, params);
             }
         }
     }
@@ -935,41 +2689,12 @@ impl EqExp {
             EqExp::EqExp(eq_exp, eq_op, rel_exp) => {
                 let eq_exp_res = eq_exp.generate_koopa_ir(buf, params);
                 let rel_exp_res = rel_exp.generate_koopa_ir(buf, params);
-
-                match eq_op {
-                    EqOp::Eq => {
-                        write!(buf, "  %{} = eq ", params.var_count).unwrap();
-                    }
-                    EqOp::Ne => {
-                        write!(buf, "  %{} = ne ", params.var_count).unwrap();
-                    }
-                }
-                params.var_count = params.var_count + 1;
-
-                match eq_exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        write!(buf, "%{}", reg_count - 1).unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        write!(buf, "{}", int_res).unwrap();
-                    }
-                }
-                write!(buf, ", ").unwrap();
-                match rel_exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        writeln!(buf, "%{}", reg_count - 1).unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        writeln!(buf, "{}", int_res).unwrap();
-                    }
-                }
-                let res = ExpResult::RegCount(params.var_count);
-                return res;
+                return generate_arith(eq_op.koopa_op(), eq_exp_res, rel_exp_res, buf, params);
             }
         }
     }
 
-    pub fn calc_const(&self, params: &mut GenerateIRParams) -> i32 {
+    pub fn calc_const(&self, params: &mut GenerateIRParams) -> ConstValue {
         match self {
             EqExp::RelExp(rel_exp) => {
                 return rel_exp.calc_const(params);
@@ -977,89 +2702,239 @@ impl EqExp {
             EqExp::EqExp(eq_exp, eq_op, rel_exp) => {
                 let eq_exp_res = eq_exp.calc_const(params);
                 let rel_exp_res = rel_exp.calc_const(params);
+                return calc_const_arith(eq_op.koopa_op(), eq_exp_res, rel_exp_res, params);
+            }
+        }
+    }
+}
 
-                match eq_op {
-                    EqOp::Eq => {
-                        return if eq_exp_res == rel_exp_res { 1 } else { 0 };
-                    }
-                    EqOp::Ne => {
-                        return if eq_exp_res != rel_exp_res { 1 } else { 0 };
-                    }
-                }
+impl AddOp {
+    pub fn koopa_op(&self) -> &'static str {
+        match self {
+            AddOp::Add => return "add",
+            AddOp::Sub => return "sub",
+        }
+    }
+}
+
+impl MulOp {
+    pub fn koopa_op(&self) -> &'static str {
+        match self {
+            MulOp::Mul => return "mul",
+            MulOp::Div => return "div",
+            MulOp::Mod => return "mod",
+        }
+    }
+}
+
+impl RelOp {
+    pub fn koopa_op(&self) -> &'static str {
+        match self {
+            RelOp::Lt => return "lt",
+            RelOp::Gt => return "gt",
+            RelOp::Le => return "le",
+            RelOp::Ge => return "ge",
+        }
+    }
+}
+
+impl EqOp {
+    pub fn koopa_op(&self) -> &'static str {
+        match self {
+            EqOp::Eq => return "eq",
+            EqOp::Ne => return "ne",
+        }
+    }
+}
+
+// 二元运算的结果放到一个新的临时变量里
+pub fn generate_binary_op(op: &str, lhs: &ExpResult, rhs: &ExpResult, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
+    writeln!(
+        buf,
+        "  %{} = {} {}, {}",
+        params.var_count,
+        op,
+        exp_result_str(lhs),
+        exp_result_str(rhs)
+    )
+    .unwrap();
+    params.var_count += 1;
+    return ExpResult::RegCount(params.var_count);
+}
+
+impl ShiftExp {
+    pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
+        match self {
+            ShiftExp::AddExp(add_exp) => {
+                return add_exp.generate_koopa_ir(buf, params);
+            }
+            ShiftExp::ShiftExp(shift_exp, shift_op, add_exp) => {
+                let shift_exp_res = shift_exp.generate_koopa_ir(buf, params);
+                let add_exp_res = add_exp.generate_koopa_ir(buf, params);
+                let op = match shift_op {
+                    ShiftOp::Shl => "shl",
+                    ShiftOp::Shr => "sar",
+                };
+                return generate_arith(op, shift_exp_res, add_exp_res, buf, params);
+            }
+        }
+    }
+
+    pub fn calc_const(&self, params: &mut GenerateIRParams) -> ConstValue {
+        match self {
+            ShiftExp::AddExp(add_exp) => {
+                return add_exp.calc_const(params);
+            }
+            ShiftExp::ShiftExp(shift_exp, shift_op, add_exp) => {
+                let shift_exp_res = shift_exp.calc_const(params);
+                let add_exp_res = add_exp.calc_const(params);
+                let op = match shift_op {
+                    ShiftOp::Shl => "shl",
+                    ShiftOp::Shr => "sar",
+                };
+                return calc_const_arith(op, shift_exp_res, add_exp_res, params);
             }
         }
     }
 }
 
-impl LAndExp {
+impl BitAndExp {
     pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
         match self {
-            LAndExp::EqExp(eq_exp) => {
+            BitAndExp::EqExp(eq_exp) => {
                 return eq_exp.generate_koopa_ir(buf, params);
             }
-            LAndExp::LAndExp(l_and_exp, l_and_op, eq_exp) => {
-                let l_and_exp_res = l_and_exp.generate_koopa_ir(buf, params);
+            BitAndExp::BitAndExp(bit_and_exp, _bit_and_op, eq_exp) => {
+                let bit_and_exp_res = bit_and_exp.generate_koopa_ir(buf, params);
                 let eq_exp_res = eq_exp.generate_koopa_ir(buf, params);
-                /* 逻辑与应该是 and (ne lhs 0) (ne rhs 0)  */
+                return generate_arith("and", bit_and_exp_res, eq_exp_res, buf, params);
+            }
+        }
+    }
 
-                match l_and_exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        writeln!(buf, "  %{} = ne %{}, 0", params.var_count, reg_count - 1)
-                            .unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        writeln!(buf, "  %{} = ne {}, 0", params.var_count, int_res).unwrap();
-                    }
-                }
-                params.var_count = params.var_count + 1;
+    pub fn calc_const(&self, params: &mut GenerateIRParams) -> ConstValue {
+        match self {
+            BitAndExp::EqExp(eq_exp) => {
+                return eq_exp.calc_const(params);
+            }
+            BitAndExp::BitAndExp(bit_and_exp, _bit_and_op, eq_exp) => {
+                let bit_and_exp_res = bit_and_exp.calc_const(params);
+                return calc_const_arith("and", bit_and_exp_res, eq_exp.calc_const(params), params);
+            }
+        }
+    }
+}
 
-                match eq_exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        writeln!(buf, "  %{} = ne %{}, 0", params.var_count, reg_count - 1)
-                            .unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        writeln!(buf, "  %{} = ne {}, 0", params.var_count, int_res).unwrap();
-                    }
-                }
-                params.var_count = params.var_count + 1;
+impl BitXorExp {
+    pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
+        match self {
+            BitXorExp::BitAndExp(bit_and_exp) => {
+                return bit_and_exp.generate_koopa_ir(buf, params);
+            }
+            BitXorExp::BitXorExp(bit_xor_exp, _bit_xor_op, bit_and_exp) => {
+                let bit_xor_exp_res = bit_xor_exp.generate_koopa_ir(buf, params);
+                let bit_and_exp_res = bit_and_exp.generate_koopa_ir(buf, params);
+                return generate_arith("xor", bit_xor_exp_res, bit_and_exp_res, buf, params);
+            }
+        }
+    }
 
-                match l_and_op {
-                    LAndOp::And => {
-                        writeln!(
-                            buf,
-                            "  %{} = and %{}, %{}",
-                            params.var_count,
-                            params.var_count - 2,
-                            params.var_count - 1
-                        )
-                        .unwrap();
-                    }
-                }
-                params.var_count = params.var_count + 1;
+    pub fn calc_const(&self, params: &mut GenerateIRParams) -> ConstValue {
+        match self {
+            BitXorExp::BitAndExp(bit_and_exp) => {
+                return bit_and_exp.calc_const(params);
+            }
+            BitXorExp::BitXorExp(bit_xor_exp, _bit_xor_op, bit_and_exp) => {
+                let bit_xor_exp_res = bit_xor_exp.calc_const(params);
+                return calc_const_arith("xor", bit_xor_exp_res, bit_and_exp.calc_const(params), params);
+            }
+        }
+    }
+}
 
-                let res = ExpResult::RegCount(params.var_count);
-                return res;
+impl BitOrExp {
+    pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
+        match self {
+            BitOrExp::BitXorExp(bit_xor_exp) => {
+                return bit_xor_exp.generate_koopa_ir(buf, params);
+            }
+            BitOrExp::BitOrExp(bit_or_exp, _bit_or_op, bit_xor_exp) => {
+                let bit_or_exp_res = bit_or_exp.generate_koopa_ir(buf, params);
+                let bit_xor_exp_res = bit_xor_exp.generate_koopa_ir(buf, params);
+                return generate_arith("or", bit_or_exp_res, bit_xor_exp_res, buf, params);
             }
         }
     }
 
-    pub fn calc_const(&self, params: &mut GenerateIRParams) -> i32 {
+    pub fn calc_const(&self, params: &mut GenerateIRParams) -> ConstValue {
         match self {
-            LAndExp::EqExp(eq_exp) => {
-                return eq_exp.calc_const(params);
+            BitOrExp::BitXorExp(bit_xor_exp) => {
+                return bit_xor_exp.calc_const(params);
             }
-            LAndExp::LAndExp(l_and_exp, l_and_op, eq_exp) => {
-                let l_and_exp_res = l_and_exp.calc_const(params);
-                let eq_exp_res = eq_exp.calc_const(params);
+            BitOrExp::BitOrExp(bit_or_exp, _bit_or_op, bit_xor_exp) => {
+                let bit_or_exp_res = bit_or_exp.calc_const(params);
+                return calc_const_arith("or", bit_or_exp_res, bit_xor_exp.calc_const(params), params);
+            }
+        }
+    }
+}
+
+// 不在if/while条件里的 && 和 ||, 比如 int y = x != 0 && 10 / x; 也要短路求值:
+// 和条件里一样跳转, 真假两边分别把1和0存到栈上, 最后读出来
+fn generate_logic_value(
+    buf: &mut Vec<u8>,
+    params: &mut GenerateIRParams,
+    short_circuit_eval: impl FnOnce(&mut Vec<u8>, &mut GenerateIRParams) -> ExpResult,
+) -> ExpResult {
+    params.logic_idx += 1;
+    let logic_idx = params.logic_idx;
+    // 外面可能正在短路求值, 跳转目标要保存下来
+    let jump_true_branch = params.jump_true_branch;
+    let jump_false_branch = params.jump_false_branch;
+    params.jump_true_branch = -8; // 是logic_true
+    params.jump_false_branch = -9; // 是logic_false
+    params.logic_stack.push(logic_idx);
+    writeln!(buf, "  %logic{} = alloc i32", logic_idx).unwrap();
+    let res = short_circuit_eval(buf, params);
+    params.logic_stack.pop();
+    params.jump_true_branch = jump_true_branch;
+    params.jump_false_branch = jump_false_branch;
+    writeln!(buf, "  br {}, %logic_true{}, %logic_false{}", exp_result_str(&res), logic_idx, logic_idx).unwrap();
+    writeln!(buf, "%logic_true{}:", logic_idx).unwrap();
+    writeln!(buf, "  store 1, %logic{}", logic_idx).unwrap();
+    writeln!(buf, "  jump %logic_end{}", logic_idx).unwrap();
+    writeln!(buf, "%logic_false{}:", logic_idx).unwrap();
+    writeln!(buf, "  store 0, %logic{}", logic_idx).unwrap();
+    writeln!(buf, "  jump %logic_end{}", logic_idx).unwrap();
+    writeln!(buf, "%logic_end{}:", logic_idx).unwrap();
+    return generate_load(&format!("%logic{}", logic_idx), &BType::Int, buf, params);
+}
+
+impl LAndExp {
+    pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
+        match self {
+            LAndExp::BitOrExp(bit_or_exp) => {
+                return bit_or_exp.generate_koopa_ir(buf, params);
+            }
+            LAndExp::LAndExp(_, _, _) => {
+                // 左边是假的话右边不能求值
+                return generate_logic_value(buf, params, |buf, params| self.short_circuit_eval(buf, params));
+            }
+        }
+    }
+
+    pub fn calc_const(&self, params: &mut GenerateIRParams) -> ConstValue {
+        match self {
+            LAndExp::BitOrExp(bit_or_exp) => {
+                return bit_or_exp.calc_const(params);
+            }
+            LAndExp::LAndExp(l_and_exp, l_and_op, bit_or_exp) => {
+                let l_and_exp_res = l_and_exp.calc_const(params).is_true();
+                let bit_or_exp_res = bit_or_exp.calc_const(params).is_true();
 
                 match l_and_op {
                     LAndOp::And => {
-                        return if l_and_exp_res != 0 && eq_exp_res != 0 {
-                            1
-                        } else {
-                            0
-                        };
+                        return ConstValue::Int((l_and_exp_res && bit_or_exp_res) as i32);
                     }
                 }
             }
@@ -1072,10 +2947,10 @@ impl LAndExp {
         params: &mut GenerateIRParams,
     ) -> ExpResult {
         match self {
-            LAndExp::EqExp(eq_exp) => {
-                return eq_exp.generate_koopa_ir(buf, params);
+            LAndExp::BitOrExp(bit_or_exp) => {
+                return bit_or_exp.generate_koopa_ir(buf, params);
             }
-            LAndExp::LAndExp(l_and_exp, _l_and_op, eq_exp) => {
+            LAndExp::LAndExp(l_and_exp, _l_and_op, bit_or_exp) => {
                 params.eval_idx += 1;
                 let eval_idx = params.eval_idx;
                 let jump_false_branch = params.jump_false_branch;
@@ -1085,16 +2960,7 @@ impl LAndExp {
                 params.jump_true_branch = eval_idx; // 否则就求右边
                 let l_and_exp_res = l_and_exp.short_circuit_eval(buf, params);
 
-                match l_and_exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        writeln!(buf, "  %{} = ne %{}, 0", params.var_count, reg_count - 1)
-                            .unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        writeln!(buf, "  %{} = ne {}, 0", params.var_count, int_res).unwrap();
-                    }
-                }
-                params.var_count = params.var_count + 1;
+                let l_and_exp_res = generate_arith("ne", l_and_exp_res, ExpResult::IntResult(0), buf, params);
 
                 let jump_false_dest = match params.jump_false_branch {
                     -1 => "then",
@@ -1102,14 +2968,20 @@ impl LAndExp {
                     -3 => "end",
                     -4 => "while_body",
                     -5 => "while_end",
+                    -6 => "cond_true",
+                    -7 => "cond_false",
+                    -8 => "logic_true",
+                    -9 => "logic_false",
                     _ => "short_circuit",
                 };
                 let jump_false_idx = match params.jump_false_branch {
                     -1 => params.then_idx,
                     -2 => params.else_idx,
                     -3 => params.end_idx,
-                    -4 => params.while_idx,
-                    -5 => params.while_idx,
+                    -4 => params.loop_stack.last().unwrap().0,
+                    -5 => params.loop_stack.last().unwrap().0,
+                    -6 | -7 => *params.cond_stack.last().unwrap(),
+                    -8 | -9 => *params.logic_stack.last().unwrap(),
                     idx => idx,
                 };
 
@@ -1119,22 +2991,28 @@ impl LAndExp {
                     -3 => "end",
                     -4 => "while_body",
                     -5 => "while_end",
+                    -6 => "cond_true",
+                    -7 => "cond_false",
+                    -8 => "logic_true",
+                    -9 => "logic_false",
                     _ => "short_circuit",
                 };
                 let jump_true_idx = match params.jump_true_branch {
                     -1 => params.then_idx,
                     -2 => params.else_idx,
                     -3 => params.end_idx,
-                    -4 => params.while_idx,
-                    -5 => params.while_idx,
+                    -4 => params.loop_stack.last().unwrap().0,
+                    -5 => params.loop_stack.last().unwrap().0,
+                    -6 | -7 => *params.cond_stack.last().unwrap(),
+                    -8 | -9 => *params.logic_stack.last().unwrap(),
                     idx => idx,
                 };
 
                 // 实现跳转
                 writeln!(
                     buf,
-                    "  br %{}, %{}{}, %{}{}",
-                    params.var_count - 1,
+                    "  br {}, %{}{}, %{}{}",
+                    exp_result_str(&l_and_exp_res),
                     jump_true_dest,
                     jump_true_idx,
                     jump_false_dest,
@@ -1147,20 +3025,9 @@ impl LAndExp {
                 params.jump_false_branch = jump_false_branch;
                 params.jump_true_branch = jump_true_branch;
 
-                let eq_exp_res = eq_exp.generate_koopa_ir(buf, params);
-
-                match eq_exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        writeln!(buf, "  %{} = ne %{}, 0", params.var_count, reg_count - 1)
-                            .unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        writeln!(buf, "  %{} = ne {}, 0", params.var_count, int_res).unwrap();
-                    }
-                }
-                params.var_count += 1;
+                let bit_or_exp_res = bit_or_exp.generate_koopa_ir(buf, params);
 
-                return ExpResult::RegCount(params.var_count);
+                return generate_arith("ne", bit_or_exp_res, ExpResult::IntResult(0), buf, params);
             }
         }
     }
@@ -1172,68 +3039,25 @@ impl LOrExp {
             LOrExp::LAndExp(l_and_exp) => {
                 return l_and_exp.generate_koopa_ir(buf, params);
             }
-            LOrExp::LOrExp(l_or_exp, l_or_op, l_and_exp) => {
-                let l_or_exp_res = l_or_exp.generate_koopa_ir(buf, params);
-                let l_and_exp_res = l_and_exp.generate_koopa_ir(buf, params);
-
-                match l_or_exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        writeln!(buf, "  %{} = ne %{}, 0", params.var_count, reg_count - 1)
-                            .unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        writeln!(buf, "  %{} = ne {}, 0", params.var_count, int_res).unwrap();
-                    }
-                }
-                params.var_count = params.var_count + 1;
-
-                match l_and_exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        writeln!(buf, "  %{} = ne %{}, 0", params.var_count, reg_count - 1)
-                            .unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        writeln!(buf, "  %{} = ne {}, 0", params.var_count, int_res).unwrap();
-                    }
-                }
-                params.var_count = params.var_count + 1;
-
-                match l_or_op {
-                    LOrOp::Or => {
-                        writeln!(
-                            buf,
-                            "  %{} = or %{}, %{}",
-                            params.var_count,
-                            params.var_count - 2,
-                            params.var_count - 1
-                        )
-                        .unwrap();
-                    }
-                }
-                params.var_count = params.var_count + 1;
-
-                let res = ExpResult::RegCount(params.var_count);
-                return res;
+            LOrExp::LOrExp(_, _, _) => {
+                // 左边是真的话右边不能求值
+                return generate_logic_value(buf, params, |buf, params| self.short_circuit_eval(buf, params));
             }
         }
     }
 
-    pub fn calc_const(&self, params: &mut GenerateIRParams) -> i32 {
+    pub fn calc_const(&self, params: &mut GenerateIRParams) -> ConstValue {
         match self {
             LOrExp::LAndExp(l_and_exp) => {
                 return l_and_exp.calc_const(params);
             }
             LOrExp::LOrExp(l_or_exp, l_or_op, l_and_exp) => {
-                let l_or_exp_res = l_or_exp.calc_const(params);
-                let l_and_exp_res = l_and_exp.calc_const(params);
+                let l_or_exp_res = l_or_exp.calc_const(params).is_true();
+                let l_and_exp_res = l_and_exp.calc_const(params).is_true();
 
                 match l_or_op {
                     LOrOp::Or => {
-                        return if l_or_exp_res != 0 || l_and_exp_res != 0 {
-                            1
-                        } else {
-                            0
-                        };
+                        return ConstValue::Int((l_or_exp_res || l_and_exp_res) as i32);
                     }
                 }
             }
@@ -1260,16 +3084,7 @@ impl LOrExp {
 
                 let l_or_exp_res = l_or_exp.short_circuit_eval(buf, params);
 
-                match l_or_exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        writeln!(buf, "  %{} = ne %{}, 0", params.var_count, reg_count - 1)
-                            .unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        writeln!(buf, "  %{} = ne {}, 0", params.var_count, int_res).unwrap();
-                    }
-                }
-                params.var_count = params.var_count + 1;
+                let l_or_exp_res = generate_arith("ne", l_or_exp_res, ExpResult::IntResult(0), buf, params);
 
                 let jump_false_dest = match params.jump_false_branch {
                     -1 => "then",
@@ -1277,14 +3092,20 @@ impl LOrExp {
                     -3 => "end",
                     -4 => "while_body",
                     -5 => "while_end",
+                    -6 => "cond_true",
+                    -7 => "cond_false",
+                    -8 => "logic_true",
+                    -9 => "logic_false",
                     _ => "short_circuit",
                 };
                 let jump_false_idx = match params.jump_false_branch {
                     -1 => params.then_idx,
                     -2 => params.else_idx,
                     -3 => params.end_idx,
-                    -4 => params.while_idx,
-                    -5 => params.while_idx,
+                    -4 => params.loop_stack.last().unwrap().0,
+                    -5 => params.loop_stack.last().unwrap().0,
+                    -6 | -7 => *params.cond_stack.last().unwrap(),
+                    -8 | -9 => *params.logic_stack.last().unwrap(),
                     idx => idx,
                 };
 
@@ -1294,22 +3115,28 @@ impl LOrExp {
                     -3 => "end",
                     -4 => "while_body",
                     -5 => "while_end",
+                    -6 => "cond_true",
+                    -7 => "cond_false",
+                    -8 => "logic_true",
+                    -9 => "logic_false",
                     _ => "short_circuit",
                 };
                 let jump_true_idx = match params.jump_true_branch {
                     -1 => params.then_idx,
                     -2 => params.else_idx,
                     -3 => params.end_idx,
-                    -4 => params.while_idx,
-                    -5 => params.while_idx,
+                    -4 => params.loop_stack.last().unwrap().0,
+                    -5 => params.loop_stack.last().unwrap().0,
+                    -6 | -7 => *params.cond_stack.last().unwrap(),
+                    -8 | -9 => *params.logic_stack.last().unwrap(),
                     idx => idx,
                 };
 
                 // 实现跳转
                 writeln!(
                     buf,
-                    "  br %{}, %{}{}, %{}{}",
-                    params.var_count - 1,
+                    "  br {}, %{}{}, %{}{}",
+                    exp_result_str(&l_or_exp_res),
                     jump_true_dest,
                     jump_true_idx,
                     jump_false_dest,
@@ -1324,50 +3151,298 @@ impl LOrExp {
 
                 let l_and_exp_res = l_and_exp.short_circuit_eval(buf, params);
 
-                match l_and_exp_res {
-                    ExpResult::RegCount(reg_count) => {
-                        writeln!(buf, "  %{} = ne %{}, 0", params.var_count, reg_count - 1)
-                            .unwrap();
-                    }
-                    ExpResult::IntResult(int_res) => {
-                        writeln!(buf, "  %{} = ne {}, 0", params.var_count, int_res).unwrap();
-                    }
-                }
-                params.var_count += 1;
-
-                return ExpResult::RegCount(params.var_count);
+                return generate_arith("ne", l_and_exp_res, ExpResult::IntResult(0), buf, params);
             }
         }
     }
 }
 
 impl LVal {
-    pub fn calc_const(&self, params: &mut GenerateIRParams) -> i32 {
-        let var_val = params.sym_tab.query(self.ident.clone()).unwrap();
-        match var_val {
+    // 语义分析里解析到的那个声明
+    fn sym_val(&self, params: &GenerateIRParams) -> SymVal {
+        return params.symbol(params.sema.l_val(self.span).decl);
+    }
+
+    // 常量表达式里只会有取到底的常量, 语义分析里检查过了
+    pub fn calc_const(&self, params: &mut GenerateIRParams) -> ConstValue {
+        match self.sym_val(params) {
             SymVal::ConstVal(res) => return res,
-            SymVal::VarName(_idx) => {
-                stderr()
-                    .write_all(b"Error: variables occurred in const init val.\n")
-                    .unwrap();
-                return 0;
+            SymVal::ConstArray(_, ref dims, ref vals, _) => {
+                let mut offset = 0;
+                for (exp, dim) in self.exps.iter().zip(dims) {
+                    let index = exp.calc_const(params).to_int();
+                    if index < 0 || index >= *dim {
+                        params.error_at(self.span, "E0506", &format!("index {} out of bounds for {}", index, self.ident));
+                        return ConstValue::Int(0);
+                    }
+                    offset = offset * dim + index;
+                }
+                return vals[offset as usize];
             }
+            _ => unreachable!(),
         }
     }
     pub fn generate_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
-        let var_val = params.sym_tab.query(self.ident.clone()).unwrap();
+        if !self.members.is_empty() {
+            return self.generate_member_koopa_ir(buf, params);
+        }
+        let var_val = self.sym_val(params);
         match var_val {
-            SymVal::ConstVal(res) => return ExpResult::IntResult(res),
-            SymVal::VarName(idx) => {
-                writeln!(
-                    buf,
-                    "  %{} = load @{}_{}",
-                    params.var_count, self.ident, idx
-                )
-                .unwrap();
-                params.var_count = params.var_count + 1;
-                return ExpResult::RegCount(params.var_count);
+            SymVal::ConstVal(res) => return res.to_exp_result(),
+            SymVal::FuncName(_, _) => unreachable!(),
+            SymVal::VarName(_, _) => {
+                let btype = self.btype(params);
+                let l_val_addr = self.generate_addr(buf, params);
+                return generate_load(&l_val_addr, &btype, buf, params);
+            }
+            SymVal::ConstArray(idx, ref dims, _, ref btype) => {
+                // 下标都是立即数就直接折叠, 否则和普通数组一样去栈上或全局取
+                let indices: Vec<ExpResult> = self
+                    .exps
+                    .iter()
+                    .map(|exp| generate_index(exp, buf, params))
+                    .collect();
+                let all_const = indices
+                    .iter()
+                    .all(|index| matches!(index, ExpResult::IntResult(_)));
+                if all_const && indices.len() == dims.len() {
+                    return self.calc_const(params).to_exp_result();
+                }
+                let l_val_addr = getelemptr_chain(format!("@{}_{}", self.ident, idx), &indices, buf, params);
+                if indices.len() == dims.len() {
+                    return generate_load(&l_val_addr, btype, buf, params);
+                }
+                writeln!(buf, "  %{} = getelemptr {}, 0", params.var_count, l_val_addr).unwrap();
+                params.var_count += 1;
+                return decayed_ptr(params.var_count, dims.len() - indices.len(), btype);
+            }
+            SymVal::ArrayName(_, ref dims, ref btype) | SymVal::PointerName(_, ref dims, ref btype) => {
+                let is_pointer = matches!(var_val, SymVal::PointerName(_, _, _));
+                // 数组形参的第一维不在dims里
+                let total_dims = if is_pointer { dims.len() + 1 } else { dims.len() };
+                let l_val_addr = self.generate_addr(buf, params);
+                if self.exps.len() == total_dims {
+                    return generate_load(&l_val_addr, btype, buf, params);
+                } else if is_pointer && self.exps.is_empty() {
+                    // 本身就是指针, 直接传下去
+                    return decayed_ptr(params.var_count, total_dims, btype);
+                } else {
+                    // 没有取到元素, 退化成指向第一个元素的指针
+                    writeln!(buf, "  %{} = getelemptr {}, 0", params.var_count, l_val_addr).unwrap();
+                }
+                params.var_count += 1;
+                return decayed_ptr(params.var_count, total_dims - self.exps.len(), btype);
+            }
+        }
+    }
+
+    // 成员没有取到底的数组
+    fn generate_member_koopa_ir(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
+        let (btype, dims) = self.member_type(params);
+        let remaining_dims = dims.len();
+        if remaining_dims > 0 && matches!(btype, BType::Struct(_)) {
+            // 地址是 *i32, 没法当成指向结构体的指针
+            params.error_at(self.span, "E0203", &format!("array of struct member of {} cannot be used as a value", self.ident));
+            return ExpResult::IntResult(0);
+        }
+        let l_val_addr = self.generate_addr(buf, params);
+        match remaining_dims {
+            0 => return generate_load(&l_val_addr, &btype, buf, params),
+            // getelemptr算出来的已经是指向第一个元素的指针了
+            1 => return decayed_ptr(params.var_count, 1, &btype),
+            _ => {
+                params.error_at(
+                    self.span,
+                    "E0203",
+                    &format!("multi-dimensional member of {} cannot be used as a value", self.ident),
+                );
+                return ExpResult::IntResult(0);
+            }
+        }
+    }
+
+    // 成员访问之后的类型, 以及最后一个成员还没取下标的那几维
+    fn member_type(&self, params: &GenerateIRParams) -> (BType, Vec<i32>) {
+        let mut btype = self.base_btype(params);
+        let mut dims: Vec<i32> = Vec::new();
+        for member in &self.members {
+            let field = member_field(&btype, member, params);
+            dims = field.dims[member.exps.len()..].to_vec();
+            btype = field.btype;
+        }
+        return (btype, dims);
+    }
+
+    pub fn koopa_type(&self, params: &mut GenerateIRParams) -> String {
+        if !self.members.is_empty() {
+            return match self.member_type(params) {
+                (btype, dims) if !dims.is_empty() => format!("*{}", typed_array_str(&dims[1..], &btype)),
+                (btype, _) => btype_str(&btype),
+            };
+        }
+        match Some(self.sym_val(params)) {
+            Some(SymVal::ArrayName(_, dims, btype)) | Some(SymVal::ConstArray(_, dims, _, btype))
+                if self.exps.len() < dims.len() =>
+            {
+                return format!("*{}", typed_array_str(&dims[self.exps.len() + 1..], &btype));
+            }
+            Some(SymVal::PointerName(_, dims, btype)) if self.exps.len() <= dims.len() => {
+                // 形参的第一维已经是指针了
+                return format!("*{}", typed_array_str(&dims[self.exps.len()..], &btype));
+            }
+            _ => return btype_str(&self.btype(params)),
+        }
+    }
+
+    // 取完下标和成员之后的类型, 语义分析里算好了
+    pub fn btype(&self, params: &GenerateIRParams) -> BType {
+        return params.sema.l_val(self.span).btype.clone();
+    }
+
+    // 变量名和下标部分的类型, 指针变量每取一层下标就去掉一层指针
+    fn base_btype(&self, params: &GenerateIRParams) -> BType {
+        match Some(self.sym_val(params)) {
+            Some(SymVal::ConstVal(val)) => return val.btype(),
+            Some(SymVal::VarName(_, btype)) => {
+                let mut btype = btype;
+                for _ in &self.exps {
+                    if let BType::Pointer(base) = btype {
+                        btype = *base;
+                    }
+                }
+                return btype;
+            }
+            Some(SymVal::ArrayName(_, _, btype))
+            | Some(SymVal::ConstArray(_, _, _, btype))
+            | Some(SymVal::PointerName(_, _, btype)) => return btype,
+            _ => return BType::Int,
+        }
+    }
+
+    // 能不能取地址和赋值: 变量, 下标取到底的数组元素, 或者取到底的成员
+    pub fn is_element(&self, params: &GenerateIRParams) -> bool {
+        return params.sema.l_val(self.span).is_element();
+    }
+
+    // 算出左值的地址; 成员就是在结构体的地址上按字偏移, ->要先把指针读出来.
+    // a.b.c 这样一串成员的偏移加在一起, 最后在最外层的结构体上getelemptr一次;
+    // 所以结构体类型的成员的地址是指向它第一个字的 *i32, 见UnaryExp::is_struct_member
+    pub fn generate_addr(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> String {
+        let mut addr = self.generate_base_addr(buf, params);
+        if self.members.is_empty() {
+            return addr;
+        }
+        let mut btype = self.base_btype(params);
+        let mut offset = ExpResult::IntResult(0);
+        for member in &self.members {
+            let field = member_field(&btype, member, params);
+            // 成员不会是指针, 只有第一个能是 ->
+            if member.arrow {
+                writeln!(buf, "  %{} = load {}", params.var_count, addr).unwrap();
+                addr = format!("%{}", params.var_count);
+                params.var_count += 1;
+            }
+            // 结构体是按字排开的[i32, n], 数组成员的下标乘上后面几维的大小, 元素是结构体的话再乘上它的字数
+            offset = generate_arith("add", offset, ExpResult::IntResult(field.offset / 4), buf, params);
+            let elem_words = struct_words(&field.btype, params).max(1);
+            for (k, exp) in member.exps.iter().enumerate() {
+                let stride: i32 = field.dims[k + 1..].iter().product::<i32>() * elem_words;
+                let index = generate_index(exp, buf, params);
+                let index = generate_arith("mul", index, ExpResult::IntResult(stride), buf, params);
+                offset = generate_arith("add", offset, index, buf, params);
+            }
+            btype = field.btype;
+        }
+        writeln!(buf, "  %{} = getelemptr {}, {}", params.var_count, addr, exp_result_str(&offset)).unwrap();
+        addr = format!("%{}", params.var_count);
+        params.var_count += 1;
+        return addr;
+    }
+
+    // 变量名和下标部分的地址, 数组就一层一层getelemptr下去
+    fn generate_base_addr(&self, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> String {
+        match self.sym_val(params) {
+            // 常量和函数都没有地址, 不会是左值
+            SymVal::ConstVal(_) | SymVal::FuncName(_, _) => unreachable!(),
+            SymVal::VarName(idx, btype) => {
+                // 指针变量的下标: 每一层都先把指针读出来, 再用getptr偏移
+                let mut addr = format!("@{}_{}", self.ident, idx);
+                let mut btype = btype;
+                for exp in &self.exps {
+                    let BType::Pointer(base) = btype else {
+                        unreachable!()
+                    };
+                    writeln!(buf, "  %{} = load {}", params.var_count, addr).unwrap();
+                    let ptr = format!("%{}", params.var_count);
+                    params.var_count += 1;
+                    let exp_res = generate_index(exp, buf, params);
+                    writeln!(buf, "  %{} = getptr {}, {}", params.var_count, ptr, exp_result_str(&exp_res)).unwrap();
+                    addr = format!("%{}", params.var_count);
+                    params.var_count += 1;
+                    btype = *base;
+                }
+                return addr;
+            }
+            SymVal::PointerName(idx, _dims, _) => {
+                // 先把指针读出来, 第一维用getptr, 后面的和数组一样
+                writeln!(buf, "  %{} = load @{}_{}", params.var_count, self.ident, idx).unwrap();
+                let mut ptr = format!("%{}", params.var_count);
+                params.var_count += 1;
+                for (i, exp) in self.exps.iter().enumerate() {
+                    let exp_res = generate_index(exp, buf, params);
+                    let inst = if i == 0 { "getptr" } else { "getelemptr" };
+                    writeln!(
+                        buf,
+                        "  %{} = {} {}, {}",
+                        params.var_count,
+                        inst,
+                        ptr,
+                        exp_result_str(&exp_res)
+                    )
+                    .unwrap();
+                    ptr = format!("%{}", params.var_count);
+                    params.var_count += 1;
+                }
+                return ptr;
+            }
+            SymVal::ArrayName(idx, _, _) | SymVal::ConstArray(idx, _, _, _) => {
+                let indices: Vec<ExpResult> = self
+                    .exps
+                    .iter()
+                    .map(|exp| generate_index(exp, buf, params))
+                    .collect();
+                return getelemptr_chain(format!("@{}_{}", self.ident, idx), &indices, buf, params);
             }
         }
     }
 }
+
+// .要求左边是结构体, ->要求左边是指向结构体的指针; 类型和成员名语义分析里检查过了
+pub fn member_field(btype: &BType, member: &Member, params: &GenerateIRParams) -> FieldInfo {
+    let struct_type = match btype {
+        BType::Pointer(base) if member.arrow => base.as_ref(),
+        _ => btype,
+    };
+    let BType::Struct(ident) = struct_type else {
+        unreachable!()
+    };
+    return params.structs[ident].field(&member.ident).unwrap().clone();
+}
+
+// 数组下标必须是整数
+pub fn generate_index(exp: &Exp, buf: &mut Vec<u8>, params: &mut GenerateIRParams) -> ExpResult {
+    let exp_res = exp.generate_koopa_ir(buf, params);
+    if exp_res.btype() == BType::Float {
+        params.error("E0204", "array subscript is not an integer");
+    }
+    return convert_exp_result(exp_res, &BType::Int, buf, params);
+}
+
+// 数组没有取到底时退化成的指针: 只剩一维的话指向的是元素, 可以做指针运算;
+// 还剩好几维的只能用来传参, 见Exp::koopa_type
+pub fn decayed_ptr(reg_count: i32, remaining_dims: usize, btype: &BType) -> ExpResult {
+    if remaining_dims == 1 {
+        return ExpResult::PtrRegCount(reg_count, BType::Pointer(Box::new(btype.clone())));
+    }
+    return ExpResult::RegCount(reg_count);
+}
@@ -0,0 +1,335 @@
+// 把几个文件各自生成的Koopa IR合成一个:
+// decl要找到别的文件里的定义, 同名的定义只能有一个, 找不到的就报错.
+// Koopa的解析器是按顺序建函数的, 调用的函数必须已经出现过, 所以函数定义要按调用关系排好.
+use std::collections::HashMap;
+
+use crate::diagnostic::Diagnostic;
+use crate::generate_ir::{FloatSigs, SwitchChains, FLOAT_INTRINSICS, SYSY_LIB_FUNCS};
+
+// 一个文件编译出来的Koopa IR
+pub struct KoopaModule {
+    pub name: String,
+    pub text: String,
+    // float在Koopa里也是i32, 参数和返回值是不是float只能看这个
+    pub float_sigs: FloatSigs,
+    // 函数名在几个文件之间是唯一的, 基本块的名字合的时候也不变, 所以直接合起来就行
+    pub switch_chains: SwitchChains,
+}
+
+#[derive(PartialEq)]
+enum ItemKind {
+    Decl,
+    Global,
+    Fun,
+}
+
+// Koopa里最外层的一项: decl一行, global一行, fun一直到单独的一行 }
+struct Item {
+    kind: ItemKind,
+    name: String,
+    sig: String, // 函数的类型, 形如 (i32, *i32): i32
+    text: String,
+    module: usize,
+}
+
+// 只有函数是几个文件共用的; 全局变量, 局部变量, 形参和字符串字面量都是每个文件自己的,
+// 编号是每个文件各自从1开始的, 合起来之前在前面加上文件的序号免得重名: @x_1 -> @__m1_x_1
+pub fn rename_private_symbols(text: &str, module: usize) -> String {
+    if module == 0 {
+        return text.to_string();
+    }
+    let funcs: Vec<String> = text
+        .lines()
+        .filter_map(|line| line.strip_prefix("decl ").or_else(|| line.strip_prefix("fun ")))
+        .map(symbol_name)
+        .collect();
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(at) = rest.find('@') {
+        out.push_str(&rest[..=at]);
+        rest = &rest[at + 1..];
+        let len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        let name = &rest[..len];
+        if !funcs.iter().any(|func| func == name) {
+            out.push_str(&format!("__m{}_", module));
+        }
+        out.push_str(name);
+        rest = &rest[len..];
+    }
+    out.push_str(rest);
+    return out;
+}
+
+// 去掉rename_private_symbols加上的文件序号
+fn strip_module_prefix(name: &str) -> &str {
+    if let Some(rest) = name.strip_prefix("__m") {
+        if let Some((idx, name)) = rest.split_once('_') {
+            if !idx.is_empty() && idx.chars().all(|c| c.is_ascii_digit()) {
+                return name;
+            }
+        }
+    }
+    return name;
+}
+
+// 运行时库的函数由sylib提供, 不用在这些文件里定义
+fn is_runtime_func(name: &str) -> bool {
+    return SYSY_LIB_FUNCS.iter().any(|(lib_func, _, _)| *lib_func == name)
+        || FLOAT_INTRINSICS.iter().any(|(intrinsic, _, _)| *intrinsic == name)
+        || name.starts_with("__putf_")
+        || name.starts_with("__ptoi_")
+        || name.starts_with("__null_");
+}
+
+// 按最外层的逗号分开, [i32, 4] 里面的逗号不算
+fn split_top_level(list: &str) -> Vec<&str> {
+    let mut parts: Vec<&str> = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in list.char_indices() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(list[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !list[start..].trim().is_empty() {
+        parts.push(list[start..].trim());
+    }
+    return parts;
+}
+
+// decl @f(i32, *i32): i32 和 fun @f(@x: i32, @a: *i32): i32 { 都变成 (i32, *i32): i32
+fn func_signature(header: &str, with_param_names: bool) -> String {
+    let Some(open) = header.find('(') else {
+        return String::new();
+    };
+    let Some(close) = header.rfind(')') else {
+        return String::new();
+    };
+    let param_types: Vec<&str> = split_top_level(&header[open + 1..close])
+        .into_iter()
+        .map(|param| match param.split_once(':') {
+            Some((_, ty)) if with_param_names => ty.trim(),
+            _ => param,
+        })
+        .collect();
+    let ret = header[close + 1..].trim_end_matches('{').trim();
+    return format!("({}){}", param_types.join(", "), ret);
+}
+
+fn symbol_name(rest: &str) -> String {
+    return rest
+        .trim_start_matches('@')
+        .split(['(', ' ', ':'])
+        .next()
+        .unwrap_or("")
+        .to_string();
+}
+
+fn parse_items(text: &str, module: usize) -> Vec<Item> {
+    let mut items: Vec<Item> = Vec::new();
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        if let Some(rest) = line.strip_prefix("decl ") {
+            items.push(Item {
+                kind: ItemKind::Decl,
+                name: symbol_name(rest),
+                sig: func_signature(rest, false),
+                text: line.to_string(),
+                module,
+            });
+        } else if let Some(rest) = line.strip_prefix("global ") {
+            items.push(Item {
+                kind: ItemKind::Global,
+                name: symbol_name(rest),
+                sig: String::new(),
+                text: line.to_string(),
+                module,
+            });
+        } else if let Some(rest) = line.strip_prefix("fun ") {
+            let mut body = vec![line];
+            for line in lines.by_ref() {
+                body.push(line);
+                if line == "}" {
+                    break;
+                }
+            }
+            items.push(Item {
+                kind: ItemKind::Fun,
+                name: symbol_name(rest),
+                sig: func_signature(rest, true),
+                text: body.join("\n"),
+                module,
+            });
+        }
+    }
+    return items;
+}
+
+// 全局变量在Koopa里叫 @x_1 或者 @__m1_x_1, 报错的时候用源文件里的名字.
+// 字符串字面量这些编译器自己起的名字原样返回, 每个文件的都不一样
+fn source_name(item: &Item) -> &str {
+    let name = strip_module_prefix(&item.name);
+    if item.kind == ItemKind::Global && !name.starts_with("__") {
+        if let Some((name, idx)) = name.rsplit_once('_') {
+            if idx.chars().all(|c| c.is_ascii_digit()) {
+                return name;
+            }
+        }
+    }
+    return &item.name;
+}
+
+fn called_funcs(text: &str) -> Vec<String> {
+    return text
+        .split("call @")
+        .skip(1)
+        .map(symbol_name)
+        .collect();
+}
+
+// 深度优先, 被调用的函数排在前面; 互相递归的函数Koopa里写不出来
+fn visit_fun(
+    idx: usize,
+    items: &[Item],
+    funcs: &HashMap<String, usize>,
+    state: &mut HashMap<usize, bool>,
+    order: &mut Vec<usize>,
+    diags: &mut Vec<Diagnostic>,
+) -> bool {
+    match state.get(&idx) {
+        Some(true) => return true,
+        Some(false) => {
+            diags.push(
+                Diagnostic::error(
+                    "E0804",
+                    &format!("mutual recursion through {} cannot be expressed in Koopa IR", items[idx].name),
+                )
+                .with_note("Koopa IR requires every function to be defined before it is called"),
+            );
+            return false;
+        }
+        None => {}
+    }
+    state.insert(idx, false);
+    let mut ok = true;
+    for callee in called_funcs(&items[idx].text) {
+        match funcs.get(&callee) {
+            Some(&callee_idx) if callee_idx != idx => {
+                ok = visit_fun(callee_idx, items, funcs, state, order, diags) && ok;
+            }
+            _ => {}
+        }
+    }
+    state.insert(idx, true);
+    order.push(idx);
+    return ok;
+}
+
+// 合并几个文件; allow_undefined的时候找不到定义的decl留着, 用在分开编译的时候.
+// 出错的话返回所有的报错
+pub fn link_modules(modules: &[KoopaModule], allow_undefined: bool) -> std::result::Result<String, Vec<Diagnostic>> {
+    let items: Vec<Item> = modules
+        .iter()
+        .enumerate()
+        .flat_map(|(i, module)| parse_items(&module.text, i))
+        .collect();
+    let mut diags: Vec<Diagnostic> = Vec::new();
+    // 先找出所有的定义
+    let mut defs: HashMap<String, usize> = HashMap::new();
+    for (idx, item) in items.iter().enumerate() {
+        if item.kind == ItemKind::Decl {
+            continue;
+        }
+        let key = source_name(item).to_string();
+        match defs.get(&key) {
+            Some(&first) if items[first].module != item.module => {
+                diags.push(
+                    Diagnostic::error("E0801", &format!("multiple definition of {} in {}", key, modules[item.module].name))
+                        .with_note(&format!("first defined in {}", modules[items[first].module].name)),
+                );
+            }
+            Some(_) => {}
+            None => {
+                defs.insert(key, idx);
+            }
+        }
+    }
+    // 每个decl要么有定义, 要么是运行时库的
+    let mut decls: Vec<usize> = Vec::new();
+    let mut decl_sigs: HashMap<String, usize> = HashMap::new();
+    for (idx, item) in items.iter().enumerate() {
+        if item.kind != ItemKind::Decl {
+            continue;
+        }
+        let other = match defs.get(&item.name) {
+            Some(&def) if items[def].kind == ItemKind::Fun => Some(def),
+            _ => decl_sigs.get(&item.name).copied(),
+        };
+        if let Some(other) = other {
+            let other_module = &modules[items[other].module];
+            let float_sig = modules[item.module].float_sigs.get(&item.name);
+            if items[other].sig != item.sig || other_module.float_sigs.get(&item.name) != float_sig {
+                diags.push(Diagnostic::error(
+                    "E0803",
+                    &format!(
+                        "conflicting types for {} (in {} and {})",
+                        item.name, modules[items[other].module].name, modules[item.module].name
+                    ),
+                ));
+            }
+            continue;
+        }
+        if !allow_undefined && !is_runtime_func(&item.name) {
+            diags.push(
+                Diagnostic::error("E0802", &format!("undefined reference to {}", item.name))
+                    .with_note(&format!("declared in {} but not defined in any input file", modules[item.module].name)),
+            );
+            continue;
+        }
+        decl_sigs.insert(item.name.clone(), idx);
+        decls.push(idx);
+    }
+    if !allow_undefined && !defs.contains_key("main") {
+        diags.push(Diagnostic::error("E0802", "undefined reference to main"));
+    }
+    let funcs: HashMap<String, usize> = defs
+        .iter()
+        .filter(|(_, idx)| items[**idx].kind == ItemKind::Fun)
+        .map(|(name, idx)| (name.clone(), *idx))
+        .collect();
+    let mut state: HashMap<usize, bool> = HashMap::new();
+    let mut order: Vec<usize> = Vec::new();
+    for (idx, item) in items.iter().enumerate() {
+        if item.kind == ItemKind::Fun && funcs.get(&item.name) == Some(&idx) {
+            visit_fun(idx, &items, &funcs, &mut state, &mut order, &mut diags);
+        }
+    }
+    if !diags.is_empty() {
+        return Err(diags);
+    }
+    let mut out = String::new();
+    for idx in decls {
+        out.push_str(&items[idx].text);
+        out.push('\n');
+    }
+    for item in &items {
+        if item.kind == ItemKind::Global {
+            out.push_str(&item.text);
+            out.push('\n');
+        }
+    }
+    for idx in order {
+        out.push('\n');
+        out.push_str(&items[idx].text);
+        out.push('\n');
+    }
+    return Ok(out);
+}
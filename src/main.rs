@@ -1,76 +1,226 @@
+// 函数的结果都写成 return x; 是这个仓库一直以来的写法, 整个crate关掉clippy的needless_return
+#![allow(clippy::needless_return)]
 // use koopa::ir::Value;
 use lalrpop_util::lalrpop_mod;
 use std::collections::HashMap;
 use std::env::args;
-use std::fs::read_to_string;
 use std::io::Result;
+use std::path::Path;
 pub mod generate_asm;
 use crate::generate_asm::GenerateAsm;
+use crate::diagnostic::{emit_diagnostics, has_errors, Diagnostic, WarningOptions};
+use crate::generate_ir::{FloatSigs, SwitchChains};
 
 pub mod ast;
+pub mod diagnostic;
+pub mod flow;
 pub mod generate_ir;
+pub mod link;
+pub mod preprocess;
+pub mod sema;
+pub mod source_map;
+pub mod syntax_error;
 // use crate::generate_ir::*;
 
 // 引用 lalrpop 生成的解析器
 // 因为我们刚刚创建了 sysy.lalrpop, 所以模块名是 sysy
-lalrpop_mod!(sysy);
+lalrpop_mod!(#[allow(clippy::all)] sysy);
 
-fn main() -> Result<()> {
-    // 解析命令行参数
-    let mut args = args();
-    args.next();
-    let mode = args.next().unwrap();
-    let input = args.next().unwrap();
-    args.next();
-    let output = args.next().unwrap();
-
-    // 读取输入文件
-    let input = read_to_string(input)?;
+// 编译一个源文件, 得到Koopa IR和后端要用的float函数签名, switch的比较链.
+// 报错和警告都在这里打出来(按-W选项过滤), 有错误的话返回None
+fn compile_to_koopa(input: &str, warnings: &WarningOptions) -> Result<Option<(String, FloatSigs, SwitchChains)>> {
+    // 读取输入文件, 先做预处理
+    let (input, line_map, mut diags) = preprocess::preprocess(Path::new(input))?;
+    let source_map = source_map::SourceMap::new(input, line_map);
+    let input = source_map.text();
 
     // 调用 lalrpop 生成的 parser 解析输入文件
-    let ast = sysy::CompUnitParser::new().parse(&input).unwrap();
+    let mut koopa_ir = String::new();
+    let mut float_sigs = FloatSigs::new();
+    let mut switch_chains = SwitchChains::new();
+    // 出了语法错误会跳过去接着解析, 把所有的语法错误都报出来; 这时候就不生成IR了
+    let mut errors = Vec::new();
+    match sysy::CompUnitParser::new().parse(&mut errors, input) {
+        Ok(ast) if errors.is_empty() => {
+            // println!("{:#?}", ast);
+
+            // 先做语义分析, 有错误的话就不生成IR了
+            let (sema_info, sema_diags) = sema::analyze(&ast);
+            let sema_failed = has_errors(&sema_diags);
+            diags.extend(sema_diags);
+            if !sema_failed {
+                // 我们把生成的Koopa IR放到缓冲区里
+                let mut buf: Vec<u8> = Vec::new();
+                let (ir_diags, chains) = ast.generate_koopa_ir(sema_info, &mut buf);
+                diags.extend(ir_diags);
+                switch_chains = chains;
+                float_sigs = ast.float_signatures();
+                koopa_ir = String::from_utf8(buf).unwrap();
+            }
+        }
+        Ok(_) => diags.extend(syntax_error::syntax_errors(errors, None, input)),
+        Err(err) => diags.extend(syntax_error::syntax_errors(errors, Some(err), input)),
+    }
 
-    // println!("{:#?}", ast);
+    let diags = warnings.apply(diags);
+    emit_diagnostics(&diags, Some(&source_map));
+    if has_errors(&diags) {
+        return Ok(None);
+    }
+    return Ok(Some((koopa_ir, float_sigs, switch_chains)));
+}
 
-    // 我们把生成的Koopa IR放到缓冲区里
-    let mut buf: Vec<u8> = Vec::new();
+// 用koopa解析成Program; 合出来的IR解析不了的话是编译器自己的问题, 也报个错
+fn parse_koopa(koopa_ir: String) -> Option<koopa::ir::Program> {
+    // RV32的指针是4个字节
+    koopa::ir::Type::set_ptr_size(4);
+    let driver = koopa::front::Driver::from(koopa_ir);
+    match driver.generate_program() {
+        Ok(program) => return Some(program),
+        Err(err) => {
+            let diag = Diagnostic::error("E0805", &format!("generated Koopa IR is invalid: {:?}", err))
+                .with_note("this is a bug in the compiler");
+            emit_diagnostics(&[diag], None);
+            return None;
+        }
+    }
+}
 
-    ast.generate_koopa_ir(&mut buf);
+// 再生成RISC-V汇编
+fn generate_riscv(koopa_ir: String, float_sigs: FloatSigs, switch_chains: SwitchChains) -> Option<String> {
+    let program = parse_koopa(koopa_ir)?;
 
-    let koopa_ir = String::from_utf8(buf).unwrap();
-    if mode == "-koopa" {
-        // 将 Koopa IR 写入输出文件
-        std::fs::write(output, koopa_ir)?;
-    } else {
-        let driver = koopa::front::Driver::from(koopa_ir.clone());
-        let program = driver.generate_program().unwrap();
+    let mut buf: Vec<u8> = Vec::new();
 
-        let mut buf: Vec<u8> = Vec::new();
+    let mut params = generate_asm::GenerateAsmParams {
+        current_register: 0,
+        stack_bytes: 0,
+        register_data: HashMap::new(),
+        register_usage: HashMap::new(),
+        stack_state: HashMap::new(),
+        save_ra: false,
+        func_names: HashMap::new(),
+        global_names: HashMap::new(),
+        float_sigs,
+        switch_chains,
+        label_count: 0,
+    };
+
+    let register_list = vec![
+        (0, false), (1, false), (2, false), (3, false),
+        (4, false), (5, false), (6, false), (7, false),
+        (8, false), (9, false), (10, false), (11, false),
+        (12, false), (13, false), (14, false),
+    ];
+
+    params.register_usage = register_list.into_iter().collect();
+
+    program.generate_asm(&mut buf, &mut params);
+
+    return Some(String::from_utf8(buf).unwrap());
+}
 
-        let mut params = generate_asm::GenerateAsmParams {
-            current_register: 0,
-            stack_bytes: 0,
-            register_data: HashMap::new(),
-            register_usage: HashMap::new(),
-            stack_state: HashMap::new(),
+fn main() -> Result<()> {
+    // 解析命令行参数: compiler -koopa|-riscv [-c] [-W...] a.c b.c ... -o output
+    // 不加-c的话链接成一个输出文件; 加了-c的话每个源文件单独输出到output目录下
+    let mut args = args();
+    args.next();
+    let mode = args.next().unwrap();
+    let mut separate = false;
+    let mut inputs: Vec<String> = Vec::new();
+    let mut output = String::new();
+    let mut warnings = WarningOptions::default();
+    let mut option_diags = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" => separate = true,
+            "-o" => output = args.next().unwrap(),
+            _ if arg.starts_with("-W") || arg == "-w" => option_diags.extend(warnings.set(&arg)),
+            _ => inputs.push(arg),
+        }
+    }
+    emit_diagnostics(&option_diags, None);
+
+    // 每个文件都编译一遍, 把所有的错误都报出来; 有错误的话什么都不输出
+    let mut modules: Vec<link::KoopaModule> = Vec::new();
+    let mut float_sigs = HashMap::new();
+    let mut switch_chains = HashMap::new();
+    let mut failed = false;
+    for (i, input) in inputs.iter().enumerate() {
+        let Some((koopa_ir, sigs, chains)) = compile_to_koopa(input, &warnings)? else {
+            failed = true;
+            continue;
         };
+        float_sigs.extend(sigs.clone());
+        switch_chains.extend(chains.clone());
+        modules.push(link::KoopaModule {
+            name: input.clone(),
+            text: link::rename_private_symbols(&koopa_ir, i),
+            float_sigs: sigs,
+            switch_chains: chains,
+        });
+    }
 
-        let register_list = vec![
-            (0, false), (1, false), (2, false), (3, false),
-            (4, false), (5, false), (6, false), (7, false),
-            (8, false), (9, false), (10, false), (11, false),
-            (12, false), (13, false), (14, false),
-        ];
-
-        params.register_usage = register_list.into_iter().collect();
-
-        program.generate_asm(&mut buf, &mut params);
+    if failed {
+        std::process::exit(1);
+    }
 
-        let asm = String::from_utf8(buf).unwrap();
-        // 将汇编写入输出文件
-        std::fs::write(output, asm)?;
+    if !separate {
+        let koopa_ir = match link::link_modules(&modules, false) {
+            Ok(koopa_ir) => koopa_ir,
+            Err(diags) => {
+                emit_diagnostics(&diags, None);
+                std::process::exit(1);
+            }
+        };
+        let text = if mode == "-koopa" {
+            // 将 Koopa IR 写入输出文件, 写之前也检查一遍
+            parse_koopa(koopa_ir.clone()).map(|_| koopa_ir)
+        } else {
+            // 将汇编写入输出文件
+            generate_riscv(koopa_ir, float_sigs, switch_chains)
+        };
+        let Some(text) = text else {
+            std::process::exit(1);
+        };
+        std::fs::write(output, text)?;
+        return Ok(());
     }
 
+    // 分开编译: 每个文件自己排好函数的顺序, 别的文件里的函数留着decl
+    let mut outputs: Vec<String> = Vec::new();
+    for module in &modules {
+        match link::link_modules(std::slice::from_ref(module), true) {
+            Ok(koopa_ir) => outputs.push(koopa_ir),
+            Err(diags) => {
+                emit_diagnostics(&diags, None);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+    let mut texts: Vec<(String, String)> = Vec::new();
+    for (module, koopa_ir) in modules.iter().zip(outputs) {
+        let stem = Path::new(&module.name).file_stem().unwrap().to_string_lossy().to_string();
+        let text = if mode == "-koopa" {
+            parse_koopa(koopa_ir.clone()).map(|_| (format!("{}.koopa", stem), koopa_ir))
+        } else {
+            generate_riscv(koopa_ir, module.float_sigs.clone(), module.switch_chains.clone()).map(|asm| (format!("{}.S", stem), asm))
+        };
+        match text {
+            Some(text) => texts.push(text),
+            None => failed = true,
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+    std::fs::create_dir_all(&output)?;
+    for (file_name, text) in texts {
+        std::fs::write(Path::new(&output).join(file_name), text)?;
+    }
 
     //println!("{}", output);
     Ok(())
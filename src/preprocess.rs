@@ -0,0 +1,790 @@
+// 预处理: 去注释, 拼接续行, 处理#include/#define/#if这些指令, 再展开宏.
+// 输出每一行都记在LineMap里, 报错的时候能找回原来的文件和行号.
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::io::Result;
+use std::path::Path;
+
+use crate::diagnostic::Diagnostic;
+use crate::source_map::Location;
+
+// 宏: params是None的是对象式宏, 否则是函数式宏
+#[derive(Debug, Clone)]
+pub struct Macro {
+    pub params: Option<Vec<String>>,
+    pub body: String,
+}
+
+// 预处理之后的第几行(从0开始)来自哪个文件的第几行(从1开始)
+#[derive(Debug, Default)]
+pub struct LineMap {
+    pub files: Vec<String>,
+    pub lines: Vec<(usize, usize)>,
+}
+
+impl LineMap {
+    pub fn lookup(&self, line: usize) -> Option<(&str, usize)> {
+        let (file, line_no) = self.lines.get(line)?;
+        return Some((&self.files[*file], *line_no));
+    }
+}
+
+// #if的一层: 这一层现在要不要输出, 前面有没有哪个分支已经选中, 有没有遇到过#else
+struct CondState {
+    active: bool,
+    parent_active: bool,
+    taken: bool,
+    seen_else: bool,
+}
+
+pub struct PreprocessParams {
+    pub macros: HashMap<String, Macro>,
+    pub line_map: LineMap,
+    pub diags: Vec<Diagnostic>,
+    cond_stack: Vec<CondState>,
+    include_depth: usize,
+    file_idx: usize,
+}
+
+// 太深了多半是头文件互相包含
+const MAX_INCLUDE_DEPTH: usize = 64;
+
+impl PreprocessParams {
+    pub fn new() -> Self {
+        let mut macros = HashMap::new();
+        // 计时函数带上调用处的行号, 和SysY运行时库的sylib.h一样
+        for name in ["starttime", "stoptime"] {
+            macros.insert(
+                name.to_string(),
+                Macro {
+                    params: Some(Vec::new()),
+                    body: format!("_sysy_{}(__LINE__)", name),
+                },
+            );
+        }
+        return PreprocessParams {
+            macros,
+            line_map: LineMap::default(),
+            diags: Vec::new(),
+            cond_stack: Vec::new(),
+            include_depth: 0,
+            file_idx: 0,
+        };
+    }
+
+    fn is_active(&self) -> bool {
+        match self.cond_stack.last() {
+            Some(cond) => return cond.active,
+            None => return true,
+        }
+    }
+
+    // 预处理的时候还没有Span, 报错直接记下文件和行号
+    fn location(&self, line_no: usize) -> Location {
+        return Location {
+            file: self.line_map.files[self.file_idx].clone(),
+            line: line_no,
+            col: 1,
+        };
+    }
+
+    fn report(&mut self, line_no: usize, diag: Diagnostic) {
+        let location = self.location(line_no);
+        self.diags.push(diag.with_location(location));
+    }
+
+    fn error(&mut self, line_no: usize, msg: &str) {
+        self.report(line_no, Diagnostic::error("E0701", msg));
+    }
+}
+
+impl Default for PreprocessParams {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+// 返回预处理之后的文本, 行号的对应关系, 以及预处理时的报错和警告
+pub fn preprocess(path: &Path) -> Result<(String, LineMap, Vec<Diagnostic>)> {
+    let mut params = PreprocessParams::new();
+    let mut out = String::new();
+    preprocess_file(path, &mut out, &mut params)?;
+    return Ok((out, params.line_map, params.diags));
+}
+
+pub fn preprocess_file(path: &Path, out: &mut String, params: &mut PreprocessParams) -> Result<()> {
+    let src = read_to_string(path)?;
+    let outer_file = params.file_idx;
+    params.file_idx = params.line_map.files.len();
+    params.line_map.files.push(path.display().to_string());
+    let cond_depth = params.cond_stack.len();
+    for (line_no, line) in logical_lines(&strip_comments(&src)) {
+        if let Some(directive) = line.trim_start().strip_prefix('#') {
+            handle_directive(directive.trim(), path, line_no, out, params)?;
+            continue;
+        }
+        if !params.is_active() {
+            continue;
+        }
+        let expanded = expand_macros(&line, &mut Vec::new(), line_no, params);
+        out.push_str(&expanded);
+        out.push('\n');
+        params.line_map.lines.push((params.file_idx, line_no));
+    }
+    // #if和#endif要在同一个文件里配对
+    if params.cond_stack.len() > cond_depth {
+        let line_no = src.lines().count();
+        params.error(line_no, "unterminated #if");
+        params.cond_stack.truncate(cond_depth);
+    }
+    params.file_idx = outer_file;
+    return Ok(());
+}
+
+fn handle_directive(
+    directive: &str,
+    path: &Path,
+    line_no: usize,
+    out: &mut String,
+    params: &mut PreprocessParams,
+) -> Result<()> {
+    let name_len = directive
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(directive.len());
+    let (name, rest) = directive.split_at(name_len);
+    let rest = rest.trim();
+    // 条件指令不管在不在输出都要处理, 不然#if和#endif配不上
+    match name {
+        "if" | "ifdef" | "ifndef" => {
+            let parent_active = params.is_active();
+            let cond = parent_active
+                && match name {
+                    "if" => eval_if(rest, line_no, params),
+                    "ifdef" => params.macros.contains_key(rest),
+                    _ => !params.macros.contains_key(rest),
+                };
+            params.cond_stack.push(CondState {
+                active: cond,
+                parent_active,
+                taken: cond,
+                seen_else: false,
+            });
+            return Ok(());
+        }
+        "elif" | "else" => {
+            let Some(cond) = params.cond_stack.last() else {
+                params.error(line_no, &format!("#{} without #if", name));
+                return Ok(());
+            };
+            let (seen_else, parent_active, taken) = (cond.seen_else, cond.parent_active, cond.taken);
+            if seen_else {
+                params.error(line_no, &format!("#{} after #else", name));
+            }
+            let enter = parent_active && !taken && (name == "else" || eval_if(rest, line_no, params));
+            let cond = params.cond_stack.last_mut().unwrap();
+            cond.active = enter;
+            cond.taken = cond.taken || enter;
+            cond.seen_else = cond.seen_else || name == "else";
+            return Ok(());
+        }
+        "endif" => {
+            if params.cond_stack.pop().is_none() {
+                params.error(line_no, "#endif without #if");
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+    if !params.is_active() {
+        return Ok(());
+    }
+    match name {
+        // 只有一个#的空指令
+        "" => {}
+        "define" => define_macro(rest, line_no, params),
+        "undef" => {
+            params.macros.remove(rest);
+        }
+        "include" => {
+            let file_name = match rest.chars().next() {
+                Some('"') => rest[1..].split('"').next(),
+                Some('<') => rest[1..].split('>').next(),
+                _ => None,
+            };
+            let Some(file_name) = file_name else {
+                params.error(line_no, "#include expects \"FILENAME\" or <FILENAME>");
+                return Ok(());
+            };
+            let include_path = path.parent().unwrap_or(Path::new(".")).join(file_name);
+            if include_path.is_file() {
+                if params.include_depth >= MAX_INCLUDE_DEPTH {
+                    params.error(line_no, "#include nested too deeply");
+                    return Ok(());
+                }
+                params.include_depth += 1;
+                preprocess_file(&include_path, out, params)?;
+                params.include_depth -= 1;
+            } else if file_name != "sylib.h" {
+                // 运行时库是编译器内置的, 找不到sylib.h也没关系
+                params.report(
+                    line_no,
+                    Diagnostic::error("E0702", &format!("{}: No such file or directory", file_name))
+                        .with_note("included files are looked up relative to the including file"),
+                );
+            }
+        }
+        "error" => params.report(line_no, Diagnostic::error("E0703", &format!("#error {}", rest))),
+        "warning" => params.report(line_no, Diagnostic::warning("W0003", &format!("#warning {}", rest))),
+        // 不认识的#pragma直接忽略
+        "pragma" => {}
+        _ => params.error(line_no, &format!("invalid preprocessing directive #{}", name)),
+    }
+    return Ok(());
+}
+
+fn is_ident_char(c: char) -> bool {
+    return c.is_ascii_alphanumeric() || c == '_';
+}
+
+fn define_macro(rest: &str, line_no: usize, params: &mut PreprocessParams) {
+    let name_len = rest.find(|c: char| !is_ident_char(c)).unwrap_or(rest.len());
+    let (name, rest) = rest.split_at(name_len);
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        params.error(line_no, "macro names must be identifiers");
+        return;
+    }
+    // 名字后面紧跟着括号的才是函数式宏
+    let mac = match rest.strip_prefix('(') {
+        Some(rest) => {
+            let Some((param_list, body)) = rest.split_once(')') else {
+                params.error(line_no, "missing ')' in macro parameter list");
+                return;
+            };
+            let macro_params: Vec<String> = param_list
+                .split(',')
+                .map(|param| param.trim().to_string())
+                .filter(|param| !param.is_empty())
+                .collect();
+            if macro_params.iter().any(|param| !param.chars().all(is_ident_char)) {
+                params.error(line_no, "expected parameter name in macro parameter list");
+                return;
+            }
+            Macro {
+                params: Some(macro_params),
+                body: body.trim().to_string(),
+            }
+        }
+        None => Macro {
+            params: None,
+            body: rest.trim().to_string(),
+        },
+    };
+    params.macros.insert(name.to_string(), mac);
+}
+
+// 去掉注释, 块注释里的换行要留下来, 不然行号就对不上了
+fn strip_comments(src: &str) -> String {
+    let chars: Vec<char> = src.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match (chars[i], chars.get(i + 1)) {
+            ('"', _) | ('\'', _) => {
+                let end = skip_literal(&chars, i);
+                out.extend(&chars[i..end]);
+                i = end;
+            }
+            ('/', Some('/')) => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            ('/', Some('*')) => {
+                i += 2;
+                out.push(' ');
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    if chars[i] == '\n' {
+                        out.push('\n');
+                    }
+                    i += 1;
+                }
+                i += 2;
+            }
+            (c, _) => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    return out;
+}
+
+// 字符串或者字符字面量的结尾(不含), 注意转义; 没有配对的引号就到行尾
+fn skip_literal(chars: &[char], start: usize) -> usize {
+    let quote = chars[start];
+    let mut i = start + 1;
+    while i < chars.len() && chars[i] != quote && chars[i] != '\n' {
+        if chars[i] == '\\' {
+            i += 1;
+        }
+        i += 1;
+    }
+    return (i + 1).min(chars.len());
+}
+
+// 反斜杠结尾的行和下一行拼起来, 行号记第一行的
+fn logical_lines(src: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    let mut continued = false;
+    for (i, line) in src.lines().enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let (line, continues) = match line.strip_suffix('\\') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        if continued {
+            lines.last_mut().unwrap().1.push_str(line);
+        } else {
+            lines.push((i + 1, line.to_string()));
+        }
+        continued = continues;
+    }
+    return lines;
+}
+
+// 展开一段文本里的宏; disabled是正在展开的宏, 不能再展开自己
+fn expand_macros(text: &str, disabled: &mut Vec<String>, line_no: usize, params: &mut PreprocessParams) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '"' || c == '\'' {
+            let end = skip_literal(&chars, i);
+            out.extend(&chars[i..end]);
+            i = end;
+            continue;
+        }
+        // 数字整个跳过, 0x1f里的x1f不是标识符
+        if c.is_ascii_digit() {
+            while i < chars.len() && (is_ident_char(chars[i]) || chars[i] == '.') {
+                out.push(chars[i]);
+                i += 1;
+            }
+            continue;
+        }
+        if !(c.is_ascii_alphabetic() || c == '_') {
+            out.push(c);
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len() && is_ident_char(chars[i]) {
+            i += 1;
+        }
+        let ident: String = chars[start..i].iter().collect();
+        if ident == "__LINE__" {
+            out.push_str(&line_no.to_string());
+            continue;
+        }
+        if ident == "__FILE__" {
+            out.push_str(&format!("{:?}", params.line_map.files[params.file_idx]));
+            continue;
+        }
+        let mac = match params.macros.get(&ident) {
+            Some(mac) if !disabled.contains(&ident) => mac.clone(),
+            _ => {
+                out.push_str(&ident);
+                continue;
+            }
+        };
+        let Some(macro_params) = &mac.params else {
+            disabled.push(ident);
+            out.push_str(&expand_macros(&mac.body, disabled, line_no, params));
+            disabled.pop();
+            continue;
+        };
+        // 函数式宏后面没有括号就只是个普通的名字
+        let mut j = i;
+        while j < chars.len() && chars[j].is_whitespace() {
+            j += 1;
+        }
+        if chars.get(j) != Some(&'(') {
+            out.push_str(&ident);
+            continue;
+        }
+        let Some((args, end)) = collect_args(&chars, j) else {
+            params.error(line_no, &format!("unterminated argument list invoking macro {}", ident));
+            out.push_str(&ident);
+            continue;
+        };
+        i = end;
+        // 没有形参的宏, 调用时的()算零个实参
+        let args = if macro_params.is_empty() && args.len() == 1 && args[0].is_empty() {
+            Vec::new()
+        } else {
+            args
+        };
+        if args.len() != macro_params.len() {
+            params.error(
+                line_no,
+                &format!(
+                    "macro {} requires {} arguments, but {} given",
+                    ident,
+                    macro_params.len(),
+                    args.len()
+                ),
+            );
+            continue;
+        }
+        let expanded_args: Vec<String> = args
+            .iter()
+            .map(|arg| expand_macros(arg, disabled, line_no, params))
+            .collect();
+        let body = substitute(&mac.body, macro_params, &args, &expanded_args);
+        disabled.push(ident);
+        out.push_str(&expand_macros(&body, disabled, line_no, params));
+        disabled.pop();
+    }
+    return out;
+}
+
+// 从左括号开始按最外层的逗号切开实参, 返回实参和右括号后面的位置
+fn collect_args(chars: &[char], open: usize) -> Option<(Vec<String>, usize)> {
+    let mut args: Vec<String> = Vec::new();
+    let mut arg = String::new();
+    let mut depth = 0;
+    let mut i = open + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '"' | '\'' => {
+                let end = skip_literal(chars, i);
+                arg.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                args.push(arg.trim().to_string());
+                return Some((args, i + 1));
+            }
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(arg.trim().to_string());
+                arg.clear();
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+        arg.push(chars[i]);
+        i += 1;
+    }
+    return None;
+}
+
+// 宏体里的一段: 普通文本, 形参, #形参, 或者##
+enum Piece {
+    Text(String),
+    Param(usize),
+    Stringify(usize),
+    Paste,
+}
+
+// 把实参代入宏体: 一般用展开过的实参, 挨着##的用原样的实参
+fn substitute(body: &str, macro_params: &[String], args: &[String], expanded_args: &[String]) -> String {
+    let chars: Vec<char> = body.chars().collect();
+    let mut pieces: Vec<Piece> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '"' || c == '\'' {
+            let end = skip_literal(&chars, i);
+            pieces.push(Piece::Text(chars[i..end].iter().collect()));
+            i = end;
+        } else if c == '#' && chars.get(i + 1) == Some(&'#') {
+            pieces.push(Piece::Paste);
+            i += 2;
+        } else if c.is_ascii_alphabetic() || c == '_' || c == '#' {
+            let start = if c == '#' { i + 1 } else { i };
+            let mut j = start;
+            while j < chars.len() && chars[j].is_whitespace() && c == '#' {
+                j += 1;
+            }
+            let ident_start = j;
+            while j < chars.len() && is_ident_char(chars[j]) {
+                j += 1;
+            }
+            let ident: String = chars[ident_start..j].iter().collect();
+            match macro_params.iter().position(|param| *param == ident) {
+                Some(idx) if c == '#' => pieces.push(Piece::Stringify(idx)),
+                Some(idx) => pieces.push(Piece::Param(idx)),
+                None => pieces.push(Piece::Text(chars[i..j.max(i + 1)].iter().collect())),
+            }
+            i = j.max(i + 1);
+        } else {
+            pieces.push(Piece::Text(c.to_string()));
+            i += 1;
+        }
+    }
+    let next_to_paste = |idx: usize| -> bool {
+        let is_blank = |piece: &Piece| matches!(piece, Piece::Text(text) if text.trim().is_empty());
+        let before = pieces[..idx].iter().rev().find(|piece| !is_blank(piece));
+        let after = pieces[idx + 1..].iter().find(|piece| !is_blank(piece));
+        return matches!(before, Some(Piece::Paste)) || matches!(after, Some(Piece::Paste));
+    };
+    let mut out = String::new();
+    let mut skip_blank = false;
+    for (idx, piece) in pieces.iter().enumerate() {
+        match piece {
+            Piece::Text(text) if skip_blank && text.trim().is_empty() => continue,
+            Piece::Text(text) => out.push_str(text),
+            Piece::Param(param) if next_to_paste(idx) => out.push_str(&args[*param]),
+            Piece::Param(param) => out.push_str(&expanded_args[*param]),
+            Piece::Stringify(param) => out.push_str(&stringify(&args[*param])),
+            Piece::Paste => {
+                out.truncate(out.trim_end().len());
+                skip_blank = true;
+                continue;
+            }
+        }
+        skip_blank = false;
+    }
+    return out;
+}
+
+// #x: 只有实参里字符串和字符字面量中的引号和反斜杠要转义
+fn stringify(arg: &str) -> String {
+    let chars: Vec<char> = arg.chars().collect();
+    let mut out = String::from("\"");
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '"' || chars[i] == '\'' {
+            let end = skip_literal(&chars, i);
+            for c in &chars[i..end] {
+                if *c == '"' || *c == '\\' {
+                    out.push('\\');
+                }
+                out.push(*c);
+            }
+            i = end;
+            continue;
+        }
+        out.push(chars[i]);
+        i += 1;
+    }
+    out.push('"');
+    return out;
+}
+
+// #if的条件: 先把defined换掉, 再展开宏, 剩下的名字都当成0
+fn eval_if(expr: &str, line_no: usize, params: &mut PreprocessParams) -> bool {
+    let mut replaced = String::new();
+    let mut rest = expr;
+    while let Some(pos) = rest.find("defined") {
+        let before_ok = !rest[..pos].ends_with(is_ident_char);
+        let after = &rest[pos + "defined".len()..];
+        if !before_ok || after.starts_with(is_ident_char) {
+            replaced.push_str(&rest[..pos + "defined".len()]);
+            rest = after;
+            continue;
+        }
+        replaced.push_str(&rest[..pos]);
+        let after = after.trim_start();
+        let (inner, parens) = match after.strip_prefix('(') {
+            Some(inner) => (inner.trim_start(), true),
+            None => (after, false),
+        };
+        let name_len = inner.find(|c: char| !is_ident_char(c)).unwrap_or(inner.len());
+        let (name, mut tail) = inner.split_at(name_len);
+        if parens {
+            match tail.trim_start().strip_prefix(')') {
+                Some(t) => tail = t,
+                None => {
+                    params.error(line_no, "missing ')' after \"defined\"");
+                    return false;
+                }
+            }
+        }
+        replaced.push_str(if params.macros.contains_key(name) { " 1 " } else { " 0 " });
+        rest = tail;
+    }
+    replaced.push_str(rest);
+    let expanded = expand_macros(&replaced, &mut Vec::new(), line_no, params);
+    let mut parser = IfExpParser {
+        tokens: tokenize_if(&expanded),
+        pos: 0,
+        error: None,
+    };
+    let value = parser.parse_cond();
+    if parser.error.is_none() && parser.pos < parser.tokens.len() {
+        parser.error = Some(format!("missing binary operator before token \"{}\"", parser.tokens[parser.pos]));
+    }
+    if let Some(msg) = parser.error {
+        params.error(line_no, &msg);
+        return false;
+    }
+    return value != 0;
+}
+
+// 切成数字, 名字和运算符
+fn tokenize_if(expr: &str) -> Vec<String> {
+    const OPS: [&str; 10] = ["<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "##", "::"];
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if is_ident_char(c) {
+            let start = i;
+            while i < chars.len() && is_ident_char(chars[i]) {
+                i += 1;
+            }
+            tokens.push(chars[start..i].iter().collect());
+        } else {
+            let pair: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            if OPS.contains(&pair.as_str()) {
+                tokens.push(pair);
+                i += 2;
+            } else {
+                tokens.push(c.to_string());
+                i += 1;
+            }
+        }
+    }
+    return tokens;
+}
+
+// #if里的整数常量表达式, 按C的优先级
+struct IfExpParser {
+    tokens: Vec<String>,
+    pos: usize,
+    error: Option<String>,
+}
+
+const IF_BINARY_OPS: [(&str, u8); 18] = [
+    ("||", 1),
+    ("&&", 2),
+    ("|", 3),
+    ("^", 4),
+    ("&", 5),
+    ("==", 6),
+    ("!=", 6),
+    ("<", 7),
+    ("<=", 7),
+    (">", 7),
+    (">=", 7),
+    ("<<", 8),
+    (">>", 8),
+    ("+", 9),
+    ("-", 9),
+    ("*", 10),
+    ("/", 10),
+    ("%", 10),
+];
+
+impl IfExpParser {
+    fn peek(&self) -> Option<&str> {
+        return self.tokens.get(self.pos).map(|token| token.as_str());
+    }
+
+    fn fail(&mut self, msg: String) -> i64 {
+        if self.error.is_none() {
+            self.error = Some(msg);
+        }
+        return 0;
+    }
+
+    fn parse_cond(&mut self) -> i64 {
+        let cond = self.parse_binary(1);
+        if self.peek() != Some("?") {
+            return cond;
+        }
+        self.pos += 1;
+        let then_val = self.parse_cond();
+        if self.peek() != Some(":") {
+            return self.fail("expected ':' in #if expression".to_string());
+        }
+        self.pos += 1;
+        let else_val = self.parse_cond();
+        return if cond != 0 { then_val } else { else_val };
+    }
+
+    fn parse_binary(&mut self, min_prec: u8) -> i64 {
+        let mut lhs = self.parse_unary();
+        loop {
+            let Some(op) = self.peek() else {
+                return lhs;
+            };
+            let Some((op, prec)) = IF_BINARY_OPS.iter().find(|(bin_op, _)| *bin_op == op).copied() else {
+                return lhs;
+            };
+            if prec < min_prec {
+                return lhs;
+            }
+            self.pos += 1;
+            let rhs = self.parse_binary(prec + 1);
+            lhs = match op {
+                "||" => (lhs != 0 || rhs != 0) as i64,
+                "&&" => (lhs != 0 && rhs != 0) as i64,
+                "|" => lhs | rhs,
+                "^" => lhs ^ rhs,
+                "&" => lhs & rhs,
+                "==" => (lhs == rhs) as i64,
+                "!=" => (lhs != rhs) as i64,
+                "<" => (lhs < rhs) as i64,
+                "<=" => (lhs <= rhs) as i64,
+                ">" => (lhs > rhs) as i64,
+                ">=" => (lhs >= rhs) as i64,
+                "<<" => lhs.wrapping_shl(rhs as u32),
+                ">>" => lhs.wrapping_shr(rhs as u32),
+                "+" => lhs.wrapping_add(rhs),
+                "-" => lhs.wrapping_sub(rhs),
+                "*" => lhs.wrapping_mul(rhs),
+                _ if rhs == 0 => self.fail("division by zero in #if".to_string()),
+                "/" => lhs.wrapping_div(rhs),
+                _ => lhs.wrapping_rem(rhs),
+            };
+        }
+    }
+
+    fn parse_unary(&mut self) -> i64 {
+        let Some(token) = self.tokens.get(self.pos).cloned() else {
+            return self.fail("#if with no expression".to_string());
+        };
+        self.pos += 1;
+        match token.as_str() {
+            "!" => return (self.parse_unary() == 0) as i64,
+            "~" => return !self.parse_unary(),
+            "-" => return self.parse_unary().wrapping_neg(),
+            "+" => return self.parse_unary(),
+            "(" => {
+                let val = self.parse_cond();
+                if self.peek() != Some(")") {
+                    return self.fail("missing ')' in expression".to_string());
+                }
+                self.pos += 1;
+                return val;
+            }
+            _ if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                let digits = token.trim_end_matches(['u', 'U', 'l', 'L']);
+                let parsed = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+                    i64::from_str_radix(hex, 16)
+                } else if digits.len() > 1 && digits.starts_with('0') {
+                    i64::from_str_radix(&digits[1..], 8)
+                } else {
+                    digits.parse::<i64>()
+                };
+                match parsed {
+                    Ok(val) => return val,
+                    Err(_) => return self.fail(format!("invalid integer \"{}\" in #if", token)),
+                }
+            }
+            // 展开完还剩下的名字当成0
+            _ if token.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') => return 0,
+            _ => return self.fail(format!("token \"{}\" is not valid in preprocessor expressions", token)),
+        }
+    }
+}
@@ -0,0 +1,1086 @@
+// 语义分析: 生成IR之前把整个AST走一遍.
+// 每个声明分到一个DeclId, 每个用到名字的地方(左值和函数调用)都解析到它指向的声明, 左值的类型也在这里算好,
+// 结果按位置记在SemaInfo里. 名字找不到或者重复声明, 左值和常量表达式的规则不对, 给常量赋值,
+// break/continue不在循环里, 都在这里报, 能指出来的话带上前面那个声明的位置;
+// 有错误的话就不生成IR了, 所以生成IR的时候名字和左值直接查SemaInfo, 不用再检查一遍.
+// 要用到表达式的类型或者常量的值的检查还留在生成IR的时候: 类型转换, 运算数, 下标和结构体的值(E0201-E0204),
+// return的值(E0403), case标号(E0404), 实参的个数和类型(E0601, E0602), 函数的类型冲突(E0603)等;
+// 这里有错误的时候那些错误还报不出来, 要等这里的错误改掉以后才有.
+// 没用过的局部变量, 走不到的代码, 没有return的函数和常量的while条件也在这里给警告
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::generate_ir::{btype_str, SYSY_LIB_FUNCS};
+
+pub type DeclId = usize;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeclKind {
+    Var,
+    Const,
+    Param,
+    Func,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeclInfo {
+    pub ident: String,
+    pub kind: DeclKind,
+    pub btype: BType, // 变量的类型, 数组是元素的类型; 函数的没有用
+    pub rank: usize,  // 数组的维数, 数组形参的第一维也算
+    pub span: Span,   // 声明的位置, 运行时库的函数是空的
+}
+
+// 左值解析的结果: 指向哪个声明, 取完下标和成员之后的类型, 还剩几维没取
+#[derive(Debug, Clone)]
+pub struct LValInfo {
+    pub decl: DeclId,
+    pub btype: BType,
+    pub rank: usize,
+}
+
+impl LValInfo {
+    // 取到底了才能赋值和取地址
+    pub fn is_element(&self) -> bool {
+        return self.rank == 0;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SemaInfo {
+    pub decls: Vec<DeclInfo>,
+    pub defs: HashMap<Span, DeclId>,     // 声明的位置 -> 声明, 位置是VarDef, ConstDef, FuncFParam, FuncDef, FuncDecl的
+    pub lvals: HashMap<Span, LValInfo>,  // 左值的位置 -> 解析结果
+    pub calls: HashMap<Span, DeclId>,    // 函数调用的位置 -> 被调用的函数
+    pub builtins: HashMap<String, DeclId>, // 运行时库的函数
+}
+
+impl SemaInfo {
+    pub fn def(&self, span: Span) -> DeclId {
+        return self.defs[&span];
+    }
+
+    pub fn l_val(&self, span: Span) -> &LValInfo {
+        return &self.lvals[&span];
+    }
+
+    pub fn call(&self, span: Span) -> DeclId {
+        return self.calls[&span];
+    }
+}
+
+pub struct SemaParams {
+    pub info: SemaInfo,
+    pub scopes: Vec<HashMap<String, DeclId>>, // 一层一层的作用域, 第一层是全局的
+    pub structs: HashMap<String, Vec<(String, BType, usize)>>, // 结构体的成员: 名字, 类型, 维数
+    pub func_defs: HashMap<DeclId, Span>, // 已经定义过的函数, 定义第二次要报错
+    pub loop_depth: i32,  // continue要在循环里
+    pub break_depth: i32, // break要在循环或者switch里
+    pub in_const: bool,   // 正在检查常量表达式: 数组维度, 常量和全局变量的初值, case的标号
+    pub used: HashSet<DeclId>, // 用到过的声明, 作用域结束的时候没用过的局部变量给警告
+    pub runtime_values: usize, // 到现在为止用到了几个运行时才知道的值, 前后一样的话中间的表达式是常量
+    pub reachable: bool,  // 当前的语句能不能走到, 走不到的代码只在最前面报一次
+    pub span: Span,       // 和生成IR的时候一样, 报错的时候指向正在检查的语句或表达式
+    pub diags: Vec<Diagnostic>,
+}
+
+impl SemaParams {
+    pub fn error(&mut self, code: &str, message: &str) {
+        let span = self.span;
+        self.error_at(span, code, message);
+    }
+    pub fn error_at(&mut self, span: Span, code: &str, message: &str) {
+        self.diags.push(Diagnostic::error(code, message).with_primary(span, ""));
+    }
+    pub fn report(&mut self, diag: Diagnostic) {
+        self.diags.push(diag);
+    }
+    pub fn with_span<T>(&mut self, span: Span, f: impl FnOnce(&mut SemaParams) -> T) -> T {
+        let outer = self.span;
+        self.span = span;
+        let res = f(self);
+        self.span = outer;
+        return res;
+    }
+    // 常量表达式里面的东西都要是常量, 包括下标
+    pub fn in_const<T>(&mut self, f: impl FnOnce(&mut SemaParams) -> T) -> T {
+        let outer = self.in_const;
+        self.in_const = true;
+        let res = f(self);
+        self.in_const = outer;
+        return res;
+    }
+    pub fn in_loop(&mut self, f: impl FnOnce(&mut SemaParams)) {
+        self.loop_depth += 1;
+        self.break_depth += 1;
+        f(self);
+        self.loop_depth -= 1;
+        self.break_depth -= 1;
+    }
+
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+    pub fn pop_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap();
+        let mut unused: Vec<DeclId> = scope.into_values().filter(|id| !self.used.contains(id)).collect();
+        unused.sort();
+        for id in unused {
+            let decl = &self.info.decls[id];
+            let diag = match decl.kind {
+                DeclKind::Var => Diagnostic::warning("W0005", &format!("unused variable {}", decl.ident)),
+                DeclKind::Const => Diagnostic::warning("W0006", &format!("unused constant {}", decl.ident)),
+                DeclKind::Param | DeclKind::Func => continue,
+            };
+            let span = decl.span;
+            self.report(diag.with_primary(span, "declared here but never used"));
+        }
+    }
+
+    // 放进最里层的作用域; 省略了名字的形参也要有DeclId, 只是不能用.
+    // 同一层里已经有这个名字的话报错, 运行时库的函数除外, 用户可以自己定义同名的
+    pub fn declare(&mut self, ident: &str, kind: DeclKind, btype: BType, rank: usize, span: Span) -> DeclId {
+        if let Some(&prev) = self.scopes.last().unwrap().get(ident) {
+            if self.info.decls[prev].span != Span::default() {
+                self.redeclared(ident, &kind, span, prev);
+            }
+        }
+        let id = self.info.decls.len();
+        self.info.decls.push(DeclInfo {
+            ident: ident.to_string(),
+            kind,
+            btype,
+            rank,
+            span,
+        });
+        self.info.defs.insert(span, id);
+        if !ident.is_empty() {
+            self.scopes.last_mut().unwrap().insert(ident.to_string(), id);
+        }
+        return id;
+    }
+
+    // 函数只在全局; 声明了好几次(或者和库函数同名)的都是同一个, 但只能定义一次
+    pub fn declare_func(&mut self, ident: &str, span: Span, is_def: bool) -> DeclId {
+        let id = match self.scopes[0].get(ident) {
+            Some(&id) if self.info.decls[id].kind == DeclKind::Func => {
+                self.info.defs.insert(span, id);
+                id
+            }
+            _ => self.declare(ident, DeclKind::Func, BType::Int, 0, span),
+        };
+        if is_def {
+            if let Some(&prev_span) = self.func_defs.get(&id) {
+                self.report(
+                    Diagnostic::error("E0102", &format!("redefinition of {}", ident))
+                        .with_primary(span, "redefined here")
+                        .with_secondary(prev_span, &format!("previous definition of {} is here", ident)),
+                );
+            }
+            self.func_defs.insert(id, span);
+        }
+        return id;
+    }
+
+    // 同一层里又声明了一次: 都不是函数的话是重复定义, 否则是把函数和变量混在一起了
+    fn redeclared(&mut self, ident: &str, kind: &DeclKind, span: Span, prev: DeclId) {
+        let prev_decl = &self.info.decls[prev];
+        let diag = if (*kind == DeclKind::Func) != (prev_decl.kind == DeclKind::Func) {
+            Diagnostic::error("E0103", &format!("{} redeclared as different kind of symbol", ident))
+        } else {
+            Diagnostic::error("E0102", &format!("redefinition of {}", ident))
+        };
+        let prev_span = prev_decl.span;
+        self.report(
+            diag.with_primary(span, "redefined here")
+                .with_secondary(prev_span, &format!("previous declaration of {} is here", ident)),
+        );
+    }
+
+    // 从里往外找
+    pub fn lookup(&self, ident: &str) -> Option<DeclId> {
+        return self.scopes.iter().rev().find_map(|scope| scope.get(ident).copied());
+    }
+
+    // 名字没找到: 看得见的名字里有差不多的(多半是拼错了)就提示一下, 指出它在哪里声明的
+    pub fn undeclared(&mut self, span: Span, message: &str, ident: &str, want_func: bool) {
+        let mut diag = Diagnostic::error("E0101", message).with_primary(span, "not found in this scope");
+        let mut best: Option<(usize, DeclId)> = None;
+        for scope in &self.scopes {
+            for (name, &id) in scope {
+                if (self.info.decls[id].kind == DeclKind::Func) != want_func {
+                    continue;
+                }
+                let distance = edit_distance(ident, name);
+                // 一样近的取先声明的, 每次报的都一样
+                if distance <= (ident.len() / 3).max(1) && best.is_none_or(|best| (distance, id) < best) {
+                    best = Some((distance, id));
+                }
+            }
+        }
+        if let Some((_, id)) = best {
+            let decl = &self.info.decls[id];
+            diag = diag.with_help(&format!("did you mean {}?", decl.ident));
+            if decl.span != Span::default() {
+                diag = diag.with_secondary(decl.span, &format!("{} declared here", decl.ident));
+            }
+        }
+        self.report(diag);
+    }
+}
+
+// 两个名字之间最少要改几个字符
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur.push((prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    return prev[b.len()];
+}
+
+pub fn analyze(comp_unit: &CompUnit) -> (SemaInfo, Vec<Diagnostic>) {
+    let mut params = SemaParams {
+        info: SemaInfo::default(),
+        scopes: vec![HashMap::new()],
+        structs: HashMap::new(),
+        func_defs: HashMap::new(),
+        loop_depth: 0,
+        break_depth: 0,
+        in_const: false,
+        used: HashSet::new(),
+        runtime_values: 0,
+        reachable: true,
+        span: Span::default(),
+        diags: Vec::new(),
+    };
+    // 运行时库的函数一开始就在全局作用域里, putf是变参的
+    let builtins = SYSY_LIB_FUNCS.iter().map(|(name, _, _)| *name).chain(["putf"]);
+    for name in builtins {
+        let id = params.declare(name, DeclKind::Func, BType::Int, 0, Span::default());
+        params.info.builtins.insert(name.to_string(), id);
+    }
+    comp_unit.analyze(&mut params);
+    return (params.info, params.diags);
+}
+
+impl CompUnit {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        for global_item in &self.global_items {
+            match global_item {
+                GlobalItem::Decl(decl) => decl.analyze_global(params),
+                GlobalItem::FuncDef(func_def) => func_def.analyze(params),
+                GlobalItem::FuncDecl(func_decl) => {
+                    params.declare_func(&func_decl.ident, func_decl.span, false);
+                    for func_f_param in &func_decl.func_f_params {
+                        func_f_param.analyze_dims(params);
+                    }
+                }
+                GlobalItem::StructDef(struct_def) => struct_def.analyze(params),
+            }
+        }
+    }
+}
+
+impl StructDef {
+    // 只记下成员的类型, 布局和别的成员错误在生成IR的时候算和报
+    pub fn analyze(&self, params: &mut SemaParams) {
+        let mut fields: Vec<(String, BType, usize)> = Vec::new();
+        for field in &self.fields {
+            params.in_const(|params| {
+                for dim in &field.dims {
+                    dim.exp.analyze(params);
+                }
+            });
+            // 指针在Koopa里没法和i32放在同一个数组里; 报了错成员还是留着, 后面用到它的地方不会再说没有这个成员
+            if let BType::Pointer(_) = field.btype {
+                params.error_at(
+                    field.span,
+                    "E0504",
+                    &format!("member {} of struct {} has type {}, pointer members are not supported", field.ident, self.ident, btype_str(&field.btype)),
+                );
+            }
+            if !fields.iter().any(|(ident, _, _)| *ident == field.ident) {
+                fields.push((field.ident.clone(), field.btype.clone(), field.dims.len()));
+            }
+        }
+        params.structs.entry(self.ident.clone()).or_insert(fields);
+    }
+}
+
+impl FuncFParam {
+    pub fn analyze_dims(&self, params: &mut SemaParams) {
+        if let Some(dims) = &self.dims {
+            params.in_const(|params| {
+                for dim in dims {
+                    dim.exp.analyze(params);
+                }
+            });
+        }
+    }
+}
+
+impl FuncDef {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        // 先声明函数自己, 函数体里可以递归调用
+        params.declare_func(&self.ident, self.span, true);
+        // 和C一样, 形参和函数体最外层的声明在同一个作用域里, 不能重名
+        params.push_scope();
+        for func_f_param in &self.func_f_params {
+            func_f_param.analyze_dims(params);
+            let rank = match &func_f_param.dims {
+                Some(dims) => dims.len() + 1,
+                None => 0,
+            };
+            params.declare(&func_f_param.ident, DeclKind::Param, func_f_param.btype.clone(), rank, func_f_param.span);
+        }
+        self.block.analyze_items(params);
+        params.pop_scope();
+        // 和C99一样main走到头返回0, 不用报
+        if self.func_type != FuncType::Void && self.ident != "main" && self.block.can_complete() {
+            let end = Span::new(self.block.span.hi - 1, self.block.span.hi);
+            self.report_missing_return(end, params);
+        }
+    }
+
+    fn report_missing_return(&self, end: Span, params: &mut SemaParams) {
+        let ret_type = if self.func_type == FuncType::Float { "float" } else { "int" };
+        params.report(
+            Diagnostic::warning("W0008", "control reaches end of non-void function")
+                .with_primary(end, "")
+                .with_secondary(self.span, &format!("{} returns {}", self.ident, ret_type))
+                .with_note("the function returns 0 here")
+                .with_help("add a return statement at the end of the function"),
+        );
+    }
+}
+
+impl Block {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        params.push_scope();
+        self.analyze_items(params);
+        params.pop_scope();
+    }
+
+    pub fn analyze_items(&self, params: &mut SemaParams) {
+        analyze_items(&self.block_items, params);
+    }
+}
+
+// 前面有一条语句走不下去的话, 后面的都走不到, 只报第一条
+pub fn analyze_items(block_items: &[BlockItem], params: &mut SemaParams) {
+    let reachable = params.reachable;
+    let mut stopped_at: Option<Span> = None;
+    for block_item in block_items {
+        if let (Some(stop_span), true) = (stopped_at, params.reachable) {
+            params.report(
+                Diagnostic::warning("W0007", "unreachable statement")
+                    .with_primary(block_item.span(), "unreachable statement")
+                    .with_secondary(stop_span, "any code following this statement is unreachable"),
+            );
+            params.reachable = false;
+        }
+        block_item.analyze(params);
+        if stopped_at.is_none() && !block_item.can_complete() {
+            stopped_at = Some(block_item.span());
+        }
+    }
+    params.reachable = reachable;
+}
+
+impl BlockItem {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            BlockItem::Decl(decl) => decl.analyze(params),
+            BlockItem::Stmt(stmt) => stmt.analyze(params),
+        }
+    }
+}
+
+impl Decl {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            Decl::ConstDecl(const_decl) => const_decl.analyze(params),
+            Decl::VarDecl(var_decl) => {
+                for var_def in &var_decl.var_defs {
+                    params.with_span(var_def.span(), |params| var_def.analyze(&var_decl.btype, params));
+                }
+            }
+        }
+    }
+
+    pub fn analyze_global(&self, params: &mut SemaParams) {
+        match self {
+            Decl::ConstDecl(const_decl) => const_decl.analyze(params),
+            Decl::VarDecl(var_decl) => {
+                for var_def in &var_decl.var_defs {
+                    params.with_span(var_def.span(), |params| var_def.analyze_global(&var_decl.btype, params));
+                }
+            }
+        }
+    }
+}
+
+impl ConstDecl {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        if let BType::Struct(_) = self.btype {
+            params.error_at(self.span, "E0504", "const structs are not supported");
+            return;
+        }
+        for const_def in &self.const_defs {
+            // 先算初值再声明, 初值里用不到自己
+            params.with_span(const_def.span, |params| {
+                params.in_const(|params| {
+                    for dim in &const_def.dims {
+                        dim.exp.analyze(params);
+                    }
+                    const_def.const_init_val.analyze(params);
+                });
+            });
+            params.declare(&const_def.ident, DeclKind::Const, self.btype.clone(), const_def.dims.len(), const_def.span);
+        }
+    }
+}
+
+impl ConstInitVal {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            ConstInitVal::ConstExp(const_exp) => const_exp.exp.analyze(params),
+            ConstInitVal::InitList(const_init_vals) => {
+                for const_init_val in const_init_vals {
+                    const_init_val.analyze(params);
+                }
+            }
+        }
+    }
+}
+
+impl VarDef {
+    fn parts(&self) -> (usize, &String, &Vec<ConstExp>, Option<&InitVal>) {
+        match self {
+            VarDef::VarDefUninit(depth, var_name, dims, _) => return (*depth, var_name, dims, None),
+            VarDef::VarDefInit(depth, var_name, dims, init_val, _) => return (*depth, var_name, dims, Some(init_val)),
+        }
+    }
+
+    // 局部变量声明完了才算初值, 和C一样初值里可以用到自己
+    pub fn analyze(&self, btype: &BType, params: &mut SemaParams) {
+        let (depth, var_name, dims, init_val) = self.parts();
+        params.in_const(|params| {
+            for dim in dims {
+                dim.exp.analyze(params);
+            }
+        });
+        params.declare(var_name, DeclKind::Var, pointer_type(btype.clone(), depth), dims.len(), self.span());
+        if let Some(init_val) = init_val {
+            init_val.analyze(params);
+        }
+    }
+
+    // 全局变量的初值要是常量, 先算初值再声明
+    pub fn analyze_global(&self, btype: &BType, params: &mut SemaParams) {
+        let (depth, var_name, dims, init_val) = self.parts();
+        params.in_const(|params| {
+            for dim in dims {
+                dim.exp.analyze(params);
+            }
+        });
+        match init_val {
+            // 用另一个结构体初始化的, 生成IR的时候报错
+            Some(init_val @ InitVal::Exp(_)) if matches!(btype, BType::Struct(_)) && depth == 0 => init_val.analyze(params),
+            Some(init_val) => params.in_const(|params| init_val.analyze(params)),
+            None => {}
+        }
+        params.declare(var_name, DeclKind::Var, pointer_type(btype.clone(), depth), dims.len(), self.span());
+    }
+}
+
+impl InitVal {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            InitVal::Exp(exp) => exp.analyze(params),
+            InitVal::InitList(init_vals) => {
+                for init_val in init_vals {
+                    init_val.analyze(params);
+                }
+            }
+        }
+    }
+}
+
+impl Stmt {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            Stmt::ClosedStmt(closed_stmt, span) => params.with_span(*span, |params| closed_stmt.analyze(params)),
+            Stmt::OpenStmt(open_stmt, span) => params.with_span(*span, |params| open_stmt.analyze(params)),
+        }
+    }
+}
+
+impl ClosedStmt {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            ClosedStmt::IfStmt(cond, then_stmt, else_stmt) => {
+                cond.analyze(params);
+                then_stmt.analyze(params);
+                else_stmt.analyze(params);
+            }
+            ClosedStmt::WhileStmt(cond, body) => {
+                analyze_while_cond(cond, params);
+                params.in_loop(|params| body.analyze(params));
+            }
+            ClosedStmt::ForStmt(for_head, body) => {
+                for_head.analyze(params, |params| body.analyze(params));
+            }
+            ClosedStmt::DoWhileStmt(body, cond) => {
+                params.in_loop(|params| body.analyze(params));
+                cond.analyze(params);
+            }
+            ClosedStmt::SwitchStmt(exp, switch_cases) => {
+                exp.analyze(params);
+                for switch_case in switch_cases {
+                    if let CaseLabel::Case(const_exp) = &switch_case.label {
+                        params.in_const(|params| const_exp.exp.analyze(params));
+                    }
+                }
+                // switch里面是同一个作用域, 只能break不能continue
+                params.push_scope();
+                params.break_depth += 1;
+                // 每个分支都可以从标号跳进来
+                for switch_case in switch_cases {
+                    analyze_items(&switch_case.block_items, params);
+                }
+                params.break_depth -= 1;
+                params.pop_scope();
+            }
+            ClosedStmt::BasicStmt(basic_stmt) => basic_stmt.analyze(params),
+        }
+    }
+}
+
+impl OpenStmt {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            OpenStmt::IfStmtNoElse(cond, then_stmt) => {
+                cond.analyze(params);
+                then_stmt.analyze(params);
+            }
+            OpenStmt::IfStmtMitElse(cond, then_stmt, else_stmt) => {
+                cond.analyze(params);
+                then_stmt.analyze(params);
+                else_stmt.analyze(params);
+            }
+            OpenStmt::WhileStmt(cond, body) => {
+                analyze_while_cond(cond, params);
+                params.in_loop(|params| body.analyze(params));
+            }
+            OpenStmt::ForStmt(for_head, body) => {
+                for_head.analyze(params, |params| body.analyze(params));
+            }
+        }
+    }
+}
+
+// while(1)是故意写的死循环; 别的常量条件多半是写错了
+fn analyze_while_cond(cond: &Exp, params: &mut SemaParams) {
+    let runtime_values = params.runtime_values;
+    cond.analyze(params);
+    // 只有while (1)算故意写的死循环, while (2)也要警告
+    if params.runtime_values != runtime_values || cond.int_literal() == Some(1) {
+        return;
+    }
+    let diag = if cond.int_literal() == Some(0) {
+        Diagnostic::warning("W0009", "while condition is always false").with_primary(cond.span, "the loop body never runs")
+    } else {
+        Diagnostic::warning("W0009", "while condition is constant")
+            .with_primary(cond.span, "")
+            .with_help("write while (1) for an intentional infinite loop")
+    };
+    params.report(diag);
+}
+
+impl ForHead {
+    // init里声明的变量只在循环里可见
+    pub fn analyze(&self, params: &mut SemaParams, body: impl FnOnce(&mut SemaParams)) {
+        params.push_scope();
+        match &self.init {
+            ForInit::Decl(decl) => decl.analyze(params),
+            ForInit::Stmt(stmt) => stmt.analyze(params),
+        }
+        if let Some(cond) = &self.cond {
+            cond.analyze(params);
+        }
+        params.in_loop(body);
+        if let Some(step) = &self.step {
+            step.analyze(params);
+        }
+        params.pop_scope();
+    }
+}
+
+impl BasicStmt {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            BasicStmt::Return(exp, _) => {
+                if let Some(exp) = exp {
+                    exp.analyze(params);
+                }
+            }
+            BasicStmt::Assgn(lhs, exp, _) | BasicStmt::CompoundAssgn(lhs, _, exp, _) => {
+                lhs.analyze(params);
+                exp.analyze(params);
+                check_lvalue(lhs, Some("assignment"), "lvalue required as left operand of assignment", params);
+            }
+            BasicStmt::Exp(exp) => {
+                if let Some(exp) = exp {
+                    exp.analyze(params);
+                }
+            }
+            BasicStmt::Block(block) => block.analyze(params),
+            BasicStmt::Break(_) => {
+                if params.break_depth == 0 {
+                    params.error("E0401", "break statement not in loop or switch");
+                }
+            }
+            BasicStmt::Continue(_) => {
+                if params.loop_depth == 0 {
+                    params.error("E0402", "continue statement not in loop");
+                }
+            }
+        }
+    }
+}
+
+// 左值: 取到底的变量, 数组元素, 成员, 或者 *p, 括号括起来的也算.
+// write是写的话是什么操作(赋值, 自增自减), 只取地址的话是None.
+// 常量不能写; 常量数组的元素在栈上有一份, 可以取地址, 但是没有const的指针, 要给警告; 单个的常量连地址都没有
+pub fn check_lvalue(unary_exp: &UnaryExp, write: Option<&str>, message: &str, params: &mut SemaParams) {
+    let target = unary_target(unary_exp, params);
+    report_lvalue(target, write, message, params);
+}
+
+// None是不能当左值, Some(None)是 *p 或者名字没找到(已经报过错了), 否则是左值指向的声明
+fn report_lvalue(target: Option<Option<DeclId>>, write: Option<&str>, message: &str, params: &mut SemaParams) {
+    let Some(target) = target else {
+        params.error("E0301", message);
+        return;
+    };
+    let Some(id) = target else {
+        return;
+    };
+    let decl = params.info.decls[id].clone();
+    if decl.kind != DeclKind::Const {
+        return;
+    }
+    match write {
+        Some(action) => {
+            let span = params.span;
+            params.report(
+                Diagnostic::error("E0302", &format!("{} of read-only variable {}", action, decl.ident))
+                    .with_primary(span, "")
+                    .with_secondary(decl.span, &format!("{} declared as const here", decl.ident)),
+            );
+        }
+        None if decl.rank == 0 => params.error("E0301", message),
+        None => discards_const(&decl, &format!("taking the address of read-only variable {}", decl.ident), params),
+    }
+}
+
+// 常量数组当实参传进去也成了普通的指针
+fn check_const_array_arg(exp: &Exp, params: &mut SemaParams) {
+    let Some(UnaryExp::PrimaryExp(primary_exp)) = exp.as_unary_exp() else {
+        return;
+    };
+    let PrimaryExp::LVal(l_val) = primary_exp.as_ref() else {
+        return;
+    };
+    let Some(info) = params.info.lvals.get(&l_val.span) else {
+        return;
+    };
+    let decl = params.info.decls[info.decl].clone();
+    if info.is_element() || decl.kind != DeclKind::Const {
+        return;
+    }
+    params.with_span(exp.span, |params| {
+        discards_const(&decl, &format!("passing read-only array {} as an argument", decl.ident), params)
+    });
+}
+
+// 指针没有const, 通过它写的话常量就被改了; 常量下标的元素还是按初值折叠, 两边会对不上
+fn discards_const(decl: &DeclInfo, action: &str, params: &mut SemaParams) {
+    let span = params.span;
+    params.report(
+        Diagnostic::warning("W0011", &format!("{} discards const", action))
+            .with_primary(span, "")
+            .with_secondary(decl.span, &format!("{} declared as const here", decl.ident))
+            .with_note("writes through the resulting pointer would modify a constant"),
+    );
+}
+
+fn unary_target(unary_exp: &UnaryExp, params: &SemaParams) -> Option<Option<DeclId>> {
+    match unary_exp {
+        UnaryExp::PrimaryExp(primary_exp) => return primary_target(primary_exp, params),
+        UnaryExp::UnaryExp(UnaryOp::Deref, _) => return Some(None),
+        _ => return None,
+    }
+}
+
+fn primary_target(primary_exp: &PrimaryExp, params: &SemaParams) -> Option<Option<DeclId>> {
+    match primary_exp {
+        PrimaryExp::LVal(l_val) => match params.info.lvals.get(&l_val.span) {
+            Some(info) if info.is_element() => return Some(Some(info.decl)),
+            Some(_) => return None,
+            None => return Some(None),
+        },
+        PrimaryExp::Exp(exp) => match exp.as_unary_exp() {
+            Some(unary_exp) => return unary_target(unary_exp, params),
+            None => return None,
+        },
+        _ => return None,
+    }
+}
+
+impl Exp {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        params.with_span(self.span, |params| self.cond_exp.analyze(params));
+    }
+}
+
+impl CondExp {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            CondExp::LOrExp(l_or_exp) => l_or_exp.analyze(params),
+            CondExp::CondExp(cond, true_exp, false_exp) => {
+                cond.analyze(params);
+                true_exp.analyze(params);
+                false_exp.analyze(params);
+            }
+        }
+    }
+}
+
+impl LOrExp {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            LOrExp::LAndExp(l_and_exp) => l_and_exp.analyze(params),
+            LOrExp::LOrExp(l_or_exp, _, l_and_exp) => {
+                l_or_exp.analyze(params);
+                l_and_exp.analyze(params);
+            }
+        }
+    }
+}
+
+impl LAndExp {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            LAndExp::BitOrExp(bit_or_exp) => bit_or_exp.analyze(params),
+            LAndExp::LAndExp(l_and_exp, _, bit_or_exp) => {
+                l_and_exp.analyze(params);
+                bit_or_exp.analyze(params);
+            }
+        }
+    }
+}
+
+impl BitOrExp {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            BitOrExp::BitXorExp(bit_xor_exp) => bit_xor_exp.analyze(params),
+            BitOrExp::BitOrExp(bit_or_exp, _, bit_xor_exp) => {
+                bit_or_exp.analyze(params);
+                bit_xor_exp.analyze(params);
+            }
+        }
+    }
+}
+
+impl BitXorExp {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            BitXorExp::BitAndExp(bit_and_exp) => bit_and_exp.analyze(params),
+            BitXorExp::BitXorExp(bit_xor_exp, _, bit_and_exp) => {
+                bit_xor_exp.analyze(params);
+                bit_and_exp.analyze(params);
+            }
+        }
+    }
+}
+
+impl BitAndExp {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            BitAndExp::EqExp(eq_exp) => eq_exp.analyze(params),
+            BitAndExp::BitAndExp(bit_and_exp, _, eq_exp) => {
+                bit_and_exp.analyze(params);
+                eq_exp.analyze(params);
+            }
+        }
+    }
+}
+
+impl EqExp {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            EqExp::RelExp(rel_exp) => rel_exp.analyze(params),
+            EqExp::EqExp(eq_exp, _, rel_exp) => {
+                eq_exp.analyze(params);
+                rel_exp.analyze(params);
+            }
+        }
+    }
+}
+
+impl RelExp {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            RelExp::ShiftExp(shift_exp) => shift_exp.analyze(params),
+            RelExp::RelExp(rel_exp, _, shift_exp) => {
+                rel_exp.analyze(params);
+                shift_exp.analyze(params);
+            }
+        }
+    }
+}
+
+impl ShiftExp {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            ShiftExp::AddExp(add_exp) => add_exp.analyze(params),
+            ShiftExp::ShiftExp(shift_exp, _, add_exp) => {
+                shift_exp.analyze(params);
+                add_exp.analyze(params);
+            }
+        }
+    }
+}
+
+impl AddExp {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            AddExp::MulExp(mul_exp) => mul_exp.analyze(params),
+            AddExp::AddExp(add_exp, _, mul_exp) => {
+                add_exp.analyze(params);
+                mul_exp.analyze(params);
+            }
+        }
+    }
+}
+
+impl MulExp {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            MulExp::UnaryExp(unary_exp) => unary_exp.analyze(params),
+            MulExp::MulExp(mul_exp, _, unary_exp) => {
+                mul_exp.analyze(params);
+                unary_exp.analyze(params);
+            }
+        }
+    }
+}
+
+impl UnaryExp {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            UnaryExp::UnaryExp(unary_op, unary_exp) => {
+                if matches!(unary_op, UnaryOp::Addr | UnaryOp::Deref) {
+                    params.runtime_values += 1;
+                    if params.in_const {
+                        params.error("E0502", "pointer operation occurred in const init val");
+                    }
+                }
+                unary_exp.analyze(params);
+                if matches!(unary_op, UnaryOp::Addr) {
+                    check_lvalue(unary_exp, None, "lvalue required as unary '&' operand", params);
+                }
+            }
+            UnaryExp::PrimaryExp(primary_exp) => primary_exp.analyze(params),
+            UnaryExp::FuncCall(ident, func_r_params, span) => {
+                params.runtime_values += 1;
+                if params.in_const {
+                    params.error("E0502", "function call occurred in const init val");
+                }
+                match params.lookup(ident) {
+                    Some(id) if params.info.decls[id].kind == DeclKind::Func => {
+                        params.info.calls.insert(*span, id);
+                    }
+                    Some(id) => {
+                        let decl_span = params.info.decls[id].span;
+                        params.report(
+                            Diagnostic::error("E0103", &format!("{} is not a function", ident))
+                                .with_primary(*span, "")
+                                .with_secondary(decl_span, &format!("{} declared here", ident)),
+                        );
+                    }
+                    None => {
+                        let message = format!("implicit declaration of function {}", ident);
+                        params.undeclared(*span, &message, ident, true);
+                    }
+                }
+                for exp in func_r_params {
+                    exp.analyze(params);
+                    check_const_array_arg(exp, params);
+                }
+            }
+            UnaryExp::PreIncDec(inc_dec_op, unary_exp) => {
+                params.runtime_values += 1;
+                if params.in_const {
+                    params.error("E0502", "increment or decrement occurred in const init val");
+                }
+                unary_exp.analyze(params);
+                let message = "lvalue required as increment or decrement operand";
+                check_lvalue(unary_exp, Some(inc_dec_action(inc_dec_op)), message, params);
+            }
+            UnaryExp::PostIncDec(primary_exp, inc_dec_op) => {
+                params.runtime_values += 1;
+                if params.in_const {
+                    params.error("E0502", "increment or decrement occurred in const init val");
+                }
+                primary_exp.analyze(params);
+                let target = primary_target(primary_exp, params);
+                let message = "lvalue required as increment or decrement operand";
+                report_lvalue(target, Some(inc_dec_action(inc_dec_op)), message, params);
+            }
+        }
+    }
+}
+
+fn inc_dec_action(inc_dec_op: &IncDecOp) -> &'static str {
+    match inc_dec_op {
+        IncDecOp::Inc => return "increment",
+        IncDecOp::Dec => return "decrement",
+    }
+}
+
+impl PrimaryExp {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        match self {
+            PrimaryExp::Exp(exp) => exp.analyze(params),
+            PrimaryExp::LVal(l_val) => l_val.analyze(params),
+            PrimaryExp::Number(_) => {}
+            PrimaryExp::StringLit(_) => {
+                params.runtime_values += 1;
+                if params.in_const {
+                    params.error("E0502", "string literal occurred in const init val");
+                }
+            }
+        }
+    }
+}
+
+impl LVal {
+    pub fn analyze(&self, params: &mut SemaParams) {
+        for exp in self.exps.iter().chain(self.members.iter().flat_map(|member| &member.exps)) {
+            exp.analyze(params);
+        }
+        let Some(info) = self.resolve(params) else {
+            params.runtime_values += 1;
+            return;
+        };
+        // 常量表达式里只能用取到底的常量
+        let decl = &params.info.decls[info.decl];
+        if decl.kind != DeclKind::Const || !info.is_element() {
+            params.runtime_values += 1;
+            if params.in_const {
+                params.error_at(self.span, "E0502", "variables occurred in const init val");
+            }
+        }
+        params.info.lvals.insert(self.span, info);
+    }
+
+    // 找到声明, 再按下标和成员算出类型; 出错就返回None
+    fn resolve(&self, params: &mut SemaParams) -> Option<LValInfo> {
+        let Some(id) = params.lookup(&self.ident) else {
+            params.undeclared(self.span, &format!("{} undeclared", self.ident), &self.ident, false);
+            return None;
+        };
+        params.used.insert(id);
+        let decl = params.info.decls[id].clone();
+        if decl.kind == DeclKind::Func {
+            let mut diag = Diagnostic::error("E0103", &format!("{} is a function", self.ident)).with_primary(self.span, "");
+            if decl.span != Span::default() {
+                diag = diag.with_secondary(decl.span, &format!("{} declared here", self.ident));
+            }
+            params.report(diag);
+            return None;
+        }
+        // 数组每取一层下标少一维; 不是数组的指针变量每取一层下标去掉一层指针
+        let mut btype = decl.btype.clone();
+        let mut rank = decl.rank;
+        if decl.rank > 0 && self.exps.len() > decl.rank {
+            params.error_at(self.span, "E0204", &format!("too many subscripts for array {}", self.ident));
+            return None;
+        }
+        for _ in &self.exps {
+            if rank > 0 {
+                rank -= 1;
+                continue;
+            }
+            let BType::Pointer(base) = btype else {
+                params.error_at(
+                    self.span,
+                    "E0204",
+                    &format!("subscripted value {} is neither array nor pointer", self.ident),
+                );
+                return None;
+            };
+            btype = *base;
+        }
+        if !self.members.is_empty() && rank > 0 {
+            params.error_at(self.span, "E0205", &format!("request for member in {}, which is an array", self.ident));
+            return None;
+        }
+        for member in &self.members {
+            let (field_btype, field_rank) = member_field(&btype, rank, member, params)?;
+            // 不支持指针成员, 定义的地方已经报过错了, 用到它的地方不再报
+            if let BType::Pointer(_) = field_btype {
+                return None;
+            }
+            if member.exps.len() > field_rank {
+                params.error_at(member.span, "E0204", &format!("subscripted member {} is not an array", member.ident));
+                return None;
+            }
+            btype = field_btype;
+            rank = field_rank - member.exps.len();
+        }
+        return Some(LValInfo { decl: id, btype, rank });
+    }
+}
+
+// .要求左边是结构体, ->要求左边是指向结构体的指针
+fn member_field(btype: &BType, rank: usize, member: &Member, params: &mut SemaParams) -> Option<(BType, usize)> {
+    let struct_type = match (member.arrow, btype) {
+        (true, BType::Pointer(base)) if rank == 0 => base.as_ref(),
+        (false, btype) => btype,
+        _ => {
+            params.error_at(
+                member.span,
+                "E0205",
+                &format!("invalid type argument of '->' (have {})", btype_str(btype)),
+            );
+            return None;
+        }
+    };
+    let (BType::Struct(ident), 0) = (struct_type, rank) else {
+        params.error_at(member.span, "E0205", &format!("request for member {} in something not a structure", member.ident));
+        return None;
+    };
+    let Some(fields) = params.structs.get(ident) else {
+        params.error_at(member.span, "E0205", &format!("dereferencing incomplete type struct {}", ident));
+        return None;
+    };
+    match fields.iter().find(|(field_ident, _, _)| *field_ident == member.ident) {
+        Some((_, field_btype, field_rank)) => return Some((field_btype.clone(), *field_rank)),
+        None => {
+            let message = format!("struct {} has no member named {}", ident, member.ident);
+            params.error_at(member.span, "E0205", &message);
+            return None;
+        }
+    }
+}
@@ -0,0 +1,91 @@
+// 把Span里的字节偏移换成文件名, 行号和列号.
+// 偏移是预处理之后的文本里的, 行号再通过LineMap换回原来的文件;
+// 列号按预处理之后的那一行算, 这一行前面展开过宏的话会有偏差
+use crate::ast::Span;
+use crate::preprocess::LineMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub file: String,
+    pub line: usize, // 从1开始
+    pub col: usize,  // 从1开始, 按字符数
+}
+
+pub struct SourceMap {
+    text: String,
+    line_starts: Vec<usize>, // 预处理之后每一行开头的偏移
+    line_map: LineMap,
+}
+
+impl SourceMap {
+    pub fn new(text: String, line_map: LineMap) -> Self {
+        let mut line_starts = vec![0];
+        for (i, c) in text.bytes().enumerate() {
+            if c == b'\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        return SourceMap {
+            text,
+            line_starts,
+            line_map,
+        };
+    }
+
+    pub fn text(&self) -> &str {
+        return &self.text;
+    }
+
+    // 偏移在预处理之后的第几行, 从0开始
+    fn line_index(&self, offset: usize) -> usize {
+        let offset = offset.min(self.text.len());
+        match self.line_starts.binary_search(&offset) {
+            Ok(idx) => return idx,
+            Err(idx) => return idx - 1,
+        }
+    }
+
+    pub fn lookup(&self, offset: usize) -> Location {
+        let offset = offset.min(self.text.len());
+        let idx = self.line_index(offset);
+        let col = self.text[self.line_starts[idx]..offset].chars().count() + 1;
+        // 文件末尾多出来的空行没有记在LineMap里, 算到最后一行
+        let mapped = self
+            .line_map
+            .lookup(idx)
+            .or_else(|| self.line_map.lookup(self.line_map.lines.len().wrapping_sub(1)));
+        match mapped {
+            Some((file, line)) => {
+                return Location {
+                    file: file.to_string(),
+                    line,
+                    col,
+                }
+            }
+            None => {
+                return Location {
+                    file: String::new(),
+                    line: idx + 1,
+                    col,
+                }
+            }
+        }
+    }
+
+    // 偏移所在的那一整行, 不带换行
+    pub fn line_text(&self, offset: usize) -> &str {
+        let idx = self.line_index(offset);
+        let start = self.line_starts[idx];
+        let end = match self.line_starts.get(idx + 1) {
+            Some(next) => next - 1,
+            None => self.text.len(),
+        };
+        return &self.text[start..end];
+    }
+
+    // 这一段对应的源代码
+    pub fn snippet(&self, span: Span) -> &str {
+        let hi = span.hi.min(self.text.len());
+        return &self.text[span.lo.min(hi)..hi];
+    }
+}
@@ -0,0 +1,150 @@
+// 语法错误: lalrpop给的是期望的记号在文法里的写法, 这里换成看得懂的说法,
+// 比如 expected `;` after expression. 缺分号的时候指向前一个记号的后面, 和rustc一样
+use lalrpop_util::{ErrorRecovery, ParseError};
+
+use crate::ast::Span;
+use crate::diagnostic::Diagnostic;
+
+// 能接在表达式后面的记号, 期望里有这些说明前面是一个完整的表达式
+const EXP_CONTINUATIONS: [&str; 32] = [
+    "`+`", "`-`", "`*`", "`/`", "`%`", "`<`", "`>`", "`<=`", "`>=`", "`==`", "`!=`", "`&&`", "`||`",
+    "`&`", "`|`", "`^`", "`<<`", "`>>`", "`?`", "`++`", "`--`", "`=`", "`+=`", "`-=`", "`*=`",
+    "`/=`", "`%=`", "`[`", "`(`", "`.`", "`->`", "`:`",
+];
+
+// 最外层只能是这些开头的
+const TOP_LEVEL_STARTERS: [&str; 5] = ["`const`", "`float`", "`int`", "`struct`", "`void`"];
+
+fn token_name(token: &str) -> String {
+    if token.starts_with("r#\"[_a-zA-Z]") {
+        return "identifier".to_string();
+    } else if token.starts_with("r#\"'") {
+        return "character constant".to_string();
+    } else if token.starts_with("r#\"\\\"") {
+        return "string literal".to_string();
+    } else if token.starts_with("r#") {
+        return "number".to_string();
+    }
+    return format!("`{}`", token.trim_matches('"'));
+}
+
+fn expected_names(expected: &[String]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for token in expected {
+        let name = token_name(token);
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    return names;
+}
+
+// `a`, `b` or `c`
+fn join_or(names: &[String]) -> String {
+    match names.split_last() {
+        Some((last, [])) => return last.clone(),
+        Some((last, rest)) => return format!("{} or {}", rest.join(", "), last),
+        None => return String::new(),
+    }
+}
+
+// 前一个记号结束的地方
+fn prev_token_end(input: &str, offset: usize) -> usize {
+    return input[..offset].trim_end().len();
+}
+
+// found是出错的记号, None是到了文件末尾
+fn unexpected(found: Option<(usize, usize)>, expected: &[String], input: &str) -> Diagnostic {
+    let names = expected_names(expected);
+    let (found_str, found_span) = match found {
+        Some((l, r)) => (format!("`{}`", &input[l..r]), Span::new(l, r)),
+        None => ("end of file".to_string(), Span::new(input.len(), input.len())),
+    };
+    let after_exp = names.iter().any(|name| name == "`+`");
+    // 表达式后面能接的运算符太多了, 只说要的是哪个右括号或者分号
+    let names: Vec<String> = if after_exp {
+        names
+            .into_iter()
+            .filter(|name| !EXP_CONTINUATIONS.contains(&name.as_str()))
+            .collect()
+    } else {
+        names
+    };
+    if names.iter().any(|name| name == "`;`") {
+        let message = if after_exp {
+            format!("expected `;` after expression, found {}", found_str)
+        } else {
+            format!("expected `;`, found {}", found_str)
+        };
+        let prev_end = prev_token_end(input, found_span.lo);
+        let mut diag = Diagnostic::error("E0001", &message).with_primary(Span::new(prev_end, prev_end), "expected `;`");
+        if found.is_some() {
+            diag = diag.with_secondary(found_span, "unexpected token");
+        }
+        return diag;
+    }
+    if !names.is_empty() && names.len() <= 3 {
+        return Diagnostic::error("E0001", &format!("expected {}, found {}", join_or(&names), found_str))
+            .with_primary(found_span, "");
+    }
+    if names.iter().any(|name| name == "identifier") && names.iter().any(|name| name == "number") {
+        return Diagnostic::error("E0001", &format!("expected expression, found {}", found_str))
+            .with_primary(found_span, "expected expression");
+    }
+    return Diagnostic::error("E0001", &format!("unexpected {}", found_str))
+        .with_primary(found_span, "")
+        .with_help(&format!("expected one of {}", names.join(", ")));
+}
+
+pub fn syntax_error<T, E: std::fmt::Display>(err: ParseError<usize, T, E>, input: &str) -> Diagnostic {
+    match err {
+        ParseError::InvalidToken { location } => {
+            let len = input[location..].chars().next().map_or(0, |c| c.len_utf8());
+            return Diagnostic::error("E0001", "invalid token").with_primary(Span::new(location, location + len), "");
+        }
+        ParseError::UnrecognizedEof { expected, .. } => return unexpected(None, &expected, input),
+        ParseError::UnrecognizedToken { token: (l, _, r), expected } => return unexpected(Some((l, r)), &expected, input),
+        ParseError::ExtraToken { token: (l, _, r) } => {
+            return Diagnostic::error("E0001", &format!("extra token `{}`", &input[l..r])).with_primary(Span::new(l, r), "");
+        }
+        ParseError::User { error } => return Diagnostic::error("E0001", &error.to_string()),
+    }
+}
+
+// 这个错误是不是在最外层, 也就是只能接一个新的声明或者函数
+fn at_top_level<T, E>(err: &ParseError<usize, T, E>) -> bool {
+    match err {
+        ParseError::UnrecognizedToken { expected, .. } | ParseError::UnrecognizedEof { expected, .. } => {
+            return expected_names(expected)
+                .iter()
+                .all(|name| TOP_LEVEL_STARTERS.contains(&name.as_str()));
+        }
+        _ => return false,
+    }
+}
+
+// 恢复过的错误, 加上最后没能恢复的那个(如果有的话); 同一个地方只报一次.
+// 跳过记号的时候不管花括号配没配对, 前面出过错之后在最外层又出的错多半是跟着错的, 不报
+pub fn syntax_errors<T, E: std::fmt::Display>(
+    recovered: Vec<ErrorRecovery<usize, T, E>>,
+    last: Option<ParseError<usize, T, E>>,
+    input: &str,
+) -> Vec<Diagnostic> {
+    let mut diags: Vec<Diagnostic> = Vec::new();
+    let errors = recovered.into_iter().map(|recovery| recovery.error).chain(last);
+    for err in errors {
+        if !diags.is_empty() && at_top_level(&err) {
+            continue;
+        }
+        let diag = syntax_error(err, input);
+        let span = diag.primary.as_ref().map(|label| label.span);
+        if diags
+            .iter()
+            .any(|prev| prev.primary.as_ref().map(|label| label.span) == span)
+        {
+            continue;
+        }
+        diags.push(diag);
+    }
+    return diags;
+}
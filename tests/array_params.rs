@@ -0,0 +1,39 @@
+mod common;
+use common::*;
+
+const ROWS: &str = "int g[2][3] = {{1, 2, 3}, {4, 5, 6}};
+int sum(int a[], int n) {
+    int i = 0, s = 0;
+    while (i < n) { s = s + a[i]; i = i + 1; }
+    return s;
+}
+int row(int m[][3], int r) { return sum(m[r], 3); }
+int pass(int a[], int n) { return sum(a, n); }
+int main() {
+    int m[2][3] = {{1, 2, 3}, {4, 5, 6}};
+    return row(m, 1) + sum(m[0], 2) + pass(g[1], 3) + row(g, 0);
+}
+";
+
+#[test]
+fn decay_and_partial_indexing() {
+    let output = compile("decay_and_partial_indexing", ROWS);
+    assert_compiles(&output);
+    let koopa = &normalize(&output.text);
+    assert!(koopa.contains("fun @sum(@a_0: *i32, @n_0: i32): i32"), "{}", koopa);
+    assert!(koopa.contains("fun @row(@m_0: *[i32, 3], @r_0: i32): i32"), "{}", koopa);
+    // 形参是指针, 下标用getptr; 数组实参退化成指向第一个元素的指针
+    assert!(koopa.contains("load @r_1\n  %_ = getptr %_, %_\n  %_ = getelemptr %_, 0\n  %_ = call @sum(%_, 3)"), "{}", koopa);
+    assert!(koopa.contains("%_ = getelemptr @m_2, 0\n  %_ = call @row(%_, 1)"), "{}", koopa);
+    assert!(koopa.contains("%_ = getelemptr @g_1, 1\n  %_ = getelemptr %_, 0\n  %_ = call @pass(%_, 3)"), "{}", koopa);
+    // 指针形参原样传下去
+    assert!(koopa.contains("%_ = load @a_2\n  %_ = load @n_2\n  %_ = call @sum(%_, %_)"), "{}", koopa);
+}
+
+#[test]
+fn pointer_values_on_the_stack() {
+    let output = compile_riscv("pointer_values_on_the_stack", ROWS);
+    assert!(output.success, "{}", output.stderr);
+    assert!(output.text.contains("la t0, g_1"), "{}", output.text);
+    assert!(output.text.contains("  .globl row\nrow:"), "{}", output.text);
+}
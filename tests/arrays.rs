@@ -0,0 +1,113 @@
+mod common;
+use common::*;
+
+// 数组按行展开以后每个元素的初值, 从局部数组逐个store的那些指令里读出来
+fn stored_values(koopa: &str, array: &str) -> Vec<String> {
+    koopa
+        .lines()
+        .skip_while(|line| !line.trim().starts_with(&format!("{} = alloc", array)))
+        .skip(1)
+        .take_while(|line| !line.contains("= alloc"))
+        .filter_map(|line| line.trim().strip_prefix("store "))
+        .map(|rest| rest.split(',').next().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn brace_elision_and_zero_fill() {
+    let src = "int main() {
+    int a[2][3] = {{1}, 4, 5};
+    int b[4] = {};
+    int c[2][2] = {1, 2, {3}};
+    return a[1][1] + b[3] + c[1][0];
+}
+";
+    let output = compile("brace_elision_and_zero_fill", src);
+    assert_compiles(&output);
+    assert_eq!(stored_values(&output.text, "@a_1"), ["1", "0", "0", "4", "5", "0"]);
+    assert_eq!(stored_values(&output.text, "@b_1"), ["0", "0", "0", "0"]);
+    assert_eq!(stored_values(&output.text, "@c_1"), ["1", "2", "3", "0"]);
+}
+
+#[test]
+fn global_and_const_arrays() {
+    let src = "const int D[3] = {1, 2, 3};
+int g[2][3] = {{1}, 4, 5};
+int z[2][2];
+int main() { return D[2] + g[1][1] + z[1][1]; }
+";
+    let output = compile("global_and_const_arrays", src);
+    assert_compiles(&output);
+    assert!(output.text.contains("global @D_1 = alloc [i32, 3], {1, 2, 3}"), "{}", output.text);
+    assert!(output.text.contains("global @g_1 = alloc [[i32, 3], 2], {{1, 0, 0}, {4, 5, 0}}"), "{}", output.text);
+    assert!(output.text.contains("global @z_1 = alloc [[i32, 2], 2], zeroinit"), "{}", output.text);
+    // 常量数组的常数下标在编译期就取出来了
+    assert!(output.text.contains("%3 = add 3, %2"), "{}", output.text);
+    assert!(output.text.contains("%0 = getelemptr @g_1, 1\n  %1 = getelemptr %0, 1"), "{}", output.text);
+}
+
+// 下标不是常数的时候要按每一维的大小乘出偏移
+#[test]
+fn runtime_index_stride() {
+    let src = "int main() { int a[2][3]; int i = 1; a[i][2] = 7; return a[i][2]; }\n";
+    let output = compile_riscv("runtime_index_stride", src);
+    assert!(output.success, "{}", output.stderr);
+    assert!(output.text.contains("li t2, 12\n  mul t1, t1, t2"), "{}", output.text);
+    assert!(output.text.contains("li t1, 8\n  add t0, t0, t1"), "{}", output.text);
+}
+
+#[test]
+fn bad_initializers_and_sizes() {
+    let output = compile("excess_elements", "int main() { int a[2] = {1, 2, 3}; return a[1]; }\n");
+    // 多出来的元素丢掉, 只是警告
+    assert!(output.success, "{}", output.stderr);
+    assert!(output.has("warning[W0004]: excess elements in array initializer"), "{}", output.stderr);
+    assert_eq!(output.text.matches("store").count(), 2, "{}", output.text);
+
+    let output = compile("variable_size", "int main() { int n = 3; int a[n]; return 0; }\n");
+    assert_fails_with(&output, "error[E0502]: variables occurred in const init val");
+}
+
+// 标量外面多套的花括号只是警告, 里面第一个值就是这个标量的初值
+#[test]
+fn braces_around_scalars() {
+    let output = compile("scalar_in_braces", "int x = {{1}};\nint main() { const int c = {{3}}; return x + c; }\n");
+    assert!(output.success, "{}", output.stderr);
+    assert_eq!(
+        output.headers(),
+        [
+            "warning[W0010]: braces around scalar initializer [-Wbraced-scalar-init]",
+            "warning[W0010]: braces around scalar initializer [-Wbraced-scalar-init]",
+        ]
+    );
+    assert!(output.text.contains("global @x_1 = alloc i32, 1"), "{}", output.text);
+    assert!(output.text.contains("add %0, 3"), "{}", output.text);
+
+    let output = compile("array_element_in_braces", "int g[2] = {{{1}, {2}}};\nint main() {\n  int l[2] = {{{4}}, 5};\n  return g[0] + l[1];\n}\n");
+    assert!(output.success, "{}", output.stderr);
+    assert_eq!(
+        output.headers(),
+        [
+            "warning[W0010]: braces around scalar initializer [-Wbraced-scalar-init]",
+            "warning[W0004]: excess elements in scalar initializer [-Wexcess-initializers]",
+            "warning[W0010]: braces around scalar initializer [-Wbraced-scalar-init]",
+        ]
+    );
+    assert!(koopa_is_valid(&output.text), "{}", output.text);
+    assert!(output.text.contains("global @g_1 = alloc [i32, 2], {1, 0}"), "{}", output.text);
+    assert_eq!(stored_values(&output.text, "@l_1"), ["4", "5"]);
+}
+
+#[test]
+fn non_positive_sizes() {
+    for (name, src, message, at) in [
+        ("negative_size", "int a[-1];\nint main() { return 0; }\n", "error[E0204]: size of array is negative", " --> a.c:1:7\n"),
+        ("zero_size", "int main() {\n  int a[0];\n  return a[0];\n}\n", "error[E0204]: size of array is zero", " --> a.c:2:9\n"),
+        ("zero_param_dim", "int f(int a[][0]) { return 0; }\nint main() { return 0; }\n", "error[E0204]: size of array is zero", " --> a.c:1:15\n"),
+        ("zero_member", "struct S { int v[1 - 1]; };\nint main() { return 0; }\n", "error[E0204]: size of array is zero", " --> a.c:1:18\n"),
+    ] {
+        let output = compile(name, src);
+        assert_fails_with(&output, message);
+        assert!(output.stderr.contains(at), "{}: {}", name, output.stderr);
+    }
+}
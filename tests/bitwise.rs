@@ -0,0 +1,58 @@
+mod common;
+use common::*;
+
+#[test]
+fn operators_lower_to_koopa() {
+    let src = "int main() {\n  int x = 4660, y = -16;\n  int h = (x << 3) ^ (x >> 2);\n  int t = ~x | 1;\n  return h & y & t;\n}\n";
+    let output = compile("operators_lower_to_koopa", src);
+    assert_compiles(&output);
+    let koopa = normalize(&output.text);
+    // >>是有符号数的算术右移, ~x就是异或-1
+    for line in ["shl %_, 3", "sar %_, 2", "xor %_, %_", "xor %_, -1", "or %_, 1", "and %_, %_"] {
+        assert!(koopa.contains(&format!("%_ = {}\n", line)), "missing {:?} in\n{}", line, koopa);
+    }
+    let output = compile_riscv("operators_lower_to_riscv", src);
+    assert!(output.success, "{}", output.stderr);
+    for inst in ["sll t1, t0, t1", "sra t1, t0, t1", "xor t1, t0, t1"] {
+        assert!(output.text.contains(inst), "missing {:?} in\n{}", inst, output.text);
+    }
+}
+
+// 和C一样: 移位低于加减, 高于比较; & ^ | 都低于==
+#[test]
+fn c_precedence() {
+    let src = "int main() {\n  int a = 1, b = 2;\n  int p = a | b == 2;\n  int q = a << b + 1;\n  int r = a & 3 ^ 2 | 8;\n  return p + q + r;\n}\n";
+    let output = compile("c_precedence", src);
+    assert_compiles(&output);
+    let koopa = normalize(&output.text);
+    assert!(koopa.contains("%_ = eq %_, 2\n  %_ = or %_, %_\n"), "{}", koopa);
+    assert!(koopa.contains("%_ = add %_, 1\n  %_ = shl %_, %_\n"), "{}", koopa);
+    assert!(koopa.contains("%_ = and %_, 3\n  %_ = xor %_, 2\n  %_ = or %_, 8\n"), "{}", koopa);
+}
+
+#[test]
+fn constants_fold() {
+    let src = "const int M = (1 << 4) - 1;\nconst int C = ~0 ^ 5 & 3 | 8 >> 1;\nconst int S = -16 >> 2;\nint g[M & 6];\nint main() {\n  return C + S;\n}\n";
+    let output = compile("constants_fold", src);
+    assert_compiles(&output);
+    assert!(output.text.contains("global @g_1 = alloc [i32, 6], zeroinit"), "{}", output.text);
+    // C = -2, S = -4
+    assert!(output.text.contains("ret -6"), "{}", output.text);
+}
+
+// 逻辑与或还是短路的, 不会和按位的混在一起
+#[test]
+fn logical_ops_still_short_circuit() {
+    let src = "int main() {\n  int x = 2, y = 0;\n  if (x & 1 && y / 0) return 1;\n  return 0;\n}\n";
+    let output = compile("logical_ops_still_short_circuit", src);
+    assert_compiles(&output);
+    let koopa = normalize(&output.text);
+    assert!(koopa.contains("%_ = and %_, 1\n  %_ = ne %_, 0\n  br %_, %short_circuit1, %end1\n%short_circuit1:\n"), "{}", koopa);
+}
+
+#[test]
+fn variables_in_const_shift() {
+    let src = "int main() {\n  int n = 2;\n  const int c = 1 << n;\n  return c;\n}\n";
+    let output = compile("variables_in_const_shift", src);
+    assert_fails_with(&output, "error[E0502]: variables occurred in const init val");
+}
@@ -0,0 +1,106 @@
+// 端到端的测试: 把SysY代码写到临时目录里, 调用编译器, 看退出码, stderr上的报错和输出文件
+#![allow(dead_code)]
+use std::path::PathBuf;
+use std::process::Command;
+
+pub struct Output {
+    pub success: bool,
+    pub stderr: String,
+    pub text: String, // 输出文件的内容, 编译失败的话是空的
+}
+
+impl Output {
+    // 报错和警告的第一行, 形如 error[E0101]: x undeclared
+    pub fn headers(&self) -> Vec<&str> {
+        self.stderr
+            .lines()
+            .filter(|line| line.starts_with("error") || line.starts_with("warning"))
+            .collect()
+    }
+
+    pub fn has(&self, header: &str) -> bool {
+        self.headers().iter().any(|line| line.starts_with(header))
+    }
+}
+
+// 每个测试用自己的目录, 并行跑不会互相覆盖
+fn test_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// files是(文件名, 内容), 后缀是.c的都当成输入; args加在模式后面
+pub fn compile_files(name: &str, mode: &str, files: &[(&str, &str)], args: &[&str]) -> Output {
+    let dir = test_dir(name);
+    let mut inputs: Vec<String> = Vec::new();
+    for (file_name, text) in files {
+        std::fs::write(dir.join(file_name), text).unwrap();
+        if file_name.ends_with(".c") {
+            inputs.push(file_name.to_string());
+        }
+    }
+    let output_name = if mode == "-koopa" { "out.koopa" } else { "out.S" };
+    let res = Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .current_dir(&dir)
+        .arg(mode)
+        .args(args)
+        .args(&inputs)
+        .arg("-o")
+        .arg(output_name)
+        .output()
+        .unwrap();
+    Output {
+        success: res.status.success(),
+        stderr: String::from_utf8_lossy(&res.stderr).to_string(),
+        text: std::fs::read_to_string(dir.join(output_name)).unwrap_or_default(),
+    }
+}
+
+pub fn compile(name: &str, src: &str) -> Output {
+    compile_files(name, "-koopa", &[("a.c", src)], &[])
+}
+
+pub fn compile_with(name: &str, src: &str, args: &[&str]) -> Output {
+    compile_files(name, "-koopa", &[("a.c", src)], args)
+}
+
+pub fn compile_riscv(name: &str, src: &str) -> Output {
+    compile_files(name, "-riscv", &[("a.c", src)], &[])
+}
+
+// Koopa的解析器能读进去才算对
+pub fn koopa_is_valid(text: &str) -> bool {
+    koopa::ir::Type::set_ptr_size(4);
+    koopa::front::Driver::from(text.to_string()).generate_program().is_ok()
+}
+
+// 临时变量的编号跟着前面生成了多少条指令变, 比较的时候都换成%_
+pub fn normalize(koopa: &str) -> String {
+    let mut res = String::new();
+    let mut chars = koopa.chars().peekable();
+    while let Some(c) = chars.next() {
+        res.push(c);
+        if c == '%' && chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+            while chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+                chars.next();
+            }
+            res.push('_');
+        }
+    }
+    res
+}
+
+// 编译成功, 没有警告, 输出的IR能被解析
+pub fn assert_compiles(output: &Output) {
+    assert!(output.success, "compile failed:\n{}", output.stderr);
+    assert!(output.headers().is_empty(), "unexpected diagnostics:\n{}", output.stderr);
+    assert!(koopa_is_valid(&output.text), "invalid Koopa IR:\n{}", output.text);
+}
+
+// 编译失败并且报了这个错
+pub fn assert_fails_with(output: &Output, header: &str) {
+    assert!(!output.success, "expected failure, got:\n{}", output.text);
+    assert!(output.has(header), "expected {}, got:\n{}", header, output.stderr);
+}
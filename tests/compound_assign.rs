@@ -0,0 +1,60 @@
+mod common;
+use common::*;
+
+// 复合赋值先load旧值, 右边算完才store
+#[test]
+fn compound_assignment_lowering() {
+    let src = "int main() {\n  int a = 5;\n  int arr[2];\n  a += 3;\n  a -= 1;\n  a *= 2;\n  a /= 3;\n  a %= 3;\n  arr[1] += a;\n  return a;\n}\n";
+    let output = compile("compound_assignment_lowering", src);
+    assert_compiles(&output);
+    let koopa = normalize(&output.text);
+    for op in ["add %_, 3", "sub %_, 1", "mul %_, 2", "div %_, 3", "mod %_, 3"] {
+        let expected = format!("%_ = load @a_1\n  %_ = {}\n  store %_, @a_1\n", op);
+        assert!(koopa.contains(&expected), "missing {:?} in\n{}", expected, koopa);
+    }
+    // 数组元素的地址只算一次
+    assert!(
+        koopa.contains("%_ = getelemptr @arr_1, 1\n  %_ = load %_\n  %_ = load @a_1\n  %_ = add %_, %_\n  store %_, %_\n"),
+        "{}",
+        koopa
+    );
+}
+
+#[test]
+fn prefix_and_postfix_values() {
+    let src = "int main() {\n  int a = 1, b;\n  b = a++ + ++a;\n  return b;\n}\n";
+    let output = compile("prefix_and_postfix_values", src);
+    assert_compiles(&output);
+    // a++取旧值%0, ++a取新值%3
+    assert!(output.text.contains("  %0 = load @a_1\n  %1 = add %0, 1\n  store %1, @a_1\n"), "{}", output.text);
+    assert!(output.text.contains("  %2 = load @a_1\n  %3 = add %2, 1\n  store %3, @a_1\n  %4 = add %0, %3\n"), "{}", output.text);
+}
+
+#[test]
+fn side_effects_in_indices_and_loops() {
+    let src = "int main() {\n  int a = 1, b = 4;\n  int arr[3] = {1, 2, 3};\n  arr[--b - 2]--;\n  for (int i = 0; i < 3; i += 1) a *= 2;\n  return a * 10 + arr[1] + b;\n}\n";
+    let output = compile("side_effects_in_indices_and_loops", src);
+    assert_compiles(&output);
+    // for的step里也能写复合赋值
+    assert!(normalize(&output.text).contains("%while_step1:\n  %_ = load @i_1\n  %_ = add %_, 1\n  store %_, @i_1\n"), "{}", output.text);
+    let output = compile_riscv("side_effects_in_indices_and_loops_riscv", src);
+    assert!(output.success, "{}", output.stderr);
+}
+
+#[test]
+fn rejected_in_constant_contexts() {
+    for (name, src) in [
+        ("const_init", "int main() {\n  int c = 1;\n  const int d = c++;\n  return d;\n}\n"),
+        ("array_size", "int main() {\n  int c = 1;\n  int e[--c];\n  return 0;\n}\n"),
+        ("global_init", "int c;\nint g = c--;\nint main() {\n  return 0;\n}\n"),
+    ] {
+        let output = compile(name, src);
+        assert!(!output.success, "{}", name);
+        assert!(
+            output.has("error[E0502]: increment or decrement occurred in const init val"),
+            "{}: {}",
+            name,
+            output.stderr
+        );
+    }
+}
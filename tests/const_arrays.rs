@@ -0,0 +1,48 @@
+mod common;
+use common::*;
+
+const SRC: &str = "const int t[3] = {1, 2, 3};
+const int m[2][2] = {{4}, {5, 6}};
+int a[t[2]];
+int main() {
+    const int local[2] = {7, t[1] * 10};
+    int i = 1;
+    int b[m[1][1]];
+    return t[1] + m[1][0] + local[1] + local[i] + t[i] + b[5];
+}
+";
+
+#[test]
+fn constant_indices_fold() {
+    let output = compile("constant_indices_fold", SRC);
+    assert_compiles(&output);
+    let koopa = &output.text;
+    // 常量数组里的元素可以当数组的长度, 也可以出现在别的常量的初值里
+    assert!(koopa.contains("global @a_1 = alloc [i32, 3], zeroinit"), "{}", koopa);
+    assert!(koopa.contains("@b_1 = alloc [i32, 6]"), "{}", koopa);
+    assert!(koopa.contains("store 20, %2"), "{}", koopa);
+    // t[1], m[1][0], local[1] 都直接换成了值, 再一起折叠成27
+    assert!(koopa.contains("%6 = add 27, %5\n"), "{}", koopa);
+}
+
+// 下标不是常数的时候还是要去内存里读, 所以常量数组也要分配空间
+#[test]
+fn runtime_indices_load() {
+    let output = compile("runtime_indices_load", SRC);
+    assert_compiles(&output);
+    let koopa = normalize(&output.text);
+    assert!(koopa.contains("global @t_1 = alloc [i32, 3], {1, 2, 3}"), "{}", koopa);
+    assert!(koopa.contains("global @m_1 = alloc [[i32, 2], 2], {{4, 0}, {5, 6}}"), "{}", koopa);
+    assert!(koopa.contains("%_ = load @i_1\n  %_ = getelemptr @local_1, %_\n  %_ = load %_"), "{}", koopa);
+    assert!(koopa.contains("%_ = load @i_1\n  %_ = getelemptr @t_1, %_\n  %_ = load %_"), "{}", koopa);
+}
+
+#[test]
+fn bad_constant_indices() {
+    let src = "const int t[2] = {1, 2};\nint main() { const int x = t[2]; return x; }\n";
+    let output = compile("out_of_bounds", src);
+    assert_fails_with(&output, "error[E0506]: index 2 out of bounds for t");
+    let src = "const int t[2] = {1, 2};\nint main() { int n = 1; const int y = t[n]; return y; }\n";
+    let output = compile("runtime_index_in_const", src);
+    assert_fails_with(&output, "error[E0502]: variables occurred in const init val");
+}
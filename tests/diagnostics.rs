@@ -0,0 +1,73 @@
+mod common;
+use common::*;
+
+#[test]
+fn error_with_snippet() {
+    let output = compile("error_with_snippet", "int main() {\n\tint a = 1;\n\tbreak;\n\treturn a;\n}\n");
+    assert_fails_with(&output, "error[E0401]: break statement not in loop or switch");
+    let expected = "error[E0401]: break statement not in loop or switch
+ --> a.c:3:2
+  |
+3 | \tbreak;
+  | \t^^^^^^
+";
+    assert!(output.stderr.starts_with(expected), "{}", output.stderr);
+    // 有错误就不写输出文件
+    assert!(output.text.is_empty(), "{}", output.text);
+}
+
+#[test]
+fn argument_count_span() {
+    let output = compile("argument_count_span", "int f(int a) { return a; }\nint main() {\n    return f(1, 2);\n}\n");
+    assert_fails_with(&output, "error[E0601]: f takes 1 arguments but 2 were supplied");
+    assert!(output.stderr.contains(" --> a.c:3:12\n"), "{}", output.stderr);
+    assert!(output.stderr.contains("  |            ^^^^^^^\n"), "{}", output.stderr);
+}
+
+#[test]
+fn help_after_snippet() {
+    let output = compile("help_after_snippet", "struct P { int x; };\nint f(struct P p) { return 0; }\nint main() { return 0; }\n");
+    let expected = "error[E0604]: struct parameter p must be passed by pointer
+ --> a.c:2:7
+  |
+2 | int f(struct P p) { return 0; }
+  |       ^^^^^^^^^^
+  |
+  = help: declare the parameter as a pointer to the struct and pass its address
+";
+    assert!(output.stderr.starts_with(expected), "{}", output.stderr);
+    assert!(!output.success);
+}
+
+// 只有警告的话照常输出, 退出码是0
+#[test]
+fn warnings_do_not_fail() {
+    let output = compile("warnings_do_not_fail", "int main() {\n  int a[2] = {1, 2, 3};\n  return a[0];\n}\n");
+    assert!(output.success, "{}", output.stderr);
+    assert_eq!(output.headers(), ["warning[W0004]: excess elements in array initializer [-Wexcess-initializers]"]);
+    assert!(koopa_is_valid(&output.text), "{}", output.text);
+}
+
+#[test]
+fn warning_with_secondary_label() {
+    let output = compile("warning_with_secondary_label", "int main() {\n\tint a = 1;\n\treturn a;\n\ta = 2;\n}\n");
+    assert!(output.success, "{}", output.stderr);
+    assert!(koopa_is_valid(&output.text), "{}", output.text);
+    let expected = "warning[W0007]: unreachable statement [-Wunreachable-code]
+ --> a.c:4:2
+  |
+3 | \treturn a;
+  | \t--------- any code following this statement is unreachable
+4 | \ta = 2;
+  | \t^^^^^^ unreachable statement
+";
+    assert_eq!(output.stderr.trim_end(), expected.trim_end());
+}
+
+// 跨行的位置只标第一行, 标到行尾
+#[test]
+fn multi_line_span() {
+    let output = compile("multi_line_span", "int main() {\n  return f(1,\n    2);\n}\n");
+    assert_fails_with(&output, "error[E0101]: implicit declaration of function f");
+    assert!(output.stderr.contains("2 |   return f(1,\n  |          ^^^^ not found in this scope\n"), "{}", output.stderr);
+}
@@ -0,0 +1,64 @@
+mod common;
+use common::*;
+
+const AREA: &str = "const float PI = 3.14159;
+float area(float r) { return PI * r * r; }
+int main() {
+    float a = area(2);
+    int n = a;
+    if (a > 12.5) n = n + 1;
+    putfloat(a);
+    return n;
+}
+";
+
+#[test]
+fn float_arith() {
+    let output = compile("float_arith", AREA);
+    assert_compiles(&output);
+    // 常量折叠成float的位模式, 实参2先转成float
+    assert!(output.text.contains("call @__fmul(1078530000, %0)"), "{}", output.text);
+    assert!(output.text.contains("call @area(1073741824)"), "{}", output.text);
+    assert!(output.text.contains("call @__ftoi("), "{}", output.text);
+    assert!(output.text.contains("call @__fgt(%7, 1095237632)"), "{}", output.text);
+    let output = compile_riscv("float_arith_riscv", AREA);
+    assert!(output.success, "{}", output.stderr);
+    for inst in ["fmul.s", "flt.s", "fcvt.w.s"] {
+        assert!(output.text.contains(inst), "missing {}:\n{}", inst, output.text);
+    }
+    assert!(!output.text.contains("call __fmul"), "{}", output.text);
+}
+
+// 混合int和float的运算, int那边先转; 0x1.8p1是十六进制的浮点数
+#[test]
+fn implicit_conversions() {
+    let src = "const float H = 0x1.8p1;\nfloat g[2] = {1.5, 2};\nfloat mix(int a, float b) { return a + b * a; }\nint main() {\n  float arr[2] = {1};\n  arr[1] = mix(1, 2) + H;\n  if (!arr[0]) return 1;\n  return arr[1] + g[0];\n}\n";
+    let output = compile("implicit_conversions", src);
+    assert_compiles(&output);
+    let koopa = normalize(&output.text);
+    assert!(koopa.contains("global @g_1 = alloc [i32, 2], {1069547520, 1073741824}"), "{}", koopa);
+    assert!(koopa.contains("%_ = call @__itof(%_)\n  %_ = call @__fmul(%_, %_)\n  %_ = call @__itof(%_)\n  %_ = call @__fadd(%_, %_)\n"), "{}", koopa);
+    assert!(koopa.contains("%_ = call @mix(1, 1073741824)\n  %_ = call @__fadd(%_, 1077936128)\n"), "{}", koopa);
+    assert!(koopa.contains("store 1065353216, %_"), "{}", koopa);
+    assert!(koopa.contains("%_ = call @__feq(%_, 0)\n"), "{}", koopa);
+}
+
+// 参数按ilp32f放: float用fa寄存器, int用a寄存器, 各算各的
+#[test]
+fn float_calling_convention() {
+    let src = "float mix(int a, float b, int c, float d) { return a + b * c - d; }\nint main() {\n  return mix(1, 2.0, 3, 2.5);\n}\n";
+    let output = compile_riscv("float_calling_convention", src);
+    assert!(output.success, "{}", output.stderr);
+    let asm = &output.text;
+    for inst in ["mv t0, a0", "fmv.x.w t0, fa0", "fmv.x.w t0, fa1", "li a0, 1", "fmv.w.x fa0, t0", "li a1, 3", "fmv.w.x fa1, t0"] {
+        assert!(asm.contains(inst), "missing {}:\n{}", inst, asm);
+    }
+}
+
+#[test]
+fn invalid_float_operands() {
+    let output = compile("float_mod", "int main() { float x = 1.5; int y = x % 2; return y; }\n");
+    assert_fails_with(&output, "error[E0202]: invalid operands of type float to mod");
+    let output = compile("float_subscript", "int main() { float x = 1.5; int a[3]; a[x] = 1; return 0; }\n");
+    assert_fails_with(&output, "error[E0204]: array subscript is not an integer");
+}
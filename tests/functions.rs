@@ -0,0 +1,46 @@
+mod common;
+use common::*;
+
+const CALLS: &str = "int fib(int n) {
+    if (n < 2) return n;
+    return fib(n - 1) + fib(n - 2);
+}
+int sum10(int a, int b, int c, int d, int e, int f, int g, int h, int i, int j) {
+    return a + b + c + d + e + f + g + h + i + j;
+}
+int main() {
+    return fib(10) + sum10(1, 2, 3, 4, 5, 6, 7, 8, 9, 10);
+}
+";
+
+#[test]
+fn recursion_and_parameters() {
+    let output = compile("recursion_and_parameters", CALLS);
+    assert_compiles(&output);
+    assert!(output.text.contains("fun @sum10(@a_0: i32, @b_0: i32, @c_0: i32"), "{}", output.text);
+    assert!(output.text.contains("call @fib(%"), "{}", output.text);
+    assert!(output.text.contains("call @sum10(1, 2, 3, 4, 5, 6, 7, 8, 9, 10)"), "{}", output.text);
+}
+
+// 前8个参数放a0-a7, 剩下的从调用者的栈底往上放
+#[test]
+fn stack_arguments() {
+    let output = compile_riscv("stack_arguments", CALLS);
+    assert!(output.success, "{}", output.stderr);
+    let asm = &output.text;
+    let call = asm.find("call sum10").unwrap();
+    let setup = &asm[..call];
+    for expected in ["li a7, 8", "li t0, 9\n  sw t0, 0(sp)", "li t0, 10\n  sw t0, 4(sp)"] {
+        assert!(setup.contains(expected), "missing {:?}:\n{}", expected, asm);
+    }
+    assert!(asm.contains("sw ra,"), "{}", asm);
+    assert!(asm.contains("  .globl fib\nfib:"), "{}", asm);
+}
+
+#[test]
+fn call_to_unknown_function() {
+    let output = compile("call_to_unknown_function", "int f(int a) { return a; }\nint main() { return g(1); }\n");
+    assert_fails_with(&output, "error[E0101]: implicit declaration of function g");
+    let output = compile("call_to_variable", "int g;\nint main() { return g(1); }\n");
+    assert_fails_with(&output, "error[E0103]: g is not a function");
+}
@@ -0,0 +1,54 @@
+mod common;
+use common::*;
+
+const COUNTER: &str = "const int N = 10, M = N * 2;
+int counter;
+int scale = M + 1;
+int bump(int x) { counter = counter + x; return counter; }
+int main() {
+    bump(N);
+    bump(scale);
+    return counter;
+}
+";
+
+#[test]
+fn global_initializers() {
+    let output = compile("global_initializers", COUNTER);
+    assert_compiles(&output);
+    // 常量不占空间, 用到的地方直接换成值
+    assert!(output.text.contains("global @counter_1 = alloc i32, zeroinit"), "{}", output.text);
+    assert!(output.text.contains("global @scale_1 = alloc i32, 21"), "{}", output.text);
+    assert!(!output.text.contains("@N"), "{}", output.text);
+    assert!(output.text.contains("call @bump(10)"), "{}", output.text);
+}
+
+#[test]
+fn data_and_bss_sections() {
+    let output = compile_riscv("data_and_bss_sections", COUNTER);
+    assert!(output.success, "{}", output.stderr);
+    let asm = &output.text;
+    assert!(asm.contains("  .bss\n  .globl counter_1\ncounter_1:\n  .zero 4"), "{}", asm);
+    assert!(asm.contains("  .data\n  .globl scale_1\nscale_1:\n  .word 21"), "{}", asm);
+    assert!(asm.contains("la t0, counter_1"), "{}", asm);
+}
+
+#[test]
+fn local_shadows_global() {
+    let output = compile("local_shadows_global", "int a = 1;\nint main() { int a = 2; return a; }\n");
+    assert_compiles(&output);
+    assert!(output.text.contains("store 2, @a_2\n  %0 = load @a_2"), "{}", output.text);
+}
+
+// 全局变量的初值和常量的初值一样, 只能用常量
+#[test]
+fn non_constant_initializers() {
+    for (name, src) in [
+        ("non_constant_const", "int g = 1;\nconst int C = g;\nint main() { return C; }\n"),
+        ("non_constant_global", "int a = 1;\nint b = a + 1;\nint main() { return b; }\n"),
+    ] {
+        let output = compile(name, src);
+        assert!(!output.success, "{}", name);
+        assert_eq!(output.headers(), ["error[E0502]: variables occurred in const init val"], "{}", name);
+    }
+}
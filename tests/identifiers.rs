@@ -0,0 +1,99 @@
+mod common;
+use common::*;
+
+#[test]
+fn shadowing_is_not_redeclaration() {
+    let src = "int n = 1;\nint f(int n) { return n; }\nint main() {\n    int f1 = f(2);\n    { int n = 3; f1 = f1 + n; }\n    return f1 + n;\n}\n";
+    let output = compile("shadowing_is_not_redeclaration", src);
+    assert_compiles(&output);
+}
+
+const MISUSED: &str = "const int N = 3;
+int f(int a) { return a; }
+int main() {
+    int count = 0;
+    int count = 1;
+    N = 4;
+    cout = 2;
+    int f = 1;
+    return f(count);
+}
+";
+
+#[test]
+fn undeclared_redeclared_and_read_only() {
+    let output = compile("undeclared_redeclared_and_read_only", MISUSED);
+    assert_fails_with(&output, "error[E0102]: redefinition of count");
+    assert!(output.stderr.contains("previous declaration of count is here"), "{}", output.stderr);
+    assert!(output.has("error[E0302]: assignment of read-only variable N"), "{}", output.stderr);
+    assert!(output.stderr.contains("N declared as const here"), "{}", output.stderr);
+    assert!(output.has("error[E0101]: cout undeclared"), "{}", output.stderr);
+    assert!(output.stderr.contains("= help: did you mean count?"), "{}", output.stderr);
+    assert!(output.has("error[E0103]: f is not a function"), "{}", output.stderr);
+}
+
+// 报错的时候顺便指出之前的声明在哪
+#[test]
+fn previous_declaration_label() {
+    let output = compile("previous_declaration_label", "int main() {\n    int count = 0;\n    int count = 1;\n    return count;\n}\n");
+    let expected = "error[E0102]: redefinition of count
+ --> a.c:3:9
+  |
+2 |     int count = 0;
+  |         --------- previous declaration of count is here
+3 |     int count = 1;
+  |         ^^^^^^^^^ redefined here
+";
+    assert!(output.stderr.starts_with(expected), "{}", output.stderr);
+}
+
+#[test]
+fn redefinitions() {
+    for (name, src, header) in [
+        ("global_twice", "int g;\nint g;\nint main() { return g; }\n", "error[E0102]: redefinition of g"),
+        ("function_twice", "int f() { return 0; }\nint f() { return 1; }\nint main() { return f(); }\n", "error[E0102]: redefinition of f"),
+        ("param_twice", "int f(int a, int a) { return a; }\nint main() { return f(1, 2); }\n", "error[E0102]: redefinition of a"),
+        ("const_and_var", "int main() {\n    const int k = 1;\n    int k = 2;\n    return k;\n}\n", "error[E0102]: redefinition of k"),
+    ] {
+        let output = compile(name, src);
+        assert_fails_with(&output, header);
+    }
+}
+
+// 数组元素和复合赋值, 自增自减也不能改常量
+#[test]
+fn read_only_writes() {
+    for (name, body, header) in [
+        ("const_scalar", "c = 2;", "error[E0302]: assignment of read-only variable c"),
+        ("const_element", "t[1] = 2;", "error[E0302]: assignment of read-only variable t"),
+        ("compound", "c += 1;", "error[E0302]: assignment of read-only variable c"),
+        ("increment", "t[0]++;", "error[E0302]: increment of read-only variable t"),
+    ] {
+        let src = format!("int main() {{\n    const int c = 1;\n    const int t[2] = {{1, 2}};\n    {}\n    return c;\n}}\n", body);
+        let output = compile(name, &src);
+        assert_fails_with(&output, header);
+    }
+}
+
+// 没有const的指针, 常量数组的地址拿出去只给警告
+#[test]
+fn read_only_addresses() {
+    let src = "const int t[3] = {1, 2, 3};\nint f(int a[]) { return a[0]; }\nint main() {\n    int i = 1;\n    int *p = &t[i];\n    return *p + f(t) + f(&t[0]);\n}\n";
+    let output = compile("read_only_addresses", src);
+    assert!(output.success, "{}", output.stderr);
+    assert_eq!(
+        output.headers(),
+        [
+            "warning[W0011]: taking the address of read-only variable t discards const [-Wdiscarded-qualifiers]",
+            "warning[W0011]: passing read-only array t as an argument discards const [-Wdiscarded-qualifiers]",
+            "warning[W0011]: taking the address of read-only variable t discards const [-Wdiscarded-qualifiers]",
+        ]
+    );
+    assert!(output.stderr.contains("1 | const int t[3] = {1, 2, 3};\n  |           ---------------- t declared as const here\n"), "{}", output.stderr);
+    assert!(output.stderr.contains(" --> a.c:6:19\n"), "{}", output.stderr);
+    // 元素的值和普通变量的地址不受影响
+    let output = compile("plain_addresses", "int t[2];\nint main() {\n    const int c = 1;\n    int *p = &t[c];\n    return *p + t[c];\n}\n");
+    assert_compiles(&output);
+    let output = compile_with("read_only_addresses_as_error", src, &["-Werror=discarded-qualifiers"]);
+    assert_fails_with(&output, "error[W0011]");
+}
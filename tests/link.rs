@@ -0,0 +1,105 @@
+mod common;
+use common::*;
+
+const MAIN_C: &str = "int total = 5;\nint get();\nint add(int, int);\nint main() {\n  putint(total + get());\n  return add(1, 2);\n}\n";
+const UTIL_C: &str = "int get() { int t = 3; return t; }\nint add(int a, int b) { return a + b; }\n";
+
+// decl换成另一个文件里的定义, 被调用的函数排在前面
+#[test]
+fn functions_across_files() {
+    let files = [("a.c", MAIN_C), ("b.c", UTIL_C)];
+    let output = compile_files("functions_across_files", "-koopa", &files, &[]);
+    assert_compiles(&output);
+    let decls: Vec<&str> = output.text.lines().filter(|line| line.starts_with("decl")).collect();
+    assert_eq!(decls, ["decl @putint(i32)"]);
+    let get = output.text.find("fun @get()").unwrap();
+    assert!(get < output.text.find("fun @main()").unwrap(), "{}", output.text);
+    assert!(output.text.contains("call @add(1, 2)"), "{}", output.text);
+    let output = compile_files("functions_across_files_riscv", "-riscv", &files, &[]);
+    assert!(output.success, "{}", output.stderr);
+    assert_eq!(output.text.matches("\nmain:\n").count(), 1, "{}", output.text);
+}
+
+// 加了-c每个文件单独输出, 别的文件里的函数留着decl
+#[test]
+fn separate_outputs() {
+    let files = [("a.c", MAIN_C), ("b.c", UTIL_C)];
+    let output = compile_files("separate_outputs", "-koopa", &files, &["-c"]);
+    assert!(output.success, "{}", output.stderr);
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("separate_outputs/out.koopa");
+    let a = std::fs::read_to_string(dir.join("a.koopa")).unwrap();
+    let b = std::fs::read_to_string(dir.join("b.koopa")).unwrap();
+    assert!(koopa_is_valid(&a) && koopa_is_valid(&b), "{}\n{}", a, b);
+    assert!(a.contains("decl @get(): i32\ndecl @add(i32, i32): i32\n"), "{}", a);
+    assert!(!b.contains("decl") && !b.contains("@main"), "{}", b);
+}
+
+#[test]
+fn symbol_errors() {
+    let conflicting = "int total = 2;\nint add(int a, float b) { return a; }\n";
+    let output = compile_files("symbol_errors", "-koopa", &[("a.c", MAIN_C), ("c.c", conflicting)], &[]);
+    assert!(!output.success);
+    assert_eq!(
+        output.headers(),
+        [
+            "error[E0801]: multiple definition of total in c.c",
+            "error[E0802]: undefined reference to get",
+            "error[E0803]: conflicting types for add (in c.c and a.c)",
+        ]
+    );
+    // 另一个位置放在note里
+    assert!(output.stderr.contains("  = note: first defined in a.c\n"), "{}", output.stderr);
+    assert!(output.stderr.contains("  = note: declared in a.c but not defined in any input file\n"), "{}", output.stderr);
+    let output = compile("no_main", "int f();\nint g() { return f(); }\n");
+    assert_fails_with(&output, "error[E0802]: undefined reference to main");
+}
+
+// 两个文件互相调用的话没法把定义排在调用前面
+#[test]
+fn mutual_recursion_across_files() {
+    let files = [("a.c", "int g();\nint f() { return g(); }\n"), ("b.c", "int f();\nint g() { return f(); }\nint main() { return 0; }\n")];
+    let output = compile_files("mutual_recursion_across_files", "-koopa", &files, &[]);
+    assert_fails_with(&output, "error[E0804]: mutual recursion through f cannot be expressed in Koopa IR");
+}
+
+// 一个文件的全局变量和另一个文件的局部变量同名, Koopa里的名字不能撞上
+#[test]
+fn same_name_in_two_files() {
+    let files = [
+        ("a.c", "int tmp = 5;\nint get();\nint main() { putint(tmp + get()); return 0; }\n"),
+        ("b.c", "int get() { int tmp = 3; return tmp; }\n"),
+    ];
+    let output = compile_files("same_name_koopa", "-koopa", &files, &[]);
+    assert_compiles(&output);
+    assert!(output.text.contains("global @tmp_1"));
+    assert!(output.text.contains("@__m1_tmp_1 = alloc i32"));
+    let output = compile_files("same_name_riscv", "-riscv", &files, &[]);
+    assert!(output.success, "{}", output.stderr);
+}
+
+// 走不下去的语句后面不生成没有入口的基本块, 读回Koopa的时候不会有unreachable的警告
+#[test]
+fn no_unreachable_blocks() {
+    let src = "int f(int x) { if (x) return 1; else return 2; }
+int g() { for (;;) { return 1; } }
+int h(int x) { switch (x) { case 1: return 1; default: return 2; } }
+int k(int x) { while (1) { x = x + 1; if (x > 5) return x; } }
+int d(int x) { do { x = x - 1; if (x) continue; return 3; } while (x > 0); return 4; }
+int e(int x) { do { return 5; } while (x); }
+int main() { return f(1) + g() + h(0) + k(0) + d(2) + e(0); }
+";
+    let files = [("a.c", src), ("b.c", "int one() { while (1) { return 1; } }\n")];
+    let output = compile_files("no_unreachable_blocks", "-koopa", &files, &[]);
+    assert_compiles(&output);
+    let koopa = &output.text;
+    for label in ["%end1:", "%while_step1:", "%while_end1:", "%switch_end1:", "%while_end2:", "%while_entry4:", "%while_end4:"] {
+        assert!(!koopa.contains(label), "unexpected {} in\n{}", label, koopa);
+    }
+    // while(1)直接进循环体
+    assert!(koopa.contains("%while_entry2:\n  jump %while_body2\n"), "{}", koopa);
+    // continue还能走到条件判断, 判断是假的时候出得来
+    assert!(koopa.contains("%while_entry3:\n"), "{}", koopa);
+    assert!(koopa.contains("%while_end3:\n  ret 4\n"), "{}", koopa);
+    let output = compile_files("no_unreachable_blocks_riscv", "-riscv", &files, &[]);
+    assert!(output.success && output.stderr.is_empty(), "{}", output.stderr);
+}
@@ -0,0 +1,61 @@
+mod common;
+use common::*;
+
+const LOOPS: &str = "int main() {
+    int s = 0;
+    for (int i = 0; i < 10; i = i + 1) {
+        if (i == 3) continue;
+        if (i == 8) break;
+        s = s + i;
+    }
+    int i = 100;
+    int k = 0;
+    do {
+        k = k + 1;
+        if (k == 2) continue;
+    } while (k < 5);
+    return s + i + k;
+}
+";
+
+// for里的continue要先走step再回到条件
+#[test]
+fn for_continue_runs_step() {
+    let output = compile("for_continue_runs_step", LOOPS);
+    assert_compiles(&output);
+    let koopa = &output.text;
+    assert!(koopa.contains("%then1:\n  jump %while_step1\n"), "{}", koopa);
+    assert!(koopa.contains("%then2:\n  jump %while_end1\n"), "{}", koopa);
+    assert!(koopa.contains("%while_step1:\n  %9 = load @i_1\n  %10 = add %9, 1\n  store %10, @i_1\n  jump %while_entry1\n"), "{}", koopa);
+    // init里声明的i只在循环里, 后面的i是另一个变量
+    assert!(koopa.contains("%while_end1:\n  @i_2 = alloc i32\n  store 100, @i_2\n"), "{}", koopa);
+}
+
+// do-while先进循环体, continue跳到条件
+#[test]
+fn do_while_enters_body_first() {
+    let output = compile("do_while_enters_body_first", LOOPS);
+    assert_compiles(&output);
+    let koopa = &output.text;
+    assert!(koopa.contains("store 0, @k_1\n  jump %while_body2\n"), "{}", koopa);
+    assert!(koopa.contains("%then3:\n  jump %while_entry2\n"), "{}", koopa);
+    assert!(koopa.contains("br %16, %while_body2, %while_end2"), "{}", koopa);
+}
+
+#[test]
+fn empty_for_head() {
+    let output = compile("empty_for_head", "int main() { int n = 0; for (;;) { n = n + 1; if (n > 3) break; } return n; }\n");
+    assert_compiles(&output);
+    assert!(output.text.contains("%while_entry1:\n  jump %while_body1\n"), "{}", output.text);
+}
+
+#[test]
+fn loop_errors() {
+    let output = compile("break_outside_loop", "int main() { break; return 0; }\n");
+    assert_fails_with(&output, "error[E0401]: break statement not in loop or switch");
+    let output = compile("continue_outside_loop", "int main() { continue; return 0; }\n");
+    assert_fails_with(&output, "error[E0402]: continue statement not in loop");
+    // for的init里的变量出了循环就没有了
+    let output = compile("for_scope", "int main() { for (int i = 0; i < 2; i = i + 1) {} return i; }\n");
+    assert_fails_with(&output, "error[E0101]: i undeclared");
+}
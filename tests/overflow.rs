@@ -0,0 +1,62 @@
+mod common;
+use common::*;
+
+#[test]
+fn int_min_literal() {
+    let output = compile("int_min_literal", "int main() { int a = -2147483648; return a == -2147483647 - 1; }\n");
+    assert_compiles(&output);
+    assert!(output.stderr.is_empty(), "{}", output.stderr);
+    assert!(output.text.contains("store -2147483648, @a_1"), "{}", output.text);
+    assert!(output.text.contains("eq %0, -2147483648"), "{}", output.text);
+}
+
+// 十六进制的字面量按u32读, 和C一样不算溢出
+#[test]
+fn hex_literals_wrap_silently() {
+    let src = "const int H = 0x80000000;\nint main() {\n  int d = 0xffffffff;\n  return d + H;\n}\n";
+    let output = compile("hex_literals_wrap_silently", src);
+    assert_compiles(&output);
+    assert!(output.stderr.is_empty(), "{}", output.stderr);
+    assert!(output.text.contains("store -1, @d_1"), "{}", output.text);
+    assert!(output.text.contains("add %0, -2147483648"), "{}", output.text);
+}
+
+const OVERFLOW: &str = "const int MIN = -2147483648;
+const int M = 65536 * 65536;
+int arr[2] = {MIN % -1, -MIN};
+int main() {
+    int a = MIN / -1;
+    int b = 2147483647 + 1;
+    int c = 4294967297;
+    return a + b + c + M;
+}
+";
+
+#[test]
+fn overflowing_constants() {
+    let output = compile("overflowing_constants", OVERFLOW);
+    assert!(output.success, "{}", output.stderr);
+    assert!(koopa_is_valid(&output.text), "{}", output.text);
+    assert_eq!(
+        output.headers(),
+        [
+            "warning[W0001]: integer overflow in constant expression (mul 65536, 65536) [-Woverflow]",
+            "warning[W0001]: integer overflow in constant expression (mod -2147483648, -1) [-Woverflow]",
+            "warning[W0001]: integer overflow in constant expression (sub 0, -2147483648) [-Woverflow]",
+            "warning[W0001]: integer overflow in constant expression (div -2147483648, -1) [-Woverflow]",
+            "warning[W0001]: integer overflow in constant expression (add 2147483647, 1) [-Woverflow]",
+            "warning[W0002]: integer constant 4294967297 is too large for int, truncated to 1 [-Woverflow]",
+        ]
+    );
+    // 和RISC-V一样按32位回绕
+    assert!(output.text.contains("global @arr_1 = alloc [i32, 2], {0, -2147483648}"), "{}", output.text);
+    assert!(output.text.contains("store -2147483648, @a_1"), "{}", output.text);
+    assert!(output.text.contains("store -2147483648, @b_1"), "{}", output.text);
+    assert!(output.text.contains("store 1, @c_1"), "{}", output.text);
+}
+
+#[test]
+fn division_by_zero() {
+    let output = compile("division_by_zero", "int main() { const int z = 1 / 0; return z; }\n");
+    assert_fails_with(&output, "error[E0505]: division by zero in constant expression");
+}
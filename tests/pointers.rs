@@ -0,0 +1,120 @@
+mod common;
+use common::*;
+
+const SWAP: &str = "int g;
+int *gp = 0;
+void swap(int *a, int *b) { int t = *a; *a = *b; *b = t; }
+int sum(int *p, int n) {
+  int s = 0;
+  while (n) { s += *p; p++; n--; }
+  return s;
+}
+int main() {
+  int x = 1, y = 2;
+  int *p = &x;
+  *p = 3;
+  swap(&x, &y);
+  int arr[4] = {1, 2, 3, 4};
+  int m[2][3] = {{1, 2, 3}, {4, 5, 6}};
+  int *q = &m[1][0];
+  int *r = arr + 1;
+  int **pp = &r;
+  gp = &g;
+  *gp = 5;
+  return x * 1000 + y * 100 + sum(arr, 4) + *(q + 2) + r[1] + **pp + g;
+}
+";
+
+#[test]
+fn address_of_and_dereference() {
+    let output = compile("address_of_and_dereference", SWAP);
+    assert_compiles(&output);
+    let koopa = normalize(&output.text);
+    assert!(koopa.contains("global @gp_1 = alloc *i32, zeroinit"), "{}", koopa);
+    assert!(koopa.contains("fun @swap(@a_0: *i32, @b_0: *i32) {"), "{}", koopa);
+    // *a = *b: 先算左边的地址, 再读右边
+    assert!(koopa.contains("%_ = load @a_1\n  %_ = load @b_1\n  %_ = load %_\n  store %_, %_\n"), "{}", koopa);
+    assert!(koopa.contains("@pp_1 = alloc **i32\n  %_ = getptr @r_1, 0\n  store %_, @pp_1\n"), "{}", koopa);
+    assert!(koopa.contains("%_ = load @pp_1\n  %_ = load %_\n  %_ = load %_\n"), "{}", koopa);
+}
+
+// 指针加整数按元素的大小缩放, 指针自增也一样
+#[test]
+fn pointer_arithmetic() {
+    let output = compile("pointer_arithmetic", SWAP);
+    assert_compiles(&output);
+    let koopa = normalize(&output.text);
+    assert!(koopa.contains("%_ = load @p_1\n  %_ = getptr %_, 1\n  store %_, @p_1\n"), "{}", koopa);
+    assert!(koopa.contains("%_ = getelemptr @arr_1, 0\n  %_ = getptr %_, 1\n  store %_, @r_1\n"), "{}", koopa);
+    assert!(koopa.contains("%_ = load @q_1\n  %_ = getptr %_, 2\n  %_ = load %_\n"), "{}", koopa);
+    let src = "int at(int **p, int i) { return **(p + i); }\nint main() { int x = 7; int *p = &x; return at(&p, 0); }\n";
+    let output = compile_riscv("pointer_arithmetic_riscv", src);
+    assert!(output.success, "{}", output.stderr);
+    // 指针本身也占4个字节
+    assert!(output.text.contains("li t2, 4\n  mul t1, t1, t2"), "{}", output.text);
+}
+
+#[test]
+fn type_errors() {
+    for (name, body, message) in [
+        ("int_to_pointer", "int *p = a;", "error[E0201]: cannot convert i32 to *i32"),
+        ("pointer_kinds", "int *p = &a;\n  float *fp = p;", "error[E0201]: cannot convert *i32 to *f32"),
+        ("address_of_rvalue", "int *q = &(a + 1);", "error[E0301]: lvalue required as unary '&' operand"),
+        ("deref_int", "int v = *a;", "error[E0202]: invalid type argument of unary '*' (have i32)"),
+        ("subscript_int", "int w = a[0];", "error[E0204]: subscripted value a is neither array nor pointer"),
+        ("pointer_in_const", "int *p = &a;\n  const int c = *p;", "error[E0502]: pointer operation occurred in const init val"),
+        ("pointer_array_init", "int *arr[2] = {&a, &a};", "error[E0502]: arrays of pointers or structs cannot have an initializer"),
+        ("add_two_pointers", "int *p = &a;\n  p + p;", "error[E0202]: invalid operands to add (have *i32 and *i32)"),
+    ] {
+        let src = format!("int main() {{\n  int a = 1;\n  {}\n  return 0;\n}}\n", body);
+        let output = compile(name, &src);
+        assert_fails_with(&output, message);
+    }
+}
+
+const POINTERS: &str = "int *gp = 0;
+int first(int *p, int a[][3]) {
+    if (p == 0) return -1;
+    return p[0];
+}
+int main() {
+    int x = 5;
+    int m[2][3];
+    int *p = &x;
+    int *q = 0;
+    int n = 0;
+    if (p) n = n + 1;
+    if (!q) n = n + 10;
+    if (p != q && gp == q) n = n + 100;
+    q = p;
+    if (q == p) n = n + 1000;
+    int r = first(0, m) + first(p, 0);
+    return n + r;
+}
+";
+
+#[test]
+fn compare_and_null() {
+    let output = compile("compare_and_null", POINTERS);
+    assert_compiles(&output);
+    assert!(output.text.contains("decl @__ptoi_pi32(*i32): i32"), "{}", output.text);
+    assert!(output.text.contains("decl @__null_pai32_3_(): *[i32, 3]"), "{}", output.text);
+    let output = compile_files("compare_and_null_riscv", "-riscv", &[("a.c", POINTERS)], &[]);
+    assert!(output.success, "{}", output.stderr);
+    assert!(!output.text.contains("call __ptoi") && !output.text.contains("call __null"), "{}", output.text);
+}
+
+#[test]
+fn nonzero_integer_to_pointer() {
+    let output = compile("nonzero_integer_to_pointer", "int main() { int *p = 1; return 0; }\n");
+    assert_fails_with(&output, "error[E0201]: cannot convert i32 to *i32");
+}
+
+#[test]
+fn unsupported_comparisons() {
+    let src = "int main() { int x; float y; int *p = &x; float *f = &y; return (p < p) + (p == f) + (p == 1); }\n";
+    let output = compile("unsupported_comparisons", src);
+    assert_fails_with(&output, "error[E0202]: invalid operands to lt (have *i32 and *i32)");
+    assert!(output.has("error[E0202]: invalid operands to eq (have *i32 and *f32)"), "{}", output.stderr);
+    assert!(output.has("error[E0202]: invalid operands to eq (have *i32 and i32)"), "{}", output.stderr);
+}
@@ -0,0 +1,88 @@
+mod common;
+use common::*;
+
+const UTIL_H: &str = "#ifndef UTIL_H
+#define UTIL_H
+#define SQ(x) ((x) * (x))
+const int N = 3;
+#endif
+";
+
+const MAIN: &str = "#include \"util.h\"
+#include \"util.h\"
+#include \"sylib.h\"
+#define DEBUG 0
+#define CAT(a, b) a ## b
+#define MAX(a, b) ((a) > (b) ? (a) : (b))
+int main() {
+  int CAT(my, var) = MAX(2, SQ(2));
+#if DEBUG
+  putint(1);
+#elif defined(SQ) && N == 3
+  putint(SQ(N + 1));
+#else
+  putint(2);
+#endif
+#ifdef UNDEFINED
+  return 1;
+#endif
+  return myvar; /* 注释 */ // 注释
+}
+";
+
+#[test]
+fn include_define_and_conditionals() {
+    let output = compile_files("include_define_and_conditionals", "-koopa", &[("util.h", UTIL_H), ("a.c", MAIN)], &[]);
+    assert_compiles(&output);
+    assert!(output.stderr.is_empty(), "{}", output.stderr);
+    // 头文件有include guard, N只声明一次; 宏展开之后常量折叠
+    assert!(output.text.contains("store 4, @myvar_1"), "{}", output.text);
+    // #if里的N不是宏, 当成0, 所以走#else
+    assert!(output.text.contains("call @putint(2)"), "{}", output.text);
+    assert!(!output.text.contains("call @putint(1)") && !output.text.contains("ret 1"), "{}", output.text);
+}
+
+// starttime()和stoptime()带上所在的行号, #x变成字符串
+#[test]
+fn timing_macros_and_line() {
+    let src = "#define STR(x) #x\nint main() {\n  starttime();\n  putf(STR(line %d\\n), __LINE__);\n  stoptime();\n  return 0;\n}\n";
+    let output = compile("timing_macros_and_line", src);
+    assert_compiles(&output);
+    let koopa = &output.text;
+    assert!(koopa.contains("decl @_sysy_starttime(i32)\ndecl @_sysy_stoptime(i32)\n"), "{}", koopa);
+    assert!(koopa.contains("call @_sysy_starttime(3)"), "{}", koopa);
+    assert!(koopa.contains("call @__putf_i(%0, 4)"), "{}", koopa);
+    assert!(koopa.contains("call @_sysy_stoptime(5)"), "{}", koopa);
+}
+
+#[test]
+fn directive_errors() {
+    let src = "#include \"missing.h\"\n#if 1\n#error stop here\n#endif\n#warning careful\n#bogus\n#endif\n#define F(x\nint main() { return 0; }\n";
+    let output = compile("directive_errors", src);
+    assert!(!output.success);
+    assert_eq!(
+        output.headers(),
+        [
+            "error[E0702]: missing.h: No such file or directory",
+            "error[E0703]: #error stop here",
+            "warning[W0003]: #warning careful [-Wcpp]",
+            "error[E0701]: invalid preprocessing directive #bogus",
+            "error[E0701]: #endif without #if",
+            "error[E0701]: missing ')' in macro parameter list",
+        ]
+    );
+    // 预处理的报错只有行号
+    assert!(output.stderr.contains("error[E0703]: #error stop here\n --> a.c:3\n"), "{}", output.stderr);
+    let output = compile("unterminated_if", "#if 1\nint main() { return 0; }\n");
+    assert_fails_with(&output, "error[E0701]: unterminated #if");
+    assert!(output.stderr.contains(" --> a.c:2\n"), "{}", output.stderr);
+}
+
+// 语法错误的位置是头文件里的行, 不是拼起来以后的行
+#[test]
+fn syntax_errors_use_line_map() {
+    let header = "int f() {\n  return 1 +;\n}\n";
+    let output = compile_files("syntax_errors_use_line_map", "-koopa", &[("f.h", header), ("a.c", "#include \"f.h\"\nint main() { return f(); }\n")], &[]);
+    assert_fails_with(&output, "error[E0001]: expected expression, found `;`");
+    assert!(output.stderr.contains(" --> f.h:2:13\n"), "{}", output.stderr);
+}
@@ -0,0 +1,63 @@
+mod common;
+use common::*;
+
+const BROKEN: &str = "int f() {
+    int a = 1
+    return a;
+}
+int g() { return (1 + ; }
+int main() {
+    int b = 2 $ 3;
+    return f() + g();
+}
+";
+
+#[test]
+fn reports_every_syntax_error() {
+    let output = compile("reports_every_syntax_error", BROKEN);
+    assert_fails_with(&output, "error[E0001]: expected `;` after expression, found `return`");
+    assert!(output.has("error[E0001]: expected expression, found `;`"), "{}", output.stderr);
+    assert!(output.has("error[E0001]: invalid token"), "{}", output.stderr);
+    // 跳过出错的地方以后f和g还在, 不会再报找不到
+    assert_eq!(output.headers().len(), 3, "{}", output.stderr);
+    assert!(output.stderr.contains(" --> a.c:7:15\n"), "{}", output.stderr);
+}
+
+#[test]
+fn dangling_else() {
+    let src = "int main() {\n    int a = 0;\n    if (a) if (a - 1) a = 2; else a = 3;\n    return a;\n}\n";
+    let output = compile("dangling_else", src);
+    assert_compiles(&output);
+    // else跟着里面那个if
+    assert!(output.text.contains("br %0, %then1, %end1"), "{}", output.text);
+    assert!(output.text.contains("br %2, %then2, %else2"), "{}", output.text);
+}
+
+// 每个例子只有一处错, 恢复以后不会再多报
+#[test]
+fn readable_expected_tokens() {
+    for (name, src, header, location) in [
+        ("missing_paren", "int main() { if (1 { return 0; } return 1; }\n", "error[E0001]: expected `)`, found `{`", "a.c:1:20"),
+        ("trailing_comma", "int f(int a,) { return a; }\nint main() { return f(1); }\n", "error[E0001]: expected `float`, `int` or `struct`, found `)`", "a.c:1:13"),
+        ("global_semicolon", "int x = 1\nint main() { return x; }\n", "error[E0001]: expected `;` after expression, found `int`", "a.c:1:10"),
+        ("break_semicolon", "int main() { while (1) break return 0; }\n", "error[E0001]: expected `;`, found `return`", "a.c:1:29"),
+        ("extra_brace", "int main() { return 0; }\n}\n", "error[E0001]: unexpected `}`", "a.c:2:1"),
+        ("multibyte_token", "int main() {\n  int 变量 = 1;\n  return 0;\n}\n", "error[E0001]: invalid token", "a.c:2:7"),
+    ] {
+        let output = compile(name, src);
+        assert_fails_with(&output, header);
+        assert_eq!(output.headers().len(), 1, "{}: {}", name, output.stderr);
+        assert!(output.stderr.contains(&format!(" --> {}\n", location)), "{}: {}", name, output.stderr);
+    }
+}
+
+// 同一个语句里的两处错都能报出来
+#[test]
+fn two_errors_in_one_function() {
+    let output = compile("two_errors_in_one_function", "int main() { int x = 1; x = ; return x }\n");
+    assert_eq!(
+        output.headers(),
+        ["error[E0001]: expected expression, found `;`", "error[E0001]: expected `;`, found `}`"]
+    );
+    assert!(output.text.is_empty(), "{}", output.text);
+}
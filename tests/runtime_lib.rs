@@ -0,0 +1,42 @@
+mod common;
+use common::*;
+
+#[test]
+fn only_used_functions_are_declared() {
+    let src = "int main() {\n    int a[10];\n    int n = getarray(a);\n    putarray(n, a);\n    putch(10);\n    return getint();\n}\n";
+    let output = compile("only_used_functions_are_declared", src);
+    assert_compiles(&output);
+    let decls: Vec<&str> = output.text.lines().filter(|line| line.starts_with("decl ")).collect();
+    assert_eq!(
+        decls,
+        ["decl @getint(): i32", "decl @getarray(*i32): i32", "decl @putch(i32)", "decl @putarray(i32, *i32)"]
+    );
+}
+
+#[test]
+fn no_declarations_without_calls() {
+    let output = compile("no_declarations_without_calls", "int main() { return 0; }\n");
+    assert_compiles(&output);
+    assert!(!output.text.contains("decl "), "{}", output.text);
+}
+
+// 自己定义了同名的函数就用自己的, 不再声明库函数
+#[test]
+fn user_definition_replaces_library() {
+    let output = compile("user_definition_replaces_library", "int getint() { return 3; }\nint main() { return getint(); }\n");
+    assert_compiles(&output);
+    assert!(!output.text.contains("decl @getint"), "{}", output.text);
+    assert!(output.text.contains("fun @getint(): i32"), "{}", output.text);
+}
+
+#[test]
+fn argument_checks() {
+    let output = compile("argument_checks", "int main() { putint(1, 2); int x = 1; putarray(2, x); return getch(3); }\n");
+    for message in [
+        "error[E0601]: putint takes 1 arguments but 2 were supplied",
+        "error[E0602]: argument 2 of putarray expects *i32 but got i32",
+        "error[E0601]: getch takes 0 arguments but 1 were supplied",
+    ] {
+        assert_fails_with(&output, message);
+    }
+}
@@ -0,0 +1,79 @@
+mod common;
+use common::*;
+
+const SHADOWING: &str = "int main() {
+    int x = 1;
+    {
+        int x = 2;
+        x = x + 1;
+    }
+    int i = 0;
+    while (i < 3) { int x = i; i = i + x + 1; if (i == 2) continue; }
+    return x;
+}
+";
+
+#[test]
+fn resolves_shadowed_names() {
+    let output = compile("resolves_shadowed_names", SHADOWING);
+    assert_compiles(&output);
+    // 每个x解析到自己的声明
+    assert!(output.text.contains("%0 = load @x_2\n  %1 = add %0, 1\n  store %1, @x_2"), "{}", output.text);
+    assert!(output.text.contains("@x_3 = alloc i32"), "{}", output.text);
+    assert!(output.text.contains("load @x_1\n  ret"), "{}", output.text);
+}
+
+#[test]
+fn reports_semantic_errors_together() {
+    let src = "int g = 1;\nconst int C = g;\nint main() {\n    int a[2];\n    continue;\n    3 = a[0];\n    while (1) { break; }\n    return a[1];\n}\n";
+    let output = compile("reports_semantic_errors_together", src);
+    assert_fails_with(&output, "error[E0502]: variables occurred in const init val");
+    assert!(output.has("error[E0402]: continue statement not in loop"), "{}", output.stderr);
+    assert!(output.has("error[E0301]: lvalue required as left operand of assignment"), "{}", output.stderr);
+    assert!(output.stderr.contains(" --> a.c:5:5\n"), "{}", output.stderr);
+}
+
+#[test]
+fn lvalue_rules() {
+    for (name, body) in [
+        ("whole_array", "int a[2];\n    a = 1;"),
+        ("partial_index", "int m[2][2];\n    m[0] = 1;"),
+        ("rvalue", "int a = 1;\n    (a + 1) = 2;"),
+        ("post_increment", "int x = 1;\n    x++ = 2;"),
+    ] {
+        let src = format!("int main() {{\n    {}\n    return 0;\n}}\n", body);
+        let output = compile(name, &src);
+        assert_fails_with(&output, "error[E0301]: lvalue required as left operand of assignment");
+        assert_eq!(output.headers().len(), 1, "{}: {}", name, output.stderr);
+    }
+    // *p 和下标取到底的元素都可以赋值
+    let output = compile("assignable", "int main() {\n    int a, m[2][2];\n    int *p = &a;\n    *p = 1;\n    m[1][0] = a;\n    return m[1][0];\n}\n");
+    assert_compiles(&output);
+}
+
+#[test]
+fn name_resolution_errors() {
+    let output = compile("undeclared", "int main() { return y; }\n");
+    assert_fails_with(&output, "error[E0101]: y undeclared");
+    let output = compile("assign_to_function", "int f(int a) { return a; }\nint main() { f = 1; return 0; }\n");
+    assert_fails_with(&output, "error[E0103]: f is a function");
+}
+
+// switch里的continue跳到外面的循环
+#[test]
+fn continue_through_switch() {
+    let src = "int main() {\n    int i, n = 0;\n    for (i = 0; i < 4; i++) {\n        switch (i) { case 1: continue; }\n        n += i;\n    }\n    return n;\n}\n";
+    let output = compile("continue_through_switch", src);
+    assert_compiles(&output);
+    assert!(output.text.contains("%case1_0:\n  jump %while_step1\n"), "{}", output.text);
+}
+
+// 类型错误在生成IR的时候才查, 语义分析有错误的时候还报不出来
+#[test]
+fn type_errors_wait_for_sema() {
+    let output = compile("type_error_hidden", "int main() {\n    int a[2];\n    y = 1;\n    return a * 2;\n}\n");
+    assert_fails_with(&output, "error[E0101]: y undeclared");
+    assert_eq!(output.headers().len(), 1, "{}", output.stderr);
+    let output = compile("type_error_reported", "int main() {\n    int a[2], y;\n    y = 1;\n    return a * 2;\n}\n");
+    assert_fails_with(&output, "error[E0202]");
+}
@@ -0,0 +1,32 @@
+mod common;
+use common::*;
+
+// main里第一次调用f之前要先有条件跳转
+fn branches_before_call(text: &str) -> bool {
+    let main = &text[text.find("fun @main").unwrap()..];
+    matches!((main.find("br "), main.find("call @f")), (Some(br), Some(call)) if br < call)
+}
+
+#[test]
+fn and_in_initializer() {
+    let output = compile("and_in_initializer", "int f() { return 1; }\nint main() { int y = 0 && f(); return y; }\n");
+    assert_compiles(&output);
+    assert!(branches_before_call(&output.text), "{}", output.text);
+}
+
+#[test]
+fn guarded_division() {
+    let src = "int f() { return 1; }\nint main() { int x = 0; int r = x != 0 && 10 / x; r = r || f(); return r; }\n";
+    let output = compile("guarded_division", src);
+    assert_compiles(&output);
+    // 除法在短路跳过去的那个基本块里
+    let main = &output.text[output.text.find("fun @main").unwrap()..];
+    assert!(main.find("br ").unwrap() < main.find(" div ").unwrap(), "{}", output.text);
+    assert!(branches_before_call(&output.text), "{}", output.text);
+}
+
+#[test]
+fn nested_in_condition() {
+    let src = "int f() { return 1; }\nint main() { int x = 1; if (f() && (x || f())) return (x && f()) + 1; return 0; }\n";
+    assert_compiles(&compile("nested_in_condition", src));
+}
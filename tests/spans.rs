@@ -0,0 +1,36 @@
+mod common;
+use common::*;
+
+// 列号按字符数算, 中文字符算一列
+#[test]
+fn columns_count_characters() {
+    let output = compile("columns_count_characters", "// 中文注释\nint main() { putf(\"中\"); return 1 +; }\n");
+    assert_fails_with(&output, "error[E0001]: expected expression, found `;`");
+    assert!(output.stderr.contains(" --> a.c:2:35\n"), "{}", output.stderr);
+    let output = compile("invalid_token", "int main() {\n  int 变量 = 1;\n  return 0;\n}\n");
+    assert_fails_with(&output, "error[E0001]: invalid token");
+    assert!(output.stderr.contains(" --> a.c:2:7\n"), "{}", output.stderr);
+}
+
+#[test]
+fn extra_and_missing_tokens() {
+    let output = compile("extra_brace", "int main() { return 0; } }");
+    assert_fails_with(&output, "error[E0001]: unexpected `}`");
+    assert!(output.stderr.contains(" --> a.c:1:26\n"), "{}", output.stderr);
+    // 文件末尾多出来的空行不在行号表里, 算到最后一行
+    let output = compile("unexpected_eof", "int main() {\n  return 0;\n");
+    assert_fails_with(&output, "error[E0001]: expected `;` after expression, found end of file");
+    assert!(output.stderr.contains(" --> a.c:2:12\n"), "{}", output.stderr);
+}
+
+// 展开宏以后行号还是原来的行, include进来的代码报头文件的位置
+#[test]
+fn positions_after_preprocessing() {
+    let files = [
+        ("defs.h", "#define ONE 1\n\nint helper() { return ONE; }\n"),
+        ("a.c", "#include \"defs.h\"\n#define TWO (ONE + ONE)\nint main() {\n  int x = TWO;\n  x = x $ 1;\n  return x;\n}\n"),
+    ];
+    let output = compile_files("positions_after_preprocessing", "-koopa", &files, &[]);
+    assert_fails_with(&output, "error[E0001]: invalid token");
+    assert!(output.stderr.contains(" --> a.c:5:9\n"), "{}", output.stderr);
+}
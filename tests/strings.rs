@@ -0,0 +1,71 @@
+mod common;
+use common::*;
+
+const PUTF: &str = "int main() {
+  int c = 'A', nl = '\\n';
+  float f = 1.5;
+  putf(\"c=%d f=%f\\n\", c, f);
+  putf(\"c=%d f=%f\\n\", nl, f);
+  putf(\"%d %d %d %d %d %d %d %d\\n\", 1, 2, 3, 4, 5, 6, 7, 8);
+  putf(\"plain\\n\");
+  return c;
+}
+";
+
+// 字符串按小端每4个字节拼成一个i32, 一样的格式串只存一份
+#[test]
+fn literals_become_globals() {
+    let output = compile("literals_become_globals", PUTF);
+    assert_compiles(&output);
+    let koopa = &output.text;
+    assert!(koopa.contains("global @__str0 = alloc [i32, 3], {1680162147, 624780832, 2662}\n"), "{}", koopa);
+    assert!(koopa.contains("global @__str2 = alloc [i32, 2], {1767992432, 2670}\n"), "{}", koopa);
+    assert!(!koopa.contains("@__str3"), "{}", koopa);
+    assert_eq!(koopa.matches("getelemptr @__str0, 0").count(), 2, "{}", koopa);
+    // 字符字面量就是int
+    assert!(koopa.contains("store 65, @c_1\n") && koopa.contains("store 10, @nl_1\n"), "{}", koopa);
+}
+
+// 每种变参组合声明一次
+#[test]
+fn one_declaration_per_signature() {
+    let output = compile("one_declaration_per_signature", PUTF);
+    assert_compiles(&output);
+    let decls: Vec<&str> = output.text.lines().filter(|line| line.starts_with("decl")).collect();
+    assert_eq!(
+        decls,
+        [
+            "decl @__putf_if(*i32, i32, i32)",
+            "decl @__putf_iiiiiiii(*i32, i32, i32, i32, i32, i32, i32, i32, i32)",
+            "decl @__putf_(*i32)",
+        ]
+    );
+}
+
+#[test]
+fn variadic_lowering() {
+    let output = compile_riscv("variadic_lowering", PUTF);
+    assert!(output.success, "{}", output.stderr);
+    let asm = &output.text;
+    assert!(asm.starts_with("  .section .rodata\n  .globl __str0\n__str0:\n"), "{}", asm);
+    assert!(!asm.contains("__putf_"), "{}", asm);
+    assert_eq!(asm.matches("call putf").count(), 4, "{}", asm);
+    // float变成double, 对齐到a2/a3; 第9个字放到栈上
+    assert!(asm.contains("  mv a2, t1\n  mv a3, t2\n  call putf\n"), "{}", asm);
+    assert!(asm.contains("  sw t0, 0(sp)\n  call putf\n"), "{}", asm);
+}
+
+#[test]
+fn misused_strings_and_putf() {
+    for (name, body, message) in [
+        ("value_used", "int x = putf(\"a\");", "error[E0203]: putf returns void, its value cannot be used"),
+        ("format_not_literal", "int f = 1;\n  putf(f);", "error[E0605]: the first argument of putf must be a string literal"),
+        ("array_argument", "int a[2];\n  putf(\"%d\", a);", "error[E0605]: arrays and pointers cannot be passed to putf"),
+        ("string_as_value", "int y = \"s\";", "error[E0605]: string literals can only be used as the format of putf"),
+        ("string_in_const", "const int z = \"t\";", "error[E0502]: string literal occurred in const init val"),
+    ] {
+        let src = format!("int main() {{\n  {}\n  return 0;\n}}\n", body);
+        let output = compile(name, &src);
+        assert_fails_with(&output, message);
+    }
+}
@@ -0,0 +1,133 @@
+mod common;
+use common::*;
+
+const RECORD: &str = "struct P { int x; float y; int v[2]; };
+struct P g = {1, 2.5, {3, 4}};
+int norm(struct P *p) { return p->x + p->v[1]; }
+int main() {
+  struct P a = {5, 1.5};
+  struct P b;
+  b = a;
+  b.x = 10;
+  b.v[0] = 7;
+  struct P *pb = &b;
+  pb->v[1] = 3;
+  return norm(&b) * 100 + a.x * 10 + g.v[1] + b.y;
+}
+";
+
+// 每个成员占一个字, 结构体就是一个i32数组, 成员按偏移取
+#[test]
+fn layout_and_member_access() {
+    let output = compile("layout_and_member_access", RECORD);
+    assert_compiles(&output);
+    let koopa = &output.text;
+    assert!(koopa.contains("global @g_1 = alloc [i32, 4], {1, 1075838976, 3, 4}\n"), "{}", koopa);
+    assert!(koopa.contains("fun @norm(@p_0: *[i32, 4]): i32 {"), "{}", koopa);
+    // p->v[1]在第3个字, b.y是float
+    assert!(koopa.contains("  %3 = load @p_1\n  %4 = getelemptr %3, 3\n"), "{}", koopa);
+    assert!(koopa.contains("  %39 = getelemptr @b_1, 1\n  %40 = load %39\n  %41 = call @__itof(%38)\n  %42 = call @__fadd(%41, %40)\n"), "{}", koopa);
+}
+
+// 结构体赋值是逐个成员复制
+#[test]
+fn assignment_copies() {
+    let output = compile("assignment_copies", RECORD);
+    assert_compiles(&output);
+    let koopa = normalize(&output.text);
+    for i in 0..4 {
+        let copy = format!("%_ = getelemptr @a_1, {}\n  %_ = load %_\n  %_ = getelemptr @b_1, {}\n  store %_, %_\n", i, i);
+        assert!(koopa.contains(&copy), "missing copy of word {}:\n{}", i, koopa);
+    }
+    // 初值没给全的成员补0
+    assert!(koopa.contains("%_ = getptr %_, 3\n  store 0, %_\n  @b_1 = alloc [i32, 4]"), "{}", koopa);
+}
+
+#[test]
+fn bad_definitions() {
+    let src = "struct P { int x; };\nstruct P { int q; };\nstruct E { };\nstruct D { int x; int x; };\nint f(struct P p) { return 0; }\nint main() {\n  struct Z z;\n  return 0;\n}\n";
+    let output = compile("bad_definitions", src);
+    assert!(!output.success);
+    assert_eq!(
+        output.headers(),
+        [
+            "warning[W0005]: unused variable z [-Wunused-variable]",
+            "error[E0504]: redefinition of struct P",
+            "error[E0504]: struct E has no members",
+            "error[E0504]: duplicate member x in struct D",
+            "error[E0604]: struct parameter p must be passed by pointer",
+            "error[E0503]: storage size of z isn't known (struct Z is not defined)",
+        ]
+    );
+    let output = compile("const_struct", "struct P { int x; };\nint main() {\n  const struct P c = {1};\n  return 0;\n}\n");
+    assert_fails_with(&output, "error[E0504]: const structs are not supported");
+}
+
+#[test]
+fn bad_member_uses() {
+    for (name, body, message) in [
+        ("assign_int", "a = 1;", "error[E0201]: incompatible types when assigning to type struct P"),
+        ("as_value", "int i = a;", "error[E0203]: struct P cannot be used as a value"),
+        ("unknown_member", "a.nope = 1;", "error[E0205]: struct P has no member named nope"),
+        ("member_of_array", "struct P arr[2];\n  arr.x = 1;", "error[E0205]: request for member in arr, which is an array"),
+        ("subscript_scalar", "a.x[0] = 1;", "error[E0204]: subscripted member x is not an array"),
+    ] {
+        let src = format!("struct P {{ int x; int v[2]; }};\nint main() {{\n  struct P a;\n  {}\n  return 0;\n}}\n", body);
+        let output = compile(name, &src);
+        assert_fails_with(&output, message);
+    }
+    // 多出来的初值只是警告
+    let output = compile("excess_init", "struct P { int x; };\nint main() {\n  struct P b = {1, 2};\n  return b.x;\n}\n");
+    assert!(output.success, "{}", output.stderr);
+    assert_eq!(output.headers(), ["warning[W0004]: excess elements in struct initializer [-Wexcess-initializers]"]);
+}
+
+const NESTED: &str = "struct P { int x; float y; };
+struct T { int v[3]; struct P p; };
+struct S { int a; struct T t[2]; struct P q; };
+struct S g = {1, {{{1, 2, 3}, {4, 5.5}}}, {6, 7.5}};
+int main() {
+    struct S s = {1, 2, 3, 4, {5, 1.5}};
+    s.t[1] = g.t[0];
+    s.t[1].p = s.q;
+    struct S *ps = &s;
+    ps->t[0].p.x = s.t[1].v[2] + g.q.x;
+    return s.t[0].p.x;
+}
+";
+
+#[test]
+fn nested_members() {
+    let output = compile("nested_members", NESTED);
+    assert_compiles(&output);
+    // S占1 + 2 * 5 + 2 = 13个字, s.t[1].v[2]在第1 + 5 + 2 = 8个字
+    assert!(output.text.contains("alloc [i32, 13]"), "{}", output.text);
+    assert!(output.text.contains("getelemptr @s_1, 8"), "{}", output.text);
+}
+
+#[test]
+fn nested_member_address() {
+    let src = "struct P { int x; };\nstruct S { struct P p; };\nint main() { struct S s; struct P *q = &s.p; return q->x; }\n";
+    let output = compile("nested_member_address", src);
+    assert_fails_with(&output, "error[E0504]: cannot take the address of a member of struct type");
+}
+
+#[test]
+fn incomplete_member() {
+    let src = "struct S { struct S s; int x; };\nint main() { return 0; }\n";
+    let output = compile("incomplete_member", src);
+    assert_fails_with(&output, "error[E0503]: member s has incomplete type struct S");
+}
+
+#[test]
+fn pointer_member() {
+    let src = "struct Q { int *p; int x; };\nint main() {\n  struct Q q;\n  int y = 1;\n  q.p = &y;\n  q.x = *q.p;\n  return q.p[0] + q.x;\n}\n";
+    let output = compile("pointer_member", src);
+    assert_fails_with(&output, "error[E0504]: member p of struct Q has type *i32, pointer members are not supported");
+    // 成员还在表里, 后面用到q.p的地方不会再报没有这个成员或者类型不对
+    assert_eq!(output.headers().len(), 1, "{}", output.stderr);
+    let src = "struct N { int v; struct N *next; };\nint main() {\n  struct N a;\n  struct N *p = &a;\n  p->next = p;\n  return p->next->v;\n}\n";
+    let output = compile("self_pointer_member", src);
+    assert_fails_with(&output, "error[E0504]: member next of struct N has type *struct N, pointer members are not supported");
+    assert_eq!(output.headers().len(), 1, "{}", output.stderr);
+}
@@ -0,0 +1,103 @@
+mod common;
+use common::*;
+
+const DISPATCH: &str = "int dense(int x) {
+    int r = 0;
+    switch (x) {
+        case 0: r = 10;
+        case 1: r = r + 1; break;
+        case 2: r = 20; break;
+        case 3: return 30;
+        default: r = -1;
+    }
+    return r;
+}
+int sparse(int x) {
+    switch (x) {
+        case 1: return 1;
+        case 100: return 2;
+        case 10000: return 3;
+    }
+    return 0;
+}
+int main() { return dense(0) + dense(2) + dense(7) + sparse(100) + sparse(5); }
+";
+
+#[test]
+fn fallthrough_and_break() {
+    let output = compile("fallthrough_and_break", DISPATCH);
+    assert_compiles(&output);
+    let koopa = &output.text;
+    // case 0没有break, 落到case 1; break跳到switch后面
+    assert!(koopa.contains("store 10, @r_1\n  jump %case1_1\n"), "{}", koopa);
+    assert!(koopa.contains("store %6, @r_1\n  jump %switch_end1\n"), "{}", koopa);
+    // 没有default的时候一个都不匹配就跳过整个switch
+    assert!(koopa.contains("%switch2_3:\n  jump %switch_end2\n"), "{}", koopa);
+}
+
+// 连续的case值用.rodata里的跳转表, 超出范围的去default
+#[test]
+fn dense_cases_use_jump_table() {
+    let output = compile_riscv("dense_cases_use_jump_table", DISPATCH);
+    assert!(output.success, "{}", output.stderr);
+    let asm = &output.text;
+    assert!(asm.contains("li t1, 4\n  bgeu t0, t1, .Ldense_case1_4\n"), "{}", asm);
+    assert!(asm.contains(
+        "  .section .rodata\n  .p2align 2\n.Ldense_switch1_table:\n  .word .Ldense_case1_0\n  .word .Ldense_case1_1\n  .word .Ldense_case1_2\n  .word .Ldense_case1_3\n  .text\n"
+    ), "{}", asm);
+    assert!(asm.contains("jr t1"), "{}", asm);
+}
+
+#[test]
+fn sparse_cases_compare() {
+    let output = compile_riscv("sparse_cases_compare", DISPATCH);
+    assert!(output.success, "{}", output.stderr);
+    let sparse = &output.text[output.text.find("sparse:").unwrap()..];
+    assert!(!sparse.contains(".rodata"), "{}", sparse);
+    for case in ["case2_0", "case2_1", "case2_2"] {
+        assert!(sparse.contains(&format!("beq t0, t1, .Lsparse_{}", case)), "{}", sparse);
+    }
+}
+
+#[test]
+fn bad_labels() {
+    let src = "int main() {\n  int x = 1;\n  switch (x) {\n    case 1: break;\n    case 1: break;\n    default: break;\n    default: break;\n  }\n  return 0;\n}\n";
+    let output = compile("bad_labels", src);
+    assert_eq!(
+        output.headers(),
+        ["error[E0404]: duplicate case value 1", "error[E0404]: multiple default labels in one switch"]
+    );
+    assert!(!output.success);
+    // 指向后面重复的那个标号, 前面那个作为第二个位置
+    let expected = "error[E0404]: multiple default labels in one switch
+ --> a.c:7:5
+  |
+6 |     default: break;
+  |     ------- first default label here
+7 |     default: break;
+  |     ^^^^^^^
+";
+    assert!(output.stderr.contains(expected), "{}", output.stderr);
+    assert!(output.stderr.contains(" --> a.c:5:5\n  |\n4 |     case 1: break;\n  |     ------ previously used here\n"), "{}", output.stderr);
+    let src = "int main() {\n  int x = 1, y = 2;\n  switch (x) {\n    case y: break;\n  }\n  return 0;\n}\n";
+    let output = compile("non_constant_case", src);
+    assert_fails_with(&output, "error[E0502]: variables occurred in const init val");
+}
+
+// 比较链是生成IR的时候记下来传给后端的, 几个文件合起来或者分开编译都还认得出来
+#[test]
+fn chains_survive_linking() {
+    let other = "int pick(int x) {\n    switch (x) {\n        case 0: return 5;\n        case 1: return 6;\n        case 2: return 7;\n        case 3: return 8;\n    }\n    return 0;\n}\n";
+    let main = "int pick(int x);\nint main() {\n    switch (pick(1)) {\n        case 5: return 1;\n        case 6: return 2;\n        case 7: return 3;\n        default: return 4;\n    }\n}\n";
+    let files = [("a.c", main), ("b.c", other)];
+    let output = compile_files("chains_survive_linking", "-riscv", &files, &[]);
+    assert!(output.success, "{}", output.stderr);
+    assert!(output.text.contains(".Lpick_switch1_table:"), "{}", output.text);
+    assert!(output.text.contains("beq t0, t1, .Lmain_case1_1"), "{}", output.text);
+    assert!(!output.text.contains(".Lmain_switch1_1:"), "{}", output.text);
+    let output = compile_files("chains_survive_separate", "-riscv", &files, &["-c"]);
+    assert!(output.success, "{}", output.stderr);
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("chains_survive_separate/out.S");
+    let b = std::fs::read_to_string(dir.join("b.S")).unwrap();
+    assert!(b.contains(".Lpick_switch1_table:"), "{}", b);
+}
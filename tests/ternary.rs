@@ -0,0 +1,56 @@
+mod common;
+use common::*;
+
+const SRC: &str = "int f(int x) { putint(x); return x; }
+const int K = 1 ? 7 : 8;
+int main() {
+  int a = 0;
+  int r = a ? f(1) : f(2);
+  int n = (a || r) ? (r > 1 ? 10 : 20) : 30;
+  if (a ? 0 : 1) r = r + 1;
+  int arr[K > 5 ? 2 : 3];
+  return r + n + K + arr[1];
+}
+";
+
+// 只有选中的一边会被求值, 结果经过栈上的位置合并
+#[test]
+fn only_chosen_arm_runs() {
+    let output = compile("only_chosen_arm_runs", SRC);
+    assert_compiles(&output);
+    let koopa = &output.text;
+    assert!(koopa.contains(
+        "  %ternary1 = alloc i32\n  br %2, %cond_true1, %cond_false1\n%cond_true1:\n  %3 = call @f(1)\n  store %3, %ternary1\n  jump %cond_end1\n%cond_false1:\n  %4 = call @f(2)\n  store %4, %ternary1\n  jump %cond_end1\n%cond_end1:\n  %5 = load %ternary1\n"
+    ), "{}", koopa);
+}
+
+#[test]
+fn nested_and_as_condition() {
+    let output = compile("nested_and_as_condition", SRC);
+    assert_compiles(&output);
+    let koopa = &output.text;
+    // 内层的条件表达式在外层的true分支里
+    let inner = koopa.find("%cond_true2:").unwrap();
+    assert!(koopa[inner..].find("%ternary3 = alloc i32").unwrap() < koopa[inner..].find("%cond_false2:").unwrap());
+    // 条件表达式本身当if的条件
+    assert!(koopa.contains("  %16 = load %ternary4\n  br %16, %then1, %end1\n"), "{}", koopa);
+}
+
+#[test]
+fn constant_conditions_fold() {
+    let output = compile("constant_conditions_fold", SRC);
+    assert_compiles(&output);
+    assert!(output.text.contains("@arr_1 = alloc [i32, 2]"), "{}", output.text);
+    assert!(output.text.contains("%22 = add %21, 7"), "{}", output.text);
+    // 和C一样, 常量表达式里没被选中的一边也只能是常量
+    let src = "int main() {\n  int a = 1;\n  const int d = 1 ? 2 : a;\n  return d;\n}\n";
+    let output = compile("unchosen_arm_checked", src);
+    assert_fails_with(&output, "error[E0502]: variables occurred in const init val");
+}
+
+#[test]
+fn runtime_condition_in_const() {
+    let src = "int main() {\n  int a = 1;\n  const int c = a ? 1 : 2;\n  return c;\n}\n";
+    let output = compile("runtime_condition_in_const", src);
+    assert_fails_with(&output, "error[E0502]: variables occurred in const init val");
+}
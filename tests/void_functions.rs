@@ -0,0 +1,53 @@
+mod common;
+use common::*;
+
+const SRC: &str = "int g;
+void set(int x) {
+    if (x < 0) return;
+    g = x;
+}
+void nothing() {}
+int noret(int x) { if (x) return 1; }
+int main() {
+    set(3);
+    nothing();
+    return g + noret(0);
+}
+";
+
+#[test]
+fn void_definitions_and_bare_return() {
+    let output = compile("void_definitions_and_bare_return", SRC);
+    assert!(output.success, "{}", output.stderr);
+    assert!(koopa_is_valid(&output.text), "{}", output.text);
+    let koopa = normalize(&output.text);
+    assert!(koopa.contains("fun @set(@x_0: i32) {"), "{}", koopa);
+    assert!(koopa.contains("%then1:\n  ret\n%end1:"), "{}", koopa);
+    assert!(koopa.contains("fun @nothing() {\n%entry:\n  ret\n}"), "{}", koopa);
+    // 调用void函数的语句不产生值
+    assert!(koopa.contains("  call @set(3)\n  call @nothing()\n"), "{}", koopa);
+}
+
+// int函数走到头也要有返回值, 不能是ret undef
+#[test]
+fn int_function_falls_off_the_end() {
+    let output = compile("int_function_falls_off_the_end", SRC);
+    assert!(output.success, "{}", output.stderr);
+    assert_eq!(output.headers(), ["warning[W0008]: control reaches end of non-void function [-Wreturn-type]"]);
+    assert!(output.text.contains("%end2:\n  ret 0\n}"), "{}", output.text);
+    assert!(!output.text.contains("undef"), "{}", output.text);
+}
+
+#[test]
+fn misused_returns_and_values() {
+    let cases = [
+        ("void_value", "void f() {}\nint main() { int x = f(); return x; }\n", "error[E0203]: f returns void, its value cannot be used"),
+        ("void_returns_value", "void f() { return 1; }\nint main() { f(); return 0; }\n", "error[E0403]: void function should not return a value"),
+        ("int_bare_return", "int g() { return; }\nint main() { return g(); }\n", "error[E0403]: non-void function should return a value"),
+    ];
+    for (name, src, message) in cases {
+        let output = compile(name, src);
+        assert!(!output.success, "{}", name);
+        assert!(output.has(message), "{}: expected {:?}, got:\n{}", name, message, output.stderr);
+    }
+}
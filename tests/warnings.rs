@@ -0,0 +1,100 @@
+mod common;
+use common::*;
+
+#[test]
+fn constant_while_condition() {
+    let src = "int main() {\n    int i = 0;\n    while (1) { i = i + 1; if (i > 3) break; }\n    while (2) { i = i + 1; if (i > 6) break; }\n    while (-1) { break; }\n    while (0) { i = 0; }\n    while (i < 10) i = i + 1;\n    return i;\n}\n";
+    let output = compile("constant_while_condition", src);
+    assert!(output.success, "{}", output.stderr);
+    assert_eq!(
+        output.headers(),
+        [
+            "warning[W0009]: while condition is constant [-Wconstant-condition]",
+            "warning[W0009]: while condition is constant [-Wconstant-condition]",
+            "warning[W0009]: while condition is always false [-Wconstant-condition]",
+        ]
+    );
+    // 只有while (1)不报, while (2)也算写错了
+    assert!(output.stderr.contains(" --> a.c:4:12\n"), "{}", output.stderr);
+    assert!(output.stderr.contains("= help: write while (1) for an intentional infinite loop"), "{}", output.stderr);
+    assert!(output.stderr.contains(" --> a.c:5:12\n"), "{}", output.stderr);
+    assert!(output.stderr.contains(" --> a.c:6:12\n"), "{}", output.stderr);
+}
+
+const UNUSED: &str = "int f(int a) {
+    if (a) return 1;
+}
+int main() {
+    int unused = 1;
+    const int K = 2;
+    return f(0);
+}
+";
+
+#[test]
+fn default_warnings() {
+    let output = compile("default_warnings", UNUSED);
+    assert!(output.success, "{}", output.stderr);
+    assert!(koopa_is_valid(&output.text), "{}", output.text);
+    assert!(output.has("warning[W0008]: control reaches end of non-void function [-Wreturn-type]"), "{}", output.stderr);
+    assert!(output.has("warning[W0005]: unused variable unused [-Wunused-variable]"), "{}", output.stderr);
+    assert!(output.has("warning[W0006]: unused constant K [-Wunused-const-variable]"), "{}", output.stderr);
+}
+
+#[test]
+fn warning_options() {
+    let output = compile_with("warning_options", UNUSED, &["-Wno-unused-variable", "-Werror=return-type"]);
+    assert_fails_with(&output, "error[W0008]: control reaches end of non-void function [-Werror=return-type]");
+    assert!(!output.has("warning[W0005]"), "{}", output.stderr);
+    assert!(output.has("warning[W0006]"), "{}", output.stderr);
+
+    let output = compile_with("warning_options_werror", UNUSED, &["-Werror", "-Wno-error=unused-const-variable"]);
+    assert_fails_with(&output, "error[W0005]: unused variable unused [-Werror=unused-variable]");
+    assert!(output.has("warning[W0006]: unused constant K [-Wunused-const-variable]"), "{}", output.stderr);
+
+    let output = compile_with("warning_options_quiet", UNUSED, &["-w"]);
+    assert_compiles(&output);
+}
+
+#[test]
+fn unknown_warning_option() {
+    let output = compile_with("unknown_warning_option", UNUSED, &["-Wbogus"]);
+    assert!(output.success, "{}", output.stderr);
+    assert!(output.stderr.starts_with("warning: unknown warning option -Wbogus"), "{}", output.stderr);
+}
+
+#[test]
+fn unreachable_code() {
+    let src = "int main() {\n    int i = 0;\n    while (i < 3) {\n        i = i + 1;\n        continue;\n        i = 0;\n    }\n    return i;\n    i = 1;\n}\n";
+    let output = compile("unreachable_code", src);
+    assert!(output.success, "{}", output.stderr);
+    let unreachable = output
+        .headers()
+        .iter()
+        .filter(|line| line.starts_with("warning[W0007]: unreachable statement"))
+        .count();
+    assert_eq!(unreachable, 2, "{}", output.stderr);
+    assert!(output.stderr.contains(" --> a.c:6:9\n") && output.stderr.contains(" --> a.c:9:5\n"), "{}", output.stderr);
+}
+
+// 选项从左到右处理, 后面的覆盖前面的
+#[test]
+fn later_options_win() {
+    let output = compile_with("later_options_win", UNUSED, &["-Werror", "-Wno-error", "-Wno-return-type", "-Wunused-variable"]);
+    assert!(output.success, "{}", output.stderr);
+    assert_eq!(
+        output.headers(),
+        [
+            "warning[W0005]: unused variable unused [-Wunused-variable]",
+            "warning[W0006]: unused constant K [-Wunused-const-variable]",
+        ]
+    );
+}
+
+// 用到的变量和参数都不报, 没用到的参数也不报
+#[test]
+fn used_names_are_quiet() {
+    let src = "int f(int a, int b) { return a; }\nint main() {\n  const int K = 2;\n  int x = K;\n  int arr[2];\n  arr[0] = x;\n  return f(arr[0], 0);\n}\n";
+    let output = compile("used_names_are_quiet", src);
+    assert_compiles(&output);
+}